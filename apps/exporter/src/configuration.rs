@@ -1,12 +1,14 @@
 use config::{Config, ConfigError, Environment, File};
 use dotenvy::dotenv;
 use serde::Deserialize;
-use swu_app::configuration::Database;
+use swu_app::{configuration::Database, liq_pay::Currency};
 
 #[derive(Deserialize, Clone)]
 pub struct Configuration {
     pub database: Database,
     pub sheets: Sheets,
+    /// Currency that donations of every currency are summed up in
+    pub reporting_currency: Currency,
 }
 
 #[derive(Deserialize, Clone)]
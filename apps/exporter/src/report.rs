@@ -2,7 +2,12 @@ extern crate google_sheets4 as sheets4;
 use serde_json::Value;
 use sheets4::api::{BatchUpdateValuesRequest, ValueRange};
 use sqlx::PgPool;
-use swu_app::startup::get_connection_pool;
+use swu_app::{
+    data::{read_donation_totals, read_exchange_rates},
+    exchange_rates::normalize,
+    liq_pay::Currency,
+    startup::get_connection_pool,
+};
 use time::OffsetDateTime;
 
 use crate::{
@@ -70,6 +75,16 @@ pub async fn run(configuration: Configuration) {
         .await,
     );
 
+    updates.extend(
+        create_bulk_updates_for_sheet(
+            &sheets,
+            spreadsheet_id,
+            "donations",
+            get_donation_total_rows(&db_pool, &configuration.reporting_currency).await,
+        )
+        .await,
+    );
+
     let request = BatchUpdateValuesRequest {
         data: Some(updates),
         value_input_option: Some("USER_ENTERED".to_owned()),
@@ -247,3 +262,31 @@ pub async fn get_widget_event_summary_rows(
     ]
     .concat()
 }
+
+pub async fn get_donation_total_rows(db_pool: &PgPool, reporting_currency: &Currency) -> Rows {
+    let totals = read_donation_totals(None, None, db_pool).await.unwrap();
+    let rates = read_exchange_rates(db_pool).await.unwrap();
+    let total = normalize(&totals, &rates, reporting_currency);
+
+    totals
+        .iter()
+        .map(|total| {
+            [
+                total.currency.clone(),
+                total.amount.to_string(),
+                total.donations.to_string(),
+            ]
+            .into_iter()
+            .map(Into::into)
+            .collect()
+        })
+        .chain([[
+            format!("Total in {}", total.currency.as_str()),
+            total.amount.to_string(),
+            total.missing_rates.join(" "),
+        ]
+        .into_iter()
+        .map(Into::into)
+        .collect()])
+        .collect()
+}
@@ -1,7 +1,7 @@
 use time::{macros::time, Date, OffsetDateTime, PrimitiveDateTime, Time};
 
 pub fn get_week_start_end(base_date: Option<OffsetDateTime>) -> (OffsetDateTime, OffsetDateTime) {
-    let base_date = base_date.map_or_else(OffsetDateTime::now_utc, |date| date);
+    let base_date = base_date.unwrap_or_else(OffsetDateTime::now_utc);
 
     let week_start = PrimitiveDateTime::new(
         Date::from_iso_week_date(
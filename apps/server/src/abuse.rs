@@ -0,0 +1,396 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    convert::Infallible,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRef, FromRequestParts},
+    http::request::Parts,
+};
+
+use crate::{
+    configuration::{CheckoutOptions, PayLimits},
+    liq_pay::{Currency, InputError, InputQuery},
+};
+
+/// Upper bound on tracked clients so that many addresses cannot grow the guard without limit
+const MAX_CLIENTS: usize = 10_000;
+
+const REQUEST_WINDOW: Duration = Duration::from_secs(60);
+const AMOUNT_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum AbuseError {
+    #[error("Too many checkouts were requested, try again in a minute.")]
+    TooManyRequests,
+
+    #[error("Too many different amounts were tried, try again later.")]
+    TooManyAmounts,
+}
+
+/// Recent checkouts of a single client
+#[derive(Default)]
+struct ClientActivity {
+    requests: Vec<Instant>,
+    amounts: Vec<(Instant, f64)>,
+    /// Time of the last checkout, kept after the windows forget it so that it stays in the index
+    last_seen: Option<Instant>,
+}
+
+impl ClientActivity {
+    fn forget_before(&mut self, now: Instant) {
+        self.requests
+            .retain(|requested_at| now.duration_since(*requested_at) < REQUEST_WINDOW);
+        self.amounts
+            .retain(|(requested_at, _)| now.duration_since(*requested_at) < AMOUNT_WINDOW);
+    }
+
+    fn check(&self, amount: f64, limits: &PayLimits) -> Result<(), AbuseError> {
+        if self.requests.len() >= usize::from(limits.requests_per_minute) {
+            return Err(AbuseError::TooManyRequests);
+        }
+
+        let distinct_amounts = self.distinct_amounts();
+        if !distinct_amounts.contains(&amount)
+            && distinct_amounts.len() >= usize::from(limits.distinct_amounts_per_hour)
+        {
+            return Err(AbuseError::TooManyAmounts);
+        }
+
+        Ok(())
+    }
+
+    fn distinct_amounts(&self) -> Vec<f64> {
+        let mut amounts: Vec<f64> = self.amounts.iter().map(|(_, amount)| *amount).collect();
+        amounts.sort_by(f64::total_cmp);
+        amounts.dedup();
+        amounts
+    }
+}
+
+/// Clients with recent checkouts, indexed by when they were last seen so the oldest is evicted first
+#[derive(Default)]
+struct Clients {
+    activity: HashMap<IpAddr, ClientActivity>,
+    last_seen: BTreeSet<(Instant, IpAddr)>,
+}
+
+/// Checks checkout requests against `PayLimits` and `CheckoutOptions` before a signed checkout is created
+#[derive(Clone)]
+pub struct CheckoutGuard {
+    limits: PayLimits,
+    options: CheckoutOptions,
+    clients: Arc<Mutex<Clients>>,
+}
+
+impl CheckoutGuard {
+    pub fn new(limits: PayLimits, options: CheckoutOptions) -> Self {
+        Self {
+            limits,
+            options,
+            clients: Arc::new(Mutex::new(Clients::default())),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Will return `InputError` if the language or currency of `query` is not enabled
+    /// or its amount is above the configured cap for its currency
+    pub fn validate(&self, query: &InputQuery) -> Result<(), InputError> {
+        if !self.options.languages.contains(&query.language) {
+            return Err(InputError::LanguageNotAvailable {
+                language: query.language.as_str(),
+            });
+        }
+
+        if !self.accepts_currency(&query.currency) {
+            return Err(InputError::CurrencyNotAvailable {
+                currency: query.currency.as_str(),
+            });
+        }
+
+        let max = self.limits.max_amount(&query.currency);
+
+        if query.amount > f64::from(max) {
+            return Err(InputError::AmountTooLarge {
+                max,
+                currency: query.currency.as_str(),
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn accepts_currency(&self, currency: &Currency) -> bool {
+        self.options.currencies.contains(currency)
+    }
+
+    /// Records a checkout of `amount` by `client` unless it goes over the limits
+    ///
+    /// # Errors
+    ///
+    /// Will return `AbuseError` if the client requested too many checkouts or tried too many amounts
+    pub fn check(&self, client: IpAddr, amount: f64) -> Result<(), AbuseError> {
+        self.check_at(client, amount, Instant::now())
+    }
+
+    /// Number of proxies in front of the server whose `X-Forwarded-For` entries can be trusted
+    pub const fn trusted_proxy_hops(&self) -> u8 {
+        self.limits.trusted_proxy_hops
+    }
+
+    fn check_at(&self, client: IpAddr, amount: f64, now: Instant) -> Result<(), AbuseError> {
+        let mut clients = self.clients.lock().expect("guard lock is poisoned");
+        let clients = &mut *clients;
+
+        if let Some(activity) = clients.activity.get_mut(&client) {
+            activity.forget_before(now);
+            activity.check(amount, &self.limits)?;
+        } else {
+            ClientActivity::default().check(amount, &self.limits)?;
+
+            if clients.activity.len() >= MAX_CLIENTS {
+                // dropping the least recently seen client keeps the limits of everyone else
+                if let Some((_, oldest)) = clients.last_seen.pop_first() {
+                    clients.activity.remove(&oldest);
+                }
+            }
+        }
+
+        let activity = clients.activity.entry(client).or_default();
+        if let Some(last_seen) = activity.last_seen.replace(now) {
+            clients.last_seen.remove(&(last_seen, client));
+        }
+        clients.last_seen.insert((now, client));
+        activity.requests.push(now);
+        activity.amounts.push((now, amount));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl CheckoutGuard {
+    /// Tight limits on English, Ukrainian and Polish checkouts in USD and UAH behind one proxy
+    pub fn for_tests() -> Self {
+        use crate::liq_pay::Language;
+
+        Self::new(
+            PayLimits {
+                requests_per_minute: 3,
+                distinct_amounts_per_hour: 2,
+                trusted_proxy_hops: 1,
+                max_amount_usd: 1_000,
+                max_amount_eur: 1_000,
+                max_amount_uah: 40_000,
+                max_amount_pln: 4_000,
+                max_amount_ron: 4_000,
+            },
+            CheckoutOptions {
+                languages: vec![Language::EN, Language::UA, Language::PL],
+                currencies: vec![Currency::USD, Currency::UAH],
+            },
+        )
+    }
+}
+
+/// Address of the donor that made the request
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+    CheckoutGuard: FromRef<S>,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // every trusted proxy appends the address it received the request from,
+        // any earlier entries are sent by the client and can be forged
+        let forwarded = CheckoutGuard::from_ref(state)
+            .trusted_proxy_hops()
+            .checked_sub(1)
+            .and_then(|skipped| {
+                parts
+                    .headers
+                    .get("x-forwarded-for")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.rsplit(',').nth(usize::from(skipped)))
+            })
+            .and_then(|address| address.trim().parse().ok());
+
+        let connected = || {
+            parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(address)| address.ip())
+        };
+
+        Ok(Self(
+            forwarded
+                .or_else(connected)
+                .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::liq_pay::Language;
+
+    #[test]
+    fn test_check_limits_requests_per_client() {
+        let guard = CheckoutGuard::for_tests();
+        let client = IpAddr::from([10, 0, 0, 1]);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(guard.check_at(client, 10.0, now), Ok(()));
+        }
+        assert_eq!(
+            guard.check_at(client, 10.0, now),
+            Err(AbuseError::TooManyRequests)
+        );
+        assert_eq!(
+            guard.check_at(IpAddr::from([10, 0, 0, 2]), 10.0, now),
+            Ok(()),
+            "Other clients should not be limited"
+        );
+        assert_eq!(
+            guard.check_at(client, 10.0, now + REQUEST_WINDOW),
+            Ok(()),
+            "Requests should be allowed again after the window"
+        );
+    }
+
+    #[test]
+    fn test_check_limits_distinct_amounts_per_client() {
+        let guard = CheckoutGuard::for_tests();
+        let client = IpAddr::from([10, 0, 0, 1]);
+        let now = Instant::now();
+
+        assert_eq!(guard.check_at(client, 1.0, now), Ok(()));
+        assert_eq!(guard.check_at(client, 2.0, now), Ok(()));
+        assert_eq!(
+            guard.check_at(client, 3.0, now),
+            Err(AbuseError::TooManyAmounts)
+        );
+
+        let later = now + REQUEST_WINDOW;
+        assert_eq!(
+            guard.check_at(client, 2.0, later),
+            Ok(()),
+            "Amounts that were already tried should be allowed"
+        );
+        assert_eq!(guard.check_at(client, 3.0, now + AMOUNT_WINDOW), Ok(()));
+    }
+
+    #[test]
+    fn test_check_keeps_limits_of_recent_clients_when_full() {
+        let guard = CheckoutGuard::for_tests();
+        let client = IpAddr::from([10, 0, 0, 1]);
+        let now = Instant::now();
+
+        for index in 0..MAX_CLIENTS - 1 {
+            let address = IpAddr::from(u32::try_from(index).unwrap().to_be_bytes());
+            assert_eq!(guard.check_at(address, 10.0, now), Ok(()));
+        }
+        for _ in 0..3 {
+            assert_eq!(
+                guard.check_at(client, 10.0, now + Duration::from_secs(1)),
+                Ok(())
+            );
+        }
+
+        let later = now + Duration::from_secs(2);
+        assert_eq!(
+            guard.check_at(IpAddr::from([192, 168, 0, 1]), 10.0, later),
+            Ok(())
+        );
+        assert_eq!(
+            guard.check_at(client, 10.0, later),
+            Err(AbuseError::TooManyRequests),
+            "A new client should not reset the limits of recent clients"
+        );
+        let clients = guard.clients.lock().unwrap();
+        assert_eq!(clients.activity.len(), MAX_CLIENTS);
+        assert_eq!(clients.last_seen.len(), MAX_CLIENTS);
+    }
+
+    #[rstest]
+    #[case(1, Some("198.51.100.1, 203.0.113.7"), [203, 0, 113, 7])]
+    #[case(2, Some("198.51.100.1, 203.0.113.7"), [198, 51, 100, 1])]
+    #[case(3, Some("198.51.100.1, 203.0.113.7"), [192, 0, 2, 1])]
+    #[case(1, None, [192, 0, 2, 1])]
+    #[case(0, Some("198.51.100.1, 203.0.113.7"), [192, 0, 2, 1])]
+    #[tokio::test]
+    async fn test_client_ip_trusts_only_configured_proxies(
+        #[case] trusted_proxy_hops: u8,
+        #[case] forwarded_for: Option<&str>,
+        #[case] expected: [u8; 4],
+    ) {
+        let mut guard = CheckoutGuard::for_tests();
+        guard.limits.trusted_proxy_hops = trusted_proxy_hops;
+        let mut request = axum::http::Request::builder();
+        if let Some(forwarded_for) = forwarded_for {
+            request = request.header("X-Forwarded-For", forwarded_for);
+        }
+        let (mut parts, ()) = request
+            .extension(ConnectInfo(SocketAddr::from(([192, 0, 2, 1], 443))))
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        let ClientIp(client) = ClientIp::from_request_parts(&mut parts, &guard)
+            .await
+            .unwrap();
+
+        assert_eq!(client, IpAddr::from(expected));
+    }
+
+    #[rstest]
+    #[case(Language::EN, Currency::USD, 1_000.0, Ok(()))]
+    #[case(
+        Language::EN,
+        Currency::USD,
+        1_000.01,
+        Err(InputError::AmountTooLarge { max: 1_000, currency: "USD" })
+    )]
+    #[case(Language::PL, Currency::UAH, 5_000.0, Ok(()))]
+    #[case(
+        Language::DE,
+        Currency::USD,
+        10.0,
+        Err(InputError::LanguageNotAvailable { language: "de" })
+    )]
+    #[case(
+        Language::EN,
+        Currency::EUR,
+        10.0,
+        Err(InputError::CurrencyNotAvailable { currency: "EUR" })
+    )]
+    fn test_validate_options_and_amount_cap(
+        #[case] language: Language,
+        #[case] currency: Currency,
+        #[case] amount: f64,
+        #[case] expected: Result<(), InputError>,
+    ) {
+        let query = InputQuery {
+            language,
+            currency,
+            amount,
+            ..InputQuery::for_tests()
+        };
+
+        assert_eq!(CheckoutGuard::for_tests().validate(&query), expected);
+    }
+}
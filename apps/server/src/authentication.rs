@@ -10,6 +10,7 @@ use axum_extra::{
     headers::{authorization::Bearer, Authorization},
     TypedHeader,
 };
+use constant_time_eq::constant_time_eq;
 use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
@@ -22,6 +23,9 @@ pub struct AuthClaims {
     pub sub: String,
     pub role: String,
     pub exp: i64,
+    /// BigCommerce user who opened the app, missing from tokens that are not minted for a store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<i32>,
 }
 
 #[async_trait]
@@ -41,8 +45,44 @@ where
             .map_err(|_| Error::NoToken)?;
 
         let state = SharedState::from_ref(state);
+        let claims = decode_token(bearer.token(), &state.jwt_secret)?;
 
-        decode_token(bearer.token(), &state.jwt_secret)
+        // tokens minted for other purposes, such as cancellation links, share the same secret
+        if claims.role != USER_ROLE {
+            return Err(Error::Unauthorized);
+        }
+
+        Ok(claims)
+    }
+}
+
+/// Operator authenticated with the admin API key
+pub struct AdminAuth;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminAuth
+where
+    SharedState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    #[tracing::instrument(name = "authenticate admin from request", skip(parts, state))]
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let TypedHeader(Authorization(bearer)) = parts
+            .extract::<TypedHeader<Authorization<Bearer>>>()
+            .await
+            .map_err(|_| Error::NoToken)?;
+
+        let state = SharedState::from_ref(state);
+        let api_key = state.admin_api_key.expose_secret();
+
+        // an empty key leaves the admin endpoints disabled
+        if api_key.is_empty() || !constant_time_eq(api_key.as_bytes(), bearer.token().as_bytes()) {
+            return Err(Error::Unauthorized);
+        }
+
+        Ok(Self)
     }
 }
 
@@ -51,22 +91,27 @@ impl IntoResponse for Error {
     fn into_response(self) -> Response {
         match self {
             Self::InvalidToken(_) | Self::NoToken => (StatusCode::BAD_REQUEST, "Invalid token"),
+            Self::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized"),
         }
         .into_response()
     }
 }
 
+const USER_ROLE: &str = "user";
+
 #[tracing::instrument(name = "create jwt token", skip(secret))]
 pub fn create_jwt(
     store_hash: &str,
+    user_id: i32,
     secret: &Secret<String>,
 ) -> Result<String, jsonwebtoken::errors::Error> {
     let expiration = OffsetDateTime::now_utc() + Duration::days(1);
 
     let claims = AuthClaims {
         sub: store_hash.to_owned(),
-        role: "user".to_owned(),
+        role: USER_ROLE.to_owned(),
         exp: expiration.unix_timestamp(),
+        user_id: Some(user_id),
     };
     let header = Header::new(Algorithm::HS512);
     let key = EncodingKey::from_secret(secret.expose_secret().as_bytes());
@@ -74,6 +119,44 @@ pub fn create_jwt(
     encode(&header, &claims, &key)
 }
 
+const UNSUBSCRIBE_ROLE: &str = "unsubscribe";
+
+/// Token embedded in cancellation links sent to donors with a recurring donation
+#[tracing::instrument(name = "create unsubscribe token", skip(secret))]
+pub fn create_unsubscribe_token(
+    order_id: &str,
+    secret: &Secret<String>,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let expiration = OffsetDateTime::now_utc() + Duration::days(365);
+
+    let claims = AuthClaims {
+        sub: order_id.to_owned(),
+        role: UNSUBSCRIBE_ROLE.to_owned(),
+        exp: expiration.unix_timestamp(),
+        user_id: None,
+    };
+    let header = Header::new(Algorithm::HS512);
+    let key = EncodingKey::from_secret(secret.expose_secret().as_bytes());
+
+    encode(&header, &claims, &key)
+}
+
+/// # Errors
+///
+/// Will return `Error::InvalidToken` if the token is not valid or was not minted for cancelling a subscription
+#[tracing::instrument(name = "decode unsubscribe token")]
+pub fn decode_unsubscribe_token(token: &str, secret: &Secret<String>) -> Result<String, Error> {
+    let claims = decode_token(token, secret)?;
+
+    if claims.role != UNSUBSCRIBE_ROLE {
+        return Err(Error::InvalidToken(
+            jsonwebtoken::errors::ErrorKind::InvalidSubject.into(),
+        ));
+    }
+
+    Ok(claims.sub)
+}
+
 pub struct AuthorizedUser(pub String);
 
 #[tracing::instrument(name = "decode token")]
@@ -93,6 +176,9 @@ pub enum Error {
 
     #[error("Token is invalid.")]
     InvalidToken(#[source] jsonwebtoken::errors::Error),
+
+    #[error("Token is not authorized.")]
+    Unauthorized,
 }
 
 #[cfg(test)]
@@ -103,7 +189,7 @@ mod tests {
     fn should_encode_and_decode_jwt_in_correct_format() {
         let store_hash = "test_store";
         let secret = Secret::from("abcdefg".to_owned());
-        let token = create_jwt(store_hash, &secret).unwrap();
+        let token = create_jwt(store_hash, 24654, &secret).unwrap();
 
         let parts: Vec<&str> = token.splitn(3, '.').collect();
 
@@ -115,9 +201,30 @@ mod tests {
         let claims = decode_token(token.as_str(), &secret).unwrap();
 
         assert_eq!("test_store", claims.sub);
+        assert_eq!(Some(24654), claims.user_id);
         assert!(
             claims.exp > (OffsetDateTime::now_utc() + Duration::minutes(30)).unix_timestamp(),
             "Expiration should be more than 30 mins"
         )
     }
+
+    #[test]
+    fn should_encode_and_decode_unsubscribe_token() {
+        let secret = Secret::from("abcdefg".to_owned());
+        let token = create_unsubscribe_token("order-1", &secret).unwrap();
+
+        assert_eq!(
+            "order-1",
+            decode_unsubscribe_token(token.as_str(), &secret).unwrap()
+        );
+        assert_eq!(
+            None,
+            decode_token(token.as_str(), &secret).unwrap().user_id,
+            "Tokens without a user should still decode"
+        );
+
+        let token = create_jwt("test_store", 24654, &secret).unwrap();
+
+        assert!(decode_unsubscribe_token(token.as_str(), &secret).is_err());
+    }
 }
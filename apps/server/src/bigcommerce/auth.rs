@@ -9,6 +9,14 @@ pub struct User {
     pub email: String,
 }
 
+/// Store Content - Modify, needed to manage the storefront script of the widget
+pub const SCRIPTS_SCOPE: &str = "store_v2_content";
+
+/// Whether the space separated scopes granted by the merchant include `scope`
+pub fn has_scope(granted: &str, scope: &str) -> bool {
+    granted.split_whitespace().any(|granted| granted == scope)
+}
+
 #[derive(Deserialize)]
 pub struct OAuthResponse {
     pub access_token: Secret<String>,
@@ -47,7 +55,28 @@ impl Claims {
             .ok_or_else(|| anyhow::anyhow!("Context did not have correct format"))
     }
 
+    /// Staff member the request was made for, who may not be the store owner
+    pub const fn get_user(&self) -> &User {
+        &self.user
+    }
+
     pub fn is_owner(&self) -> bool {
         self.owner.id == self.user.id && self.owner.email == self.user.email
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("store_v2_content store_v2_information_read_only", true)]
+    #[case("store_v2_information_read_only store_v2_content", true)]
+    #[case("store_v2_content_read_only store_v2_information_read_only", false)]
+    #[case("", false)]
+    fn test_has_scripts_scope(#[case] granted: &str, #[case] expected: bool) {
+        assert_eq!(has_scope(granted, SCRIPTS_SCOPE), expected);
+    }
+}
@@ -1,17 +1,27 @@
 use anyhow::Context;
+use constant_time_eq::constant_time_eq;
+use hmac::{Hmac, Mac};
 use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
 use reqwest::{header, Client};
 use secrecy::{ExposeSecret, Secret};
 use serde_json::json;
+use sha2::Sha256;
 
 use crate::authentication::Error;
 
 use super::{
     auth::{Claims, OAuthResponse},
+    retry::{QuotaResets, SendWithRetry},
     script::{GetResponse, ListResponse, Script},
     store::{APIToken, Information},
+    webhook::{self, TOKEN_HEADER},
 };
 
+/// Largest page the Scripts API returns
+const SCRIPTS_PAGE_LIMIT: u32 = 250;
+/// Pages of scripts read before giving up on a listing that does not end
+const SCRIPTS_MAX_PAGES: u32 = 20;
+
 #[derive(Clone)]
 pub struct HttpAPI {
     api_base_url: String,
@@ -19,7 +29,9 @@ pub struct HttpAPI {
     client_id: String,
     client_secret: Secret<String>,
     install_redirect_uri: String,
+    webhook_url: String,
     http_client: Client,
+    quota_resets: QuotaResets,
 }
 
 impl HttpAPI {
@@ -29,6 +41,7 @@ impl HttpAPI {
         client_id: String,
         client_secret: Secret<String>,
         install_redirect_uri: String,
+        webhook_url: String,
         timeout: std::time::Duration,
     ) -> Self {
         let mut headers = header::HeaderMap::new();
@@ -52,7 +65,9 @@ impl HttpAPI {
             client_id,
             client_secret,
             install_redirect_uri,
+            webhook_url,
             http_client,
+            quota_resets: QuotaResets::default(),
         }
     }
 
@@ -78,6 +93,7 @@ impl HttpAPI {
                 "scope": scope,
                 "context": context
             }))
+            // the authorization code can only be exchanged once, so the request is never repeated
             .send()
             .await?
             .json()
@@ -95,22 +111,53 @@ impl HttpAPI {
         format!("{}/stores/{}/v2/store", self.api_base_url, store_hash)
     }
 
+    fn get_webhooks_route(&self, store_hash: &str) -> String {
+        format!("{}/stores/{}/v3/hooks", self.api_base_url, store_hash)
+    }
+
     fn get_scripts_route_with_id(&self, store_hash: &str, script_id: &str) -> String {
         format!("{}/{}", self.get_scripts_route(store_hash), script_id)
     }
 
-    #[tracing::instrument(name = "get all scripts", skip(self))]
-    pub async fn get_all_scripts(&self, store: &APIToken) -> Result<ListResponse, anyhow::Error> {
+    #[tracing::instrument(name = "get scripts page", skip(self))]
+    pub async fn get_scripts_page(
+        &self,
+        store: &APIToken,
+        page: u32,
+    ) -> Result<ListResponse, anyhow::Error> {
         self.http_client
             .get(self.get_scripts_route(store.get_store_hash()))
             .headers(store.get_api_headers()?)
-            .send()
+            .query(&[("page", page), ("limit", SCRIPTS_PAGE_LIMIT)])
+            .send_with_retry(&self.quota_resets, store.get_store_hash())
             .await
-            .context("get all scripts request")?
+            .context("get scripts page request")?
             .error_for_status()?
             .json::<ListResponse>()
             .await
-            .context("parse get all scripts response")
+            .context("parse get scripts page response")
+    }
+
+    /// Scripts the app created in the store across every page of the listing
+    #[tracing::instrument(name = "get all scripts", skip(self))]
+    pub async fn get_all_scripts(
+        &self,
+        store: &APIToken,
+    ) -> Result<Vec<GetResponse>, anyhow::Error> {
+        let mut scripts = vec![];
+
+        for page in 1..=SCRIPTS_MAX_PAGES {
+            let response = self.get_scripts_page(store, page).await?;
+            let has_page_after = response.has_page_after(page);
+            scripts.extend(response.data);
+
+            if !has_page_after {
+                return Ok(scripts);
+            }
+        }
+
+        // a partial listing would miss scripts of the app, which then get duplicated
+        anyhow::bail!("Scripts listing has more than {SCRIPTS_MAX_PAGES} pages")
     }
 
     #[tracing::instrument(name = "try get scripts with name", skip(self))]
@@ -121,24 +168,18 @@ impl HttpAPI {
     ) -> Result<Option<GetResponse>, anyhow::Error> {
         let scripts = self.get_all_scripts(store).await?;
 
-        for script in scripts.data {
-            if script.name == name {
-                return Ok(Some(script));
-            }
-        }
-
-        Ok(None)
+        Ok(scripts.into_iter().find(|script| script.name == name))
     }
 
     #[tracing::instrument(name = "remove all scripts", skip(self))]
     pub async fn remove_all_scripts(&self, store: &APIToken) -> Result<(), anyhow::Error> {
         let scripts = self.get_all_scripts(store).await?;
 
-        for script in scripts.data {
+        for script in scripts {
             self.http_client
                 .delete(self.get_scripts_route_with_id(store.get_store_hash(), &script.uuid))
                 .headers(store.get_api_headers()?)
-                .send()
+                .send_with_retry(&self.quota_resets, store.get_store_hash())
                 .await
                 .context("delete script request")?;
         }
@@ -156,7 +197,7 @@ impl HttpAPI {
             .post(self.get_scripts_route(store.get_store_hash()))
             .headers(store.get_api_headers()?)
             .json(&script.generate_script_body())
-            .send()
+            .send_with_retry(&self.quota_resets, store.get_store_hash())
             .await
             .context("create script request")?
             .error_for_status()?;
@@ -175,7 +216,7 @@ impl HttpAPI {
             .put(self.get_scripts_route_with_id(store.get_store_hash(), script_uuid))
             .headers(store.get_api_headers()?)
             .json(&script.generate_script_body())
-            .send()
+            .send_with_retry(&self.quota_resets, store.get_store_hash())
             .await
             .context("update script request")?
             .error_for_status()?;
@@ -183,6 +224,85 @@ impl HttpAPI {
         Ok(())
     }
 
+    /// Token sent back by BigCommerce in a custom header of every webhook for the store
+    pub fn webhook_token(&self, store_hash: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.client_secret.expose_secret().as_bytes())
+            .expect("HMAC can take key of any size");
+        mac.update(store_hash.as_bytes());
+
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    pub fn verify_webhook_token(&self, store_hash: &str, token: &str) -> bool {
+        constant_time_eq(self.webhook_token(store_hash).as_bytes(), token.as_bytes())
+    }
+
+    #[tracing::instrument(name = "get all webhooks", skip(self))]
+    pub async fn get_all_webhooks(
+        &self,
+        store: &APIToken,
+    ) -> Result<webhook::ListResponse, anyhow::Error> {
+        self.http_client
+            .get(self.get_webhooks_route(store.get_store_hash()))
+            .headers(store.get_api_headers()?)
+            .send_with_retry(&self.quota_resets, store.get_store_hash())
+            .await
+            .context("get all webhooks request")?
+            .error_for_status()?
+            .json::<webhook::ListResponse>()
+            .await
+            .context("parse get all webhooks response")
+    }
+
+    /// Creates the webhooks the store is missing and reactivates the ones BigCommerce disabled
+    #[tracing::instrument(name = "register webhooks", skip(self))]
+    pub async fn register_webhooks(&self, store: &APIToken) -> Result<(), anyhow::Error> {
+        let webhooks = self.get_all_webhooks(store).await?;
+        let body = |scope: &str| {
+            json!({
+                "scope": scope,
+                "destination": self.webhook_url,
+                "is_active": true,
+                "headers": {
+                    TOKEN_HEADER: self.webhook_token(store.get_store_hash()),
+                },
+            })
+        };
+
+        for scope in webhook::SCOPES {
+            let existing = webhooks
+                .data
+                .iter()
+                .find(|webhook| webhook.scope == scope && webhook.destination == self.webhook_url);
+
+            let request = match existing {
+                Some(webhook) if webhook.is_active => continue,
+                Some(webhook) => self.http_client.put(format!(
+                    "{}/{}",
+                    self.get_webhooks_route(store.get_store_hash()),
+                    webhook.id
+                )),
+                None => self
+                    .http_client
+                    .post(self.get_webhooks_route(store.get_store_hash())),
+            };
+
+            request
+                .headers(store.get_api_headers()?)
+                .json(&body(scope))
+                .send_with_retry(&self.quota_resets, store.get_store_hash())
+                .await
+                .context("register webhook request")?
+                .error_for_status()?;
+        }
+
+        Ok(())
+    }
+
     #[tracing::instrument(name = "decode bc signed jwt", skip(self))]
     pub fn decode_jwt(&self, token: &str) -> Result<Claims, Error> {
         let key = DecodingKey::from_secret(self.client_secret.expose_secret().as_bytes());
@@ -202,7 +322,7 @@ impl HttpAPI {
         self.http_client
             .get(self.get_store_information_route(store.get_store_hash()))
             .headers(store.get_api_headers()?)
-            .send()
+            .send_with_retry(&self.quota_resets, store.get_store_hash())
             .await
             .context("get store information request")?
             .error_for_status()?
@@ -1,4 +1,6 @@
 pub mod auth;
 pub mod client;
+pub mod retry;
 pub mod script;
 pub mod store;
+pub mod webhook;
@@ -0,0 +1,298 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use axum::async_trait;
+use reqwest::{header::HeaderMap, Method, RequestBuilder, Response, StatusCode};
+
+/// Attempts after the first one before the last response is returned as is
+const MAX_RETRIES: u32 = 3;
+
+const BASE_DELAY: Duration = Duration::from_millis(250);
+
+/// Longest wait for the rate limit window to reset so that requests from the dashboard stay responsive
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+const TIME_RESET_HEADER: &str = "X-Rate-Limit-Time-Reset-Ms";
+const REQUESTS_LEFT_HEADER: &str = "X-Rate-Limit-Requests-Left";
+
+/// When the rate limit window of each store resets after a response left no requests in it
+#[derive(Clone, Default)]
+pub struct QuotaResets(Arc<Mutex<HashMap<String, Instant>>>);
+
+impl QuotaResets {
+    /// Holds the next request to a store back until its rate limit window resets
+    async fn wait(&self, store_hash: &str) {
+        let reset = self
+            .0
+            .lock()
+            .expect("quota lock is poisoned")
+            .get(store_hash)
+            .copied();
+
+        if let Some(delay) = reset.map(|reset| reset.saturating_duration_since(Instant::now())) {
+            if !delay.is_zero() {
+                tracing::info!(?delay, "waiting for bigcommerce rate limit reset");
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+
+    fn record(&self, store_hash: &str, headers: &HeaderMap) {
+        let mut resets = self.0.lock().expect("quota lock is poisoned");
+
+        match quota_delay(headers) {
+            Some(delay) => resets.insert(store_hash.to_owned(), Instant::now() + delay),
+            None => resets.remove(store_hash),
+        };
+    }
+}
+
+/// Sends BigCommerce requests again when the API is rate limiting or briefly unavailable
+#[async_trait]
+pub trait SendWithRetry {
+    /// Sends the request to the API of `store_hash` once its rate limit window in `quota` allows
+    async fn send_with_retry(
+        self,
+        quota: &QuotaResets,
+        store_hash: &str,
+    ) -> Result<Response, reqwest::Error>;
+}
+
+#[async_trait]
+impl SendWithRetry for RequestBuilder {
+    #[tracing::instrument(
+        name = "send bigcommerce request",
+        skip(self, quota),
+        fields(retries = tracing::field::Empty)
+    )]
+    async fn send_with_retry(
+        self,
+        quota: &QuotaResets,
+        store_hash: &str,
+    ) -> Result<Response, reqwest::Error> {
+        let (client, request) = self.build_split();
+        let request = request?;
+        let mut retries = 0;
+
+        loop {
+            // an earlier request to the store may have used up its rate limit window
+            quota.wait(store_hash).await;
+
+            // requests with a streamed body cannot be repeated
+            let Some(attempt) = request.try_clone() else {
+                let result = client.execute(request).await;
+                if let Ok(response) = &result {
+                    quota.record(store_hash, response.headers());
+                }
+                return result;
+            };
+
+            let result = client.execute(attempt).await;
+            let delay = match &result {
+                Ok(response) => {
+                    quota.record(store_hash, response.headers());
+                    retry_delay(
+                        request.method(),
+                        response.status(),
+                        response.headers(),
+                        retries,
+                    )
+                }
+                // the request never reached BigCommerce
+                Err(error) if error.is_connect() => Some(backoff(retries)),
+                Err(_) => None,
+            };
+
+            match delay {
+                Some(delay) if retries < MAX_RETRIES => {
+                    retries += 1;
+                    tracing::warn!(retries, ?delay, "retrying bigcommerce request");
+                    tokio::time::sleep(delay).await;
+                }
+                _ => {
+                    tracing::Span::current().record("retries", retries);
+                    return result;
+                }
+            }
+        }
+    }
+}
+
+/// How long to wait before sending the request again, if it should be sent again at all
+fn retry_delay(
+    method: &Method,
+    status: StatusCode,
+    headers: &HeaderMap,
+    retries: u32,
+) -> Option<Duration> {
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => {
+            Some(reset_delay(headers).unwrap_or_else(|| backoff(retries)))
+        }
+        // a request that failed on the server may have been applied, so only repeat idempotent ones
+        StatusCode::INTERNAL_SERVER_ERROR
+        | StatusCode::BAD_GATEWAY
+        | StatusCode::SERVICE_UNAVAILABLE
+        | StatusCode::GATEWAY_TIMEOUT
+            if method.is_idempotent() =>
+        {
+            Some(backoff(retries))
+        }
+        _ => None,
+    }
+}
+
+/// Wait until the rate limit window resets when no requests are left in it
+fn quota_delay(headers: &HeaderMap) -> Option<Duration> {
+    let requests_left = headers
+        .get(REQUESTS_LEFT_HEADER)?
+        .to_str()
+        .ok()?
+        .parse::<u64>()
+        .ok()?;
+
+    if requests_left == 0 {
+        reset_delay(headers)
+    } else {
+        None
+    }
+}
+
+fn reset_delay(headers: &HeaderMap) -> Option<Duration> {
+    let reset_ms = headers
+        .get(TIME_RESET_HEADER)?
+        .to_str()
+        .ok()?
+        .parse::<u64>()
+        .ok()?;
+
+    Some(Duration::from_millis(reset_ms).min(MAX_DELAY))
+}
+
+/// Exponential backoff with jitter so that concurrent requests do not retry in lockstep
+fn backoff(retries: u32) -> Duration {
+    let delay = BASE_DELAY.saturating_mul(2_u32.saturating_pow(retries));
+    let jitter = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |now| now.subsec_nanos())
+        % u32::try_from(delay.as_millis()).unwrap_or(u32::MAX).max(1);
+
+    (delay + Duration::from_millis(jitter.into())).min(MAX_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn headers(values: &[(&'static str, &str)]) -> HeaderMap {
+        values
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+            .collect()
+    }
+
+    #[rstest]
+    #[case(Method::GET, StatusCode::SERVICE_UNAVAILABLE, true)]
+    #[case(Method::POST, StatusCode::SERVICE_UNAVAILABLE, false)]
+    #[case(Method::GET, StatusCode::BAD_GATEWAY, true)]
+    #[case(Method::PUT, StatusCode::INTERNAL_SERVER_ERROR, true)]
+    #[case(Method::DELETE, StatusCode::GATEWAY_TIMEOUT, true)]
+    #[case(Method::POST, StatusCode::INTERNAL_SERVER_ERROR, false)]
+    #[case(Method::GET, StatusCode::OK, false)]
+    #[case(Method::GET, StatusCode::NOT_FOUND, false)]
+    #[case(Method::POST, StatusCode::UNPROCESSABLE_ENTITY, false)]
+    fn test_retry_delay_for_status(
+        #[case] method: Method,
+        #[case] status: StatusCode,
+        #[case] retried: bool,
+    ) {
+        assert_eq!(
+            retry_delay(&method, status, &HeaderMap::new(), 0).is_some(),
+            retried
+        );
+    }
+
+    #[test]
+    fn test_retry_delay_waits_for_rate_limit_reset() {
+        let delay = retry_delay(
+            &Method::POST,
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers(&[(TIME_RESET_HEADER, "1500")]),
+            0,
+        );
+        assert_eq!(delay, Some(Duration::from_millis(1500)));
+
+        let delay = retry_delay(
+            &Method::POST,
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers(&[(TIME_RESET_HEADER, "60000")]),
+            0,
+        );
+        assert_eq!(delay, Some(MAX_DELAY));
+
+        let delay = retry_delay(
+            &Method::POST,
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers(&[(TIME_RESET_HEADER, "soon")]),
+            0,
+        )
+        .unwrap();
+        assert!(delay >= BASE_DELAY);
+    }
+
+    #[rstest]
+    #[case(&[(REQUESTS_LEFT_HEADER, "0"), (TIME_RESET_HEADER, "500")], Some(Duration::from_millis(500)))]
+    #[case(&[(REQUESTS_LEFT_HEADER, "3"), (TIME_RESET_HEADER, "500")], None)]
+    #[case(&[(TIME_RESET_HEADER, "500")], None)]
+    fn test_quota_delay(
+        #[case] values: &[(&'static str, &str)],
+        #[case] expected: Option<Duration>,
+    ) {
+        assert_eq!(quota_delay(&headers(values)), expected);
+    }
+
+    #[tokio::test]
+    async fn test_quota_resets_hold_back_only_the_exhausted_store() {
+        let quota = QuotaResets::default();
+        quota.record(
+            "exhausted-store",
+            &headers(&[(REQUESTS_LEFT_HEADER, "0"), (TIME_RESET_HEADER, "200")]),
+        );
+        quota.record(
+            "other-store",
+            &headers(&[(REQUESTS_LEFT_HEADER, "5"), (TIME_RESET_HEADER, "200")]),
+        );
+
+        let started = Instant::now();
+        quota.wait("other-store").await;
+        assert!(started.elapsed() < Duration::from_millis(100));
+
+        quota.wait("exhausted-store").await;
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        quota.record("exhausted-store", &headers(&[(REQUESTS_LEFT_HEADER, "10")]));
+        let started = Instant::now();
+        quota.wait("exhausted-store").await;
+        assert!(started.elapsed() < Duration::from_millis(100));
+    }
+
+    #[rstest]
+    #[case(0, BASE_DELAY, BASE_DELAY * 2)]
+    #[case(1, BASE_DELAY * 2, BASE_DELAY * 4)]
+    #[case(2, BASE_DELAY * 4, BASE_DELAY * 8)]
+    #[case(10, MAX_DELAY, MAX_DELAY)]
+    fn test_backoff_grows_with_jitter(
+        #[case] retries: u32,
+        #[case] min: Duration,
+        #[case] max: Duration,
+    ) {
+        let delay = backoff(retries);
+
+        assert!(min <= delay && delay <= max, "{delay:?}");
+    }
+}
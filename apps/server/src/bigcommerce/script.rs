@@ -3,6 +3,28 @@ use serde::Deserialize;
 #[derive(Deserialize)]
 pub struct ListResponse {
     pub data: Vec<GetResponse>,
+    #[serde(default)]
+    pub meta: Meta,
+}
+
+impl ListResponse {
+    /// Whether the listing continues after the requested `page`
+    ///
+    /// An empty page ends the listing even if the pagination claims otherwise
+    pub fn has_page_after(&self, page: u32) -> bool {
+        !self.data.is_empty() && page < self.meta.pagination.total_pages
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct Meta {
+    #[serde(default)]
+    pub pagination: Pagination,
+}
+
+#[derive(Deserialize, Default)]
+pub struct Pagination {
+    pub total_pages: u32,
 }
 
 #[derive(Deserialize)]
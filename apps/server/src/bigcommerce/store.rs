@@ -6,6 +6,11 @@ use serde::{Deserialize, Serialize};
 #[derive(Deserialize, Serialize)]
 pub struct Information {
     pub secure_url: String,
+    #[serde(default)]
+    pub name: String,
+    /// e.g. `live` or `prelaunch`
+    #[serde(default)]
+    pub status: String,
 }
 
 #[derive(Debug)]
@@ -0,0 +1,97 @@
+use serde::Deserialize;
+
+/// Header carrying the token that authenticates webhooks we registered
+pub const TOKEN_HEADER: &str = "X-SWU-Webhook-Token";
+
+/// Webhooks registered for every store at install time
+pub const SCOPES: [&str; 3] = [
+    "store/app/uninstalled",
+    "store/information/updated",
+    "store/channel/*",
+];
+
+#[derive(Deserialize)]
+pub struct ListResponse {
+    pub data: Vec<GetResponse>,
+}
+
+#[derive(Deserialize)]
+pub struct GetResponse {
+    pub id: i64,
+    pub scope: String,
+    pub destination: String,
+    pub is_active: bool,
+}
+
+/// Kind of change a webhook event notifies about
+#[derive(Debug, PartialEq, Eq)]
+pub enum EventKind {
+    AppUninstalled,
+    InformationUpdated,
+    ChannelChanged,
+    Unknown,
+}
+
+/// Payload BigCommerce sends to the webhook destination
+#[derive(Deserialize, Debug)]
+pub struct Event {
+    pub scope: String,
+    producer: String,
+}
+
+impl Event {
+    pub fn get_store_hash(&self) -> Result<&str, anyhow::Error> {
+        self.producer
+            .split_once('/')
+            .map(|x| x.1)
+            .filter(|store_hash| !store_hash.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Producer did not have correct format"))
+    }
+
+    pub fn kind(&self) -> EventKind {
+        match self.scope.as_str() {
+            "store/app/uninstalled" => EventKind::AppUninstalled,
+            "store/information/updated" => EventKind::InformationUpdated,
+            scope if scope.starts_with("store/channel/") => EventKind::ChannelChanged,
+            _ => EventKind::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("store/app/uninstalled", EventKind::AppUninstalled)]
+    #[case("store/information/updated", EventKind::InformationUpdated)]
+    #[case("store/channel/created", EventKind::ChannelChanged)]
+    #[case("store/channel/updated", EventKind::ChannelChanged)]
+    #[case("store/order/created", EventKind::Unknown)]
+    fn test_event_kind(#[case] scope: &str, #[case] expected: EventKind) {
+        let event = Event {
+            scope: scope.to_owned(),
+            producer: "stores/test-store".to_owned(),
+        };
+
+        assert_eq!(event.kind(), expected);
+        assert_eq!(event.get_store_hash().unwrap(), "test-store");
+    }
+
+    #[test]
+    fn test_event_without_store_hash() {
+        let event: Event = serde_json::from_value(serde_json::json!({
+            "scope": "store/app/uninstalled",
+            "store_id": "1025646",
+            "data": {"type": "store", "id": 1025646},
+            "hash": "f2d2a0a2f6b9b8c0e0d7f0a0c5f0e2f2b6f2b2e2",
+            "created_at": 1_561_482_670,
+            "producer": "stores"
+        }))
+        .unwrap();
+
+        assert!(event.get_store_hash().is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Upper bound on entries so that arbitrary keys cannot grow the cache without limit
+const MAX_ENTRIES: usize = 1_000;
+
+/// In-memory cache shared across requests where entries expire after `ttl`
+#[derive(Clone)]
+pub struct TtlCache<K, V> {
+    ttl: Duration,
+    entries: Arc<Mutex<Entries<K, V>>>,
+}
+
+struct Entries<K, V> {
+    values: HashMap<K, (Instant, V)>,
+    /// Keys in the order they were inserted, which is also the order they expire in
+    insertions: VecDeque<(Instant, K)>,
+}
+
+impl<K: Eq + Hash, V> Entries<K, V> {
+    /// Drops the oldest insertion, which is stale when its key was inserted again since
+    fn pop_oldest(&mut self) {
+        if let Some((inserted_at, key)) = self.insertions.pop_front() {
+            if self
+                .values
+                .get(&key)
+                .is_some_and(|(value_inserted_at, _)| *value_inserted_at == inserted_at)
+            {
+                self.values.remove(&key);
+            }
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> TtlCache<K, V> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Arc::new(Mutex::new(Entries {
+                values: HashMap::new(),
+                insertions: VecDeque::new(),
+            })),
+        }
+    }
+
+    pub const fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let entries = self.entries.lock().expect("cache lock is poisoned");
+
+        entries
+            .values
+            .get(key)
+            .filter(|(inserted_at, _)| inserted_at.elapsed() < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries.lock().expect("cache lock is poisoned");
+
+        while entries
+            .insertions
+            .front()
+            .is_some_and(|(inserted_at, _)| inserted_at.elapsed() >= self.ttl)
+        {
+            entries.pop_oldest();
+        }
+        // evicting the oldest entry keeps the popular ones, which are inserted again on expiry
+        while entries.values.len() >= MAX_ENTRIES && !entries.values.contains_key(&key) {
+            entries.pop_oldest();
+        }
+
+        let now = Instant::now();
+        entries.insertions.push_back((now, key.clone()));
+        entries.values.insert(key, (now, value));
+    }
+
+    /// Drops every entry, for when the data they were computed from changed
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().expect("cache lock is poisoned");
+        entries.values.clear();
+        entries.insertions.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_returns_value_until_expired() {
+        let cache = TtlCache::new(Duration::from_secs(60));
+        cache.insert("key", 1);
+        assert_eq!(cache.get(&"key"), Some(1));
+        assert_eq!(cache.get(&"other"), None);
+
+        cache.clear();
+        assert_eq!(cache.get(&"key"), None);
+
+        let cache = TtlCache::new(Duration::ZERO);
+        cache.insert("key", 1);
+        assert_eq!(cache.get(&"key"), None);
+    }
+
+    #[test]
+    fn test_cache_evicts_oldest_entry_when_full() {
+        let cache = TtlCache::new(Duration::from_secs(60));
+        cache.insert(0, 0);
+        cache.insert(1, 1);
+        cache.insert(0, 0);
+
+        for key in 2..=MAX_ENTRIES {
+            cache.insert(key, key);
+        }
+
+        assert_eq!(cache.get(&1), None, "The oldest entry should be evicted");
+        assert_eq!(cache.get(&0), Some(0));
+        assert_eq!(cache.get(&MAX_ENTRIES), Some(MAX_ENTRIES));
+    }
+}
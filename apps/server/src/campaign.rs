@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::liq_pay::{Currency, InputError, InputQuery};
+
+/// Slug of the campaign used by checkouts that do not name one
+pub const GENERAL_CAMPAIGN: &str = "general";
+
+/// Fundraiser that checkouts are made for
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Campaign {
+    pub slug: String,
+    pub description: String,
+    pub currencies: Vec<Currency>,
+    /// Currency preselected on the landing page
+    pub default_currency: Currency,
+    pub preset_amounts: Vec<f64>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub starts_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub ends_at: Option<OffsetDateTime>,
+}
+
+/// Settings of a campaign that admins can change
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CampaignSettings {
+    pub description: String,
+    pub currencies: Vec<Currency>,
+    pub default_currency: Currency,
+    pub preset_amounts: Vec<f64>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub starts_at: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub ends_at: Option<OffsetDateTime>,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum CampaignError {
+    #[error("Slug must be 1 to 50 lowercase letters, digits or dashes.")]
+    InvalidSlug,
+
+    #[error("Description must not be empty.")]
+    EmptyDescription,
+
+    #[error("At least one currency must be accepted.")]
+    NoCurrencies,
+
+    #[error("Default currency {currency} is not one of the accepted currencies.")]
+    DefaultCurrencyNotAccepted { currency: &'static str },
+
+    #[error("Preset amounts must be positive.")]
+    InvalidPresetAmount,
+
+    #[error("Campaign must end after it starts.")]
+    EndsBeforeStart,
+}
+
+impl Campaign {
+    /// Campaign that has been used since before campaigns could be configured
+    ///
+    /// It accepts the currencies `LiqPay` accepts, currencies that need Stripe
+    /// are only offered once a stored general campaign lists them
+    pub fn general() -> Self {
+        Self {
+            slug: GENERAL_CAMPAIGN.to_owned(),
+            description: "Support BigCommerce colleagues defending Ukraine".to_owned(),
+            currencies: Currency::LIQ_PAY.to_vec(),
+            default_currency: Currency::USD,
+            preset_amounts: vec![10.0, 25.0, 50.0, 100.0],
+            starts_at: None,
+            ends_at: None,
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Will return `CampaignError` if the settings do not make a campaign donors can check out
+    pub fn from_settings(slug: &str, settings: CampaignSettings) -> Result<Self, CampaignError> {
+        if slug.is_empty()
+            || slug.len() > 50
+            || !slug
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return Err(CampaignError::InvalidSlug);
+        }
+        if settings.description.trim().is_empty() {
+            return Err(CampaignError::EmptyDescription);
+        }
+        if settings.currencies.is_empty() {
+            return Err(CampaignError::NoCurrencies);
+        }
+        if !settings.currencies.contains(&settings.default_currency) {
+            return Err(CampaignError::DefaultCurrencyNotAccepted {
+                currency: settings.default_currency.as_str(),
+            });
+        }
+        if settings
+            .preset_amounts
+            .iter()
+            .any(|amount| !amount.is_finite() || *amount <= 0.0)
+        {
+            return Err(CampaignError::InvalidPresetAmount);
+        }
+        if let (Some(starts_at), Some(ends_at)) = (settings.starts_at, settings.ends_at) {
+            if ends_at <= starts_at {
+                return Err(CampaignError::EndsBeforeStart);
+            }
+        }
+
+        Ok(Self {
+            slug: slug.to_owned(),
+            description: settings.description,
+            currencies: settings.currencies,
+            default_currency: settings.default_currency,
+            preset_amounts: settings.preset_amounts,
+            starts_at: settings.starts_at,
+            ends_at: settings.ends_at,
+        })
+    }
+
+    pub fn is_active(&self, now: OffsetDateTime) -> bool {
+        self.starts_at.is_none_or(|starts_at| starts_at <= now)
+            && self.ends_at.is_none_or(|ends_at| now < ends_at)
+    }
+
+    /// # Errors
+    ///
+    /// Will return `InputError` if the campaign does not accept the currency of `query`
+    pub fn validate(&self, query: &InputQuery) -> Result<(), InputError> {
+        if self.currencies.contains(&query.currency) {
+            Ok(())
+        } else {
+            Err(InputError::CurrencyNotAllowed {
+                currency: query.currency.as_str(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use time::{macros::datetime, Duration};
+
+    use super::*;
+
+    #[rstest]
+    #[case(None, None, true)]
+    #[case(Some(Duration::days(-1)), None, true)]
+    #[case(Some(Duration::days(1)), None, false)]
+    #[case(None, Some(Duration::days(1)), true)]
+    #[case(None, Some(Duration::ZERO), false)]
+    #[case(Some(Duration::days(-2)), Some(Duration::days(-1)), false)]
+    fn test_is_active(
+        #[case] starts_in: Option<Duration>,
+        #[case] ends_in: Option<Duration>,
+        #[case] expected: bool,
+    ) {
+        let now = datetime!(2024-11-01 12:00 UTC);
+        let campaign = Campaign {
+            starts_at: starts_in.map(|duration| now + duration),
+            ends_at: ends_in.map(|duration| now + duration),
+            ..Campaign::general()
+        };
+
+        assert_eq!(campaign.is_active(now), expected);
+    }
+
+    #[rstest]
+    #[case(Currency::UAH, Ok(()))]
+    #[case(Currency::USD, Err(InputError::CurrencyNotAllowed { currency: "USD" }))]
+    fn test_validate_currency(
+        #[case] currency: Currency,
+        #[case] expected: Result<(), InputError>,
+    ) {
+        let campaign = Campaign {
+            currencies: vec![Currency::UAH],
+            ..Campaign::general()
+        };
+        let query = InputQuery {
+            currency,
+            ..InputQuery::for_tests()
+        };
+
+        assert_eq!(campaign.validate(&query), expected);
+    }
+
+    #[rstest]
+    #[case("winter-2025", Currency::UAH, None)]
+    #[case("Winter", Currency::UAH, Some(CampaignError::InvalidSlug))]
+    #[case("", Currency::UAH, Some(CampaignError::InvalidSlug))]
+    #[case(
+        "winter",
+        Currency::USD,
+        Some(CampaignError::DefaultCurrencyNotAccepted { currency: "USD" })
+    )]
+    fn test_from_settings(
+        #[case] slug: &str,
+        #[case] default_currency: Currency,
+        #[case] expected_error: Option<CampaignError>,
+    ) {
+        let settings = CampaignSettings {
+            description: "Winter fundraiser".to_owned(),
+            currencies: vec![Currency::UAH, Currency::EUR],
+            default_currency,
+            preset_amounts: vec![100.0],
+            starts_at: None,
+            ends_at: None,
+        };
+
+        assert_eq!(
+            Campaign::from_settings(slug, settings).err(),
+            expected_error
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{str::FromStr, sync::Arc};
 
 use config::{Config, ConfigError, Environment, File};
 use dotenvy::dotenv;
@@ -11,10 +11,14 @@ use sqlx::{
 };
 
 use crate::{
+    abuse::CheckoutGuard,
     bigcommerce::client::HttpAPI as BigCommerceHttpAPI,
-    liq_pay::HttpAPI as LiqPayHttpAPI,
+    liq_pay::{Currency, HttpAPI as LiqPayHttpAPI, Language},
+    payments::Payments,
+    receipts::Mailer,
     startup::get_connection_pool,
-    state::{AppState, SharedState},
+    state::{AppState, DonationTotalsCache, SharedState},
+    stripe::HttpAPI as StripeHttpAPI,
 };
 
 #[derive(Deserialize, Clone)]
@@ -23,6 +27,11 @@ pub struct Configuration {
     pub application: Application,
     pub bigcommerce: BigCommerce,
     pub liq_pay: LiqPay,
+    pub stripe: Stripe,
+    pub pay_limits: PayLimits,
+    pub checkout: CheckoutOptions,
+    pub smtp: Smtp,
+    pub reconciliation: Reconciliation,
 }
 
 #[derive(Deserialize, Clone)]
@@ -43,6 +52,8 @@ pub struct BigCommerce {
     pub client_id: String,
     pub client_secret: Secret<String>,
     pub install_redirect_uri: String,
+    /// Destination of the webhooks registered for every store, i.e. `/bigcommerce/webhooks`
+    pub webhook_url: String,
 
     pub api_base_url: String,
     pub login_base_url: String,
@@ -79,12 +90,130 @@ impl Database {
 pub struct LiqPay {
     pub public_key: Secret<String>,
     pub private_key: Secret<String>,
+    /// Page donors are sent back to after checkout
+    pub result_url: String,
+    /// Endpoint that receives payment callbacks
+    pub server_url: String,
+    /// Forced on outside of production so test checkouts never charge real money
+    pub sandbox: bool,
+
+    pub api_base_url: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout: u16,
+}
+
+impl LiqPay {
+    pub fn client(&self) -> LiqPayHttpAPI {
+        LiqPayHttpAPI::new(
+            self.public_key.clone(),
+            self.private_key.clone(),
+            self.api_base_url.clone(),
+            self.result_url.clone(),
+            self.server_url.clone(),
+            self.sandbox,
+            std::time::Duration::from_millis(self.timeout.into()),
+        )
+    }
+}
+
+// environment variables can only hold lists as comma separated values
+serde_aux::StringOrVecToVecParser!(deserialize_comma_separated, |c| c == ',', true);
+
+#[derive(Deserialize, Clone)]
+pub struct Stripe {
+    pub secret_key: Secret<String>,
+    pub webhook_secret: Secret<String>,
+    pub result_url: String,
+    /// Currencies checked out with Stripe unless the donor picks a provider
+    #[serde(deserialize_with = "deserialize_comma_separated")]
+    pub currencies: Vec<Currency>,
+
+    pub api_base_url: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout: u16,
+}
+
+/// Protection of the public checkout endpoints against card testing
+#[derive(Deserialize, Clone)]
+pub struct PayLimits {
+    /// Checkouts a single client can create in a minute
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub requests_per_minute: u16,
+    /// Different amounts a single client can check out in an hour
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub distinct_amounts_per_hour: u16,
+    /// Proxies in front of the server that append to `X-Forwarded-For`, with none the connection address is used
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub trusted_proxy_hops: u8,
+
+    /// Largest amounts in whole units, which must not exceed what the providers accept
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_amount_usd: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_amount_eur: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_amount_uah: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_amount_pln: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_amount_ron: u32,
+}
+
+impl PayLimits {
+    pub const fn max_amount(&self, currency: &Currency) -> u32 {
+        match currency {
+            Currency::USD => self.max_amount_usd,
+            Currency::EUR => self.max_amount_eur,
+            Currency::UAH => self.max_amount_uah,
+            Currency::PLN => self.max_amount_pln,
+            Currency::RON => self.max_amount_ron,
+        }
+    }
+}
+
+/// Languages and currencies that checkouts can be requested in
+#[derive(Deserialize, Clone)]
+pub struct CheckoutOptions {
+    #[serde(deserialize_with = "deserialize_comma_separated")]
+    pub languages: Vec<Language>,
+    /// Currencies other than USD, EUR and UAH also need to be checked out with Stripe
+    #[serde(deserialize_with = "deserialize_comma_separated")]
+    pub currencies: Vec<Currency>,
+}
+
+/// Mail server that donor receipts are sent through, sending is disabled while `host` is empty
+#[derive(Deserialize, Clone)]
+pub struct Smtp {
+    pub host: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub port: u16,
+    pub username: String,
+    pub password: Secret<String>,
+    /// Upgrades the connection with STARTTLS, only turned off for a local SMTP sink
+    pub tls: bool,
+    /// Mailbox receipts are sent from, e.g. `Stand With Ukraine <receipts@example.com>`
+    pub from: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout: u16,
+}
+
+/// Job that recovers payments whose callbacks never arrived
+#[derive(Deserialize, Clone, Debug)]
+pub struct Reconciliation {
+    /// Checkouts younger than this may still be completed by the donor
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub pending_after_minutes: u16,
+    /// Checkouts older than this are considered abandoned and no longer checked
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lookback_hours: u16,
 }
 
 #[derive(Deserialize, Clone)]
 pub struct Application {
     pub base_url: String,
     pub jwt_secret: Secret<String>,
+    /// Key for operator endpoints, which stay disabled while it is empty
+    pub admin_api_key: Secret<String>,
 
     pub lightstep_access_token: Secret<String>,
 
@@ -93,6 +222,9 @@ pub struct Application {
     pub port: u16,
 }
 
+/// How long donation totals are served from memory before they are read again
+const DONATION_TOTALS_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
 const CONFIGURATION_PATH: &str = "configuration/base";
 const SERVER_WORKSPACE_PATH: &str = "apps/server";
 
@@ -103,6 +235,13 @@ impl Configuration {
     pub fn generate_from_environment() -> Result<Self, ConfigError> {
         dotenv().ok();
 
+        let environment = AppEnvironment::try_from(
+            std::env::var("APP_ENVIRONMENT")
+                .unwrap_or_else(|_| AppEnvironment::Local.as_str().to_owned())
+                .as_str(),
+        )
+        .map_err(ConfigError::Message)?;
+
         let base_path =
             std::env::current_dir().expect("Failed to determine the current directory.");
 
@@ -119,6 +258,43 @@ impl Configuration {
             .add_source(Environment::with_prefix("APP").separator("__"))
             .build()?
             .try_deserialize()
+            .and_then(|configuration: Self| configuration.for_environment(&environment))
+    }
+
+    fn for_environment(mut self, environment: &AppEnvironment) -> Result<Self, ConfigError> {
+        if let Some(currency) = self
+            .checkout
+            .currencies
+            .iter()
+            .find(|currency| !currency.is_supported_by_liq_pay())
+        {
+            if self.stripe.secret_key.expose_secret().is_empty() {
+                return Err(ConfigError::Message(format!(
+                    "checkout.currencies includes {}, which LiqPay does not accept, so stripe.secret_key must be set.",
+                    currency.as_str()
+                )));
+            }
+        }
+
+        if environment != &AppEnvironment::Production {
+            self.liq_pay.sandbox = true;
+            return Ok(self);
+        }
+
+        // providers redirect donors and send callbacks to these, which the local defaults break
+        for (key, url) in [
+            ("liq_pay.server_url", &self.liq_pay.server_url),
+            ("liq_pay.result_url", &self.liq_pay.result_url),
+            ("stripe.result_url", &self.stripe.result_url),
+        ] {
+            if !is_public_url(url) {
+                return Err(ConfigError::Message(format!(
+                    "{key} must be a public URL in production, got `{url}`."
+                )));
+            }
+        }
+
+        Ok(self)
     }
 
     pub fn get_app_state(&self) -> SharedState {
@@ -129,23 +305,43 @@ impl Configuration {
             self.bigcommerce.client_id.clone(),
             self.bigcommerce.client_secret.clone(),
             self.bigcommerce.install_redirect_uri.clone(),
+            self.bigcommerce.webhook_url.clone(),
             std::time::Duration::from_millis(self.bigcommerce.timeout.into()),
         );
-        let liq_pay_client = LiqPayHttpAPI::new(
-            self.liq_pay.public_key.clone(),
-            self.liq_pay.private_key.clone(),
+        let stripe_client = StripeHttpAPI::new(
+            self.stripe.secret_key.clone(),
+            self.stripe.webhook_secret.clone(),
+            self.stripe.api_base_url.clone(),
+            self.stripe.result_url.clone(),
+            std::time::Duration::from_millis(self.stripe.timeout.into()),
         );
 
         Arc::new(AppState {
             db_pool,
             base_url: self.application.base_url.clone(),
             jwt_secret: self.application.jwt_secret.clone(),
+            admin_api_key: self.application.admin_api_key.clone(),
             bigcommerce_client,
-            liq_pay_client,
+            payments: Payments::new(
+                self.liq_pay.client(),
+                stripe_client,
+                self.stripe.currencies.clone(),
+            ),
+            donation_totals: DonationTotalsCache::new(DONATION_TOTALS_TTL),
+            mailer: (!self.smtp.host.is_empty())
+                .then(|| Mailer::new(&self.smtp, &self.application.base_url)),
+            checkout_guard: CheckoutGuard::new(self.pay_limits.clone(), self.checkout.clone()),
         })
     }
 }
 
+fn is_public_url(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(ToOwned::to_owned))
+        .is_some_and(|host| !matches!(host.as_str(), "localhost" | "127.0.0.1" | "[::1]"))
+}
+
 pub struct LightstepAccessToken(pub Secret<String>);
 
 impl AsRef<Secret<String>> for LightstepAccessToken {
@@ -207,4 +403,83 @@ mod tests {
                 .expect("Could not convert string to Environment")
         );
     }
+
+    fn base_configuration(overrides: &[(&str, &str)]) -> Configuration {
+        overrides
+            .iter()
+            .fold(
+                Config::builder().add_source(File::from(
+                    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(CONFIGURATION_PATH),
+                )),
+                |builder, (key, value)| builder.set_override(*key, *value).unwrap(),
+            )
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_local_environment_uses_liq_pay_sandbox() {
+        let configuration = base_configuration(&[])
+            .for_environment(&AppEnvironment::Local)
+            .expect("Local defaults should be valid");
+
+        assert!(configuration.liq_pay.sandbox);
+    }
+
+    #[rstest]
+    #[case("USD,EUR,UAH", "", true)]
+    #[case("USD,PLN", "", false)]
+    #[case("USD,PLN", "sk_test", true)]
+    fn test_stripe_only_currencies_need_stripe(
+        #[case] currencies: &str,
+        #[case] stripe_secret_key: &str,
+        #[case] expected_valid: bool,
+    ) {
+        let configuration = base_configuration(&[
+            ("checkout.currencies", currencies),
+            ("stripe.secret_key", stripe_secret_key),
+        ])
+        .for_environment(&AppEnvironment::Local);
+
+        assert_eq!(configuration.is_ok(), expected_valid);
+    }
+
+    #[rstest]
+    #[case("liq_pay.server_url")]
+    #[case("liq_pay.result_url")]
+    #[case("stripe.result_url")]
+    fn test_production_environment_fails_with_local_url(#[case] local_key: &str) {
+        let overrides = [
+            ("liq_pay.server_url", "https://example.com/pay/callback"),
+            ("liq_pay.result_url", "https://example.com"),
+            ("stripe.result_url", "https://example.com"),
+        ];
+        let overrides = overrides
+            .iter()
+            .filter(|(key, _)| *key != local_key)
+            .copied()
+            .collect::<Vec<_>>();
+
+        let error = base_configuration(&overrides)
+            .for_environment(&AppEnvironment::Production)
+            .err()
+            .expect("Production should not use local URLs");
+
+        assert!(error.to_string().starts_with(local_key));
+    }
+
+    #[test]
+    fn test_production_environment_accepts_public_urls() {
+        let configuration = base_configuration(&[
+            ("liq_pay.server_url", "https://example.com/pay/callback"),
+            ("liq_pay.result_url", "https://example.com"),
+            ("stripe.result_url", "https://example.com"),
+        ])
+        .for_environment(&AppEnvironment::Production)
+        .expect("Public URLs should be valid");
+
+        assert!(!configuration.liq_pay.sandbox);
+    }
 }
@@ -4,23 +4,43 @@ use anyhow::Context;
 use email_address::EmailAddress;
 use secrecy::Secret;
 use serde::{Deserialize, Serialize};
-use sqlx::{types::time::OffsetDateTime, PgPool};
+use sqlx::{types::time::OffsetDateTime, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-use crate::bigcommerce::{script::Script, store::APIToken};
+use crate::{
+    bigcommerce::{
+        auth::User,
+        script::Script,
+        store::{APIToken, Information},
+    },
+    campaign::{Campaign, GENERAL_CAMPAIGN},
+    exchange_rates::{ExchangeRate, NormalizedTotal},
+    liq_pay::{Currency, Payment, PaymentStatus, SubscribePeriod},
+    payments::Provider,
+};
 
+/// Records the credentials of an install along with the scopes that were granted and the installer
 #[tracing::instrument(name = "write store credentials to database", skip(store, pool))]
-pub async fn write_store_credentials(store: &APIToken, pool: &PgPool) -> Result<(), sqlx::Error> {
+pub async fn write_store_credentials(
+    store: &APIToken,
+    scope: &str,
+    installer: &User,
+    pool: &PgPool,
+) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
-        INSERT INTO stores (id, store_hash, access_token, installed_at, uninstalled)
-        VALUES ($1, $2, $3, $4, false)
-        ON CONFLICT (store_hash) DO UPDATE set access_token = $3, installed_at = $4, uninstalled = false;
+        INSERT INTO stores (id, store_hash, access_token, installed_at, uninstalled, scope, installed_by_user_id, installed_by_email)
+        VALUES ($1, $2, $3, $4, false, $5, $6, $7)
+        ON CONFLICT (store_hash) DO UPDATE
+        SET access_token = $3, installed_at = $4, uninstalled = false, scope = $5, installed_by_user_id = $6, installed_by_email = $7;
         "#,
         Uuid::new_v4(),
         store.get_store_hash(),
         store.get_access_token(),
-        OffsetDateTime::now_utc()
+        OffsetDateTime::now_utc(),
+        scope,
+        installer.id,
+        installer.email,
     )
     .execute(pool)
     .await?;
@@ -28,6 +48,21 @@ pub async fn write_store_credentials(store: &APIToken, pool: &PgPool) -> Result<
     Ok(())
 }
 
+/// Scopes granted at install time, missing for stores installed before they were recorded
+#[tracing::instrument(name = "read store scope from database", skip(pool))]
+pub async fn read_store_scope(
+    store_hash: &str,
+    pool: &PgPool,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query!(
+        "SELECT scope FROM stores WHERE store_hash = $1;",
+        store_hash
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|row| row.and_then(|row| row.scope))
+}
+
 #[tracing::instrument(name = "read store credentials from database", skip(store_hash, pool))]
 pub async fn read_store_credentials(
     store_hash: &str,
@@ -48,6 +83,49 @@ pub async fn read_store_credentials(
     ))
 }
 
+/// Records a staff member who opened the app, unless the store was never installed
+#[tracing::instrument(name = "write store user to database", skip(store_hash, pool))]
+pub async fn write_store_user(
+    store_hash: &str,
+    user: &User,
+    pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let now = OffsetDateTime::now_utc();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO store_users (store_hash, user_id, email, created_at, last_seen_at)
+        SELECT store_hash, $2, $3, $4, $4 FROM stores WHERE store_hash = $1
+        ON CONFLICT (store_hash, user_id) DO UPDATE SET email = $3, last_seen_at = $4;
+        "#,
+        store_hash,
+        user.id,
+        user.email,
+        now,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "delete store user from database", skip(store_hash, pool))]
+pub async fn delete_store_user(
+    store_hash: &str,
+    user_id: i32,
+    pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM store_users WHERE store_hash = $1 AND user_id = $2;",
+        store_hash,
+        user_id,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
 #[tracing::instrument(
     name = "write store is uninstalled in database",
     skip(store_hash, pool)
@@ -70,6 +148,33 @@ pub async fn write_store_as_uninstalled(
     Ok(())
 }
 
+#[tracing::instrument(
+    name = "write store information in database",
+    skip(store_hash, information, pool)
+)]
+pub async fn write_store_information(
+    store_hash: &str,
+    information: &Information,
+    pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE stores
+        SET name = $1, secure_url = $2, status = $3, information_updated_at = $4
+        WHERE store_hash = $5;
+        "#,
+        information.name,
+        information.secure_url,
+        information.status,
+        OffsetDateTime::now_utc(),
+        store_hash,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
 #[tracing::instrument(
     name = "write store published status in database",
     skip(store_hash, pool)
@@ -77,16 +182,18 @@ pub async fn write_store_as_uninstalled(
 pub async fn write_store_published(
     store_hash: &str,
     status: bool,
+    user_id: Option<i32>,
     pool: &PgPool,
 ) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
         UPDATE stores
-        SET published = $1
+        SET published = $1, published_changed_by = $3
         WHERE store_hash = $2;
         "#,
         status,
         store_hash,
+        user_id,
     )
     .execute(pool)
     .await?;
@@ -98,6 +205,7 @@ pub async fn write_store_published(
 pub async fn write_unpublish_feedback(
     store_hash: &str,
     reason: &str,
+    user_id: Option<i32>,
     pool: &PgPool,
 ) -> Result<(), sqlx::Error> {
     // get at most the first 1000 characters
@@ -105,12 +213,13 @@ pub async fn write_unpublish_feedback(
 
     sqlx::query!(
         r#"
-        INSERT INTO unpublish_events (store_hash, unpublished_at, reason)
-        VALUES ($1, $2, $3);
+        INSERT INTO unpublish_events (store_hash, unpublished_at, reason, user_id)
+        VALUES ($1, $2, $3, $4);
         "#,
         store_hash,
         OffsetDateTime::now_utc(),
-        reason
+        reason,
+        user_id,
     )
     .execute(pool)
     .await?;
@@ -230,7 +339,7 @@ pub async fn read_widget_configuration(
     Ok(widget_configuration)
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub enum Charity {
     Unicef,
@@ -417,6 +526,685 @@ pub async fn write_universal_widget_event(
     Ok(())
 }
 
+/// Records the latest state of a charge with an entry in its status history
+///
+/// Every charge of an order is a separate donation, identified by the payment id reported by the provider
+///
+/// Returns `false` without changing the donation if its recorded status cannot change to the status of `payment`
+#[tracing::instrument(name = "write donation to database", skip(db_pool))]
+pub async fn write_donation(
+    payment: &Payment,
+    provider: Provider,
+    db_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let now = OffsetDateTime::now_utc();
+    // providers that do not report a payment id only charge an order once
+    let payment_id = payment.payment_id.unwrap_or_default();
+    let mut transaction = db_pool.begin().await?;
+
+    let previous_status = sqlx::query!(
+        "SELECT status, raw_status FROM donations WHERE order_id = $1 AND payment_id = $2 FOR UPDATE;",
+        payment.order_id,
+        payment_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    .map(|row| (row.status, row.raw_status));
+
+    let previous = previous_status
+        .as_ref()
+        .map(|(status, _)| status.parse().unwrap_or(PaymentStatus::Pending));
+    if previous
+        .as_ref()
+        .is_some_and(|previous| !previous.can_change_to(&payment.status))
+    {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO donations (order_id, payment_id, action, status, raw_status, amount, currency, provider, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $9)
+        ON CONFLICT (order_id, payment_id) DO UPDATE
+        SET action = $3, status = $4, raw_status = $5, amount = $6, currency = $7, provider = $8, updated_at = $9;
+        "#,
+        payment.order_id,
+        payment_id,
+        payment.action,
+        payment.status.as_str(),
+        payment.raw_status,
+        payment.amount,
+        payment.currency.as_str(),
+        provider.as_str(),
+        now,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    // statuses that are all read as pending are still told apart in the history
+    let (previous_status, previous_raw_status) = previous_status.unzip();
+    if previous_raw_status.as_ref() != Some(&payment.raw_status) {
+        sqlx::query!(
+            r#"
+            INSERT INTO donation_status_changes (order_id, payment_id, previous_status, status, raw_status, amount, err_code, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8);
+            "#,
+            payment.order_id,
+            payment_id,
+            previous_status,
+            payment.status.as_str(),
+            payment.raw_status,
+            payment.amount,
+            payment.err_code,
+            now,
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(true)
+}
+
+/// Checkouts made between `from` and `until` that have neither a charge nor a subscription in a final status yet
+#[tracing::instrument(name = "read pending checkouts from database", skip(db_pool))]
+pub async fn read_pending_checkouts(
+    from: OffsetDateTime,
+    until: OffsetDateTime,
+    db_pool: &PgPool,
+) -> Result<Vec<(String, Provider)>, anyhow::Error> {
+    let final_statuses = PaymentStatus::FINAL.map(|status| status.as_str().to_owned());
+
+    sqlx::query!(
+        r#"
+        SELECT checkouts.order_id, checkouts.provider
+        FROM checkouts
+        WHERE checkouts.created_at >= $1 AND checkouts.created_at < $2
+            AND NOT EXISTS (
+                SELECT FROM donations
+                WHERE donations.order_id = checkouts.order_id AND donations.status = ANY($3)
+            )
+            AND NOT EXISTS (
+                SELECT FROM subscriptions
+                WHERE subscriptions.order_id = checkouts.order_id AND subscriptions.status = ANY($3)
+            )
+        ORDER BY checkouts.created_at;
+        "#,
+        from,
+        until,
+        &final_statuses,
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to read pending checkouts")?
+    .into_iter()
+    .map(|row| {
+        Provider::try_from(row.provider.as_str())
+            .map(|provider| (row.order_id, provider))
+            .map_err(anyhow::Error::msg)
+    })
+    .collect()
+}
+
+/// Charge as recorded from payment callbacks along with the checkout it came from
+#[derive(Debug, Serialize)]
+pub struct DonationRecord {
+    pub order_id: String,
+    pub payment_id: i64,
+    pub provider: String,
+    pub action: String,
+    pub status: String,
+    pub raw_status: String,
+    pub amount: f64,
+    pub currency: String,
+    pub campaign: Option<String>,
+    pub store_hash: Option<String>,
+    pub source: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+}
+
+#[tracing::instrument(name = "read donations from database", skip(db_pool))]
+pub async fn read_donations(
+    order_id: &str,
+    db_pool: &PgPool,
+) -> Result<Vec<DonationRecord>, sqlx::Error> {
+    sqlx::query_as!(
+        DonationRecord,
+        r#"
+        SELECT donations.order_id, payment_id, donations.provider, action, status, raw_status, amount, currency,
+            campaign as "campaign?", store_hash, source, donations.created_at, updated_at
+        FROM donations
+        LEFT JOIN checkouts ON checkouts.order_id = donations.order_id
+        WHERE donations.order_id = $1
+        ORDER BY donations.created_at, payment_id;
+        "#,
+        order_id
+    )
+    .fetch_all(db_pool)
+    .await
+}
+
+#[derive(Debug, Serialize)]
+pub struct DonationStatusChange {
+    pub payment_id: i64,
+    pub previous_status: Option<String>,
+    pub status: String,
+    pub raw_status: String,
+    pub amount: f64,
+    pub err_code: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+#[tracing::instrument(name = "read donation status changes from database", skip(db_pool))]
+pub async fn read_donation_status_changes(
+    order_id: &str,
+    db_pool: &PgPool,
+) -> Result<Vec<DonationStatusChange>, sqlx::Error> {
+    sqlx::query_as!(
+        DonationStatusChange,
+        r#"
+        SELECT payment_id, previous_status, status, raw_status, amount, err_code, created_at
+        FROM donation_status_changes
+        WHERE order_id = $1
+        ORDER BY id;
+        "#,
+        order_id
+    )
+    .fetch_all(db_pool)
+    .await
+}
+
+#[tracing::instrument(name = "write checkout to database", skip(db_pool))]
+pub async fn write_checkout(
+    order_id: &str,
+    provider: Provider,
+    campaign: &str,
+    store_hash: Option<&str>,
+    source: Option<&Charity>,
+    receipt_email: Option<&str>,
+    db_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO checkouts (order_id, provider, campaign, store_hash, source, receipt_email, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7);
+        "#,
+        order_id,
+        provider.as_str(),
+        campaign,
+        store_hash.and_then(store_hash_field_from_str),
+        source.map(Charity::to_value_string),
+        receipt_email,
+        OffsetDateTime::now_utc(),
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CurrencyTotal {
+    pub currency: String,
+    pub amount: f64,
+    /// Checkouts with a successful charge, so a subscription counts once
+    /// and a donor who gives twice counts twice
+    pub donations: i64,
+}
+
+#[tracing::instrument(name = "read donation totals from database", skip(db_pool))]
+pub async fn read_donation_totals(
+    campaign: Option<&str>,
+    store_hash: Option<&str>,
+    db_pool: &PgPool,
+) -> Result<Vec<CurrencyTotal>, sqlx::Error> {
+    let confirmed = PaymentStatus::CONFIRMED.map(|status| status.as_str().to_owned());
+
+    sqlx::query_as!(
+        CurrencyTotal,
+        r#"
+        SELECT donations.currency, SUM(donations.amount) AS "amount!", COUNT(DISTINCT donations.order_id) AS "donations!"
+        FROM donations
+        LEFT JOIN checkouts ON checkouts.order_id = donations.order_id
+        WHERE donations.status = ANY($1)
+        AND ($2::VARCHAR IS NULL OR COALESCE(checkouts.campaign, $4) = $2)
+        AND ($3::VARCHAR IS NULL OR checkouts.store_hash = $3)
+        GROUP BY donations.currency
+        ORDER BY donations.currency;
+        "#,
+        &confirmed,
+        campaign,
+        store_hash,
+        // donations made before campaigns existed were all for the general campaign
+        GENERAL_CAMPAIGN,
+    )
+    .fetch_all(db_pool)
+    .await
+}
+
+/// Donation totals per currency along with their sum in the requested reporting currency
+#[derive(Debug, Clone, Serialize)]
+pub struct DonationSummary {
+    pub totals: Vec<CurrencyTotal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<NormalizedTotal>,
+}
+
+#[tracing::instrument(name = "write exchange rates to database", skip(db_pool))]
+pub async fn write_exchange_rates(
+    rates: &[ExchangeRate],
+    db_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let now = OffsetDateTime::now_utc();
+    let mut transaction = db_pool.begin().await?;
+
+    for rate in rates {
+        sqlx::query!(
+            r#"
+            INSERT INTO exchange_rates (currency, base_currency, rate, updated_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (currency, base_currency) DO UPDATE SET rate = $3, updated_at = $4;
+            "#,
+            rate.currency.as_str(),
+            rate.base_currency.as_str(),
+            rate.rate,
+            now,
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "read exchange rates from database", skip(db_pool))]
+pub async fn read_exchange_rates(db_pool: &PgPool) -> Result<Vec<ExchangeRate>, anyhow::Error> {
+    sqlx::query!(
+        r#"
+        SELECT currency, base_currency, rate
+        FROM exchange_rates
+        ORDER BY currency, base_currency;
+        "#,
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to read exchange rates")?
+    .into_iter()
+    .map(|row| {
+        Ok(ExchangeRate {
+            currency: row.currency.parse().map_err(anyhow::Error::msg)?,
+            base_currency: row.base_currency.parse().map_err(anyhow::Error::msg)?,
+            rate: row.rate,
+        })
+    })
+    .collect()
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SourceTotal {
+    pub source: Option<String>,
+    pub currency: String,
+    pub amount: f64,
+    /// Checkouts with a successful charge, counted like `CurrencyTotal::donations`
+    pub donations: i64,
+}
+
+#[tracing::instrument(name = "read store donation totals from database", skip(db_pool))]
+pub async fn read_store_donation_totals(
+    store_hash: &str,
+    db_pool: &PgPool,
+) -> Result<Vec<SourceTotal>, sqlx::Error> {
+    let confirmed = PaymentStatus::CONFIRMED.map(|status| status.as_str().to_owned());
+
+    sqlx::query_as!(
+        SourceTotal,
+        r#"
+        SELECT checkouts.source, donations.currency, SUM(donations.amount) AS "amount!", COUNT(DISTINCT donations.order_id) AS "donations!"
+        FROM checkouts
+        JOIN donations ON donations.order_id = checkouts.order_id
+        WHERE checkouts.store_hash = $1 AND donations.status = ANY($2)
+        GROUP BY checkouts.source, donations.currency
+        ORDER BY checkouts.source NULLS LAST, donations.currency;
+        "#,
+        store_hash,
+        &confirmed,
+    )
+    .fetch_all(db_pool)
+    .await
+}
+
+#[tracing::instrument(name = "write prepared subscription to database", skip(db_pool))]
+pub async fn write_prepared_subscription(
+    order_id: &str,
+    amount: f64,
+    currency: &Currency,
+    periodicity: &SubscribePeriod,
+    start: OffsetDateTime,
+    db_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let now = OffsetDateTime::now_utc();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (order_id, status, amount, currency, periodicity, next_charge_at, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $7)
+        ON CONFLICT (order_id) DO NOTHING;
+        "#,
+        order_id,
+        PaymentStatus::Prepared.as_str(),
+        amount,
+        currency.as_str(),
+        periodicity.as_str(),
+        start,
+        now,
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "write subscription to database", skip(db_pool))]
+pub async fn write_subscription(
+    payment: &Payment,
+    status: &PaymentStatus,
+    next_charge_at: Option<OffsetDateTime>,
+    db_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let now = OffsetDateTime::now_utc();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (order_id, status, amount, currency, next_charge_at, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $6)
+        ON CONFLICT (order_id) DO UPDATE
+        SET status = $2, amount = $3, currency = $4, next_charge_at = $5, updated_at = $6;
+        "#,
+        payment.order_id,
+        status.as_str(),
+        payment.amount,
+        payment.currency.as_str(),
+        next_charge_at,
+        now,
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct Subscription {
+    pub order_id: String,
+    pub status: String,
+    pub amount: f64,
+    pub currency: String,
+    pub periodicity: String,
+    pub next_charge_at: Option<OffsetDateTime>,
+}
+
+#[tracing::instrument(name = "read subscription from database", skip(db_pool))]
+pub async fn read_subscription(
+    order_id: &str,
+    db_pool: &PgPool,
+) -> Result<Option<Subscription>, sqlx::Error> {
+    sqlx::query_as!(
+        Subscription,
+        r#"
+        SELECT order_id, status, amount, currency, periodicity, next_charge_at FROM subscriptions
+        WHERE order_id = $1;
+        "#,
+        order_id,
+    )
+    .fetch_optional(db_pool)
+    .await
+}
+
+#[tracing::instrument(name = "read campaign from database", skip(db_pool))]
+pub async fn read_campaign(
+    slug: &str,
+    db_pool: &PgPool,
+) -> Result<Option<Campaign>, anyhow::Error> {
+    let Some(row) = sqlx::query!(
+        r#"
+        SELECT slug, description, currencies, default_currency, preset_amounts, starts_at, ends_at
+        FROM campaigns
+        WHERE slug = $1;
+        "#,
+        slug,
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Read campaign from database")?
+    else {
+        return Ok(None);
+    };
+
+    let currencies = row
+        .currencies
+        .iter()
+        .map(|currency| currency.parse::<Currency>())
+        .collect::<Result<_, _>>()
+        .map_err(anyhow::Error::msg)
+        .context("Parse campaign currencies")?;
+    let default_currency = row
+        .default_currency
+        .parse::<Currency>()
+        .map_err(anyhow::Error::msg)
+        .context("Parse campaign default currency")?;
+
+    Ok(Some(Campaign {
+        slug: row.slug,
+        description: row.description,
+        currencies,
+        default_currency,
+        preset_amounts: row.preset_amounts,
+        starts_at: row.starts_at,
+        ends_at: row.ends_at,
+    }))
+}
+
+/// Creates the campaign or replaces the settings of an existing one
+#[tracing::instrument(name = "write campaign to database", skip(db_pool))]
+pub async fn write_campaign(campaign: &Campaign, db_pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO campaigns (slug, description, currencies, default_currency, preset_amounts, starts_at, ends_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (slug) DO UPDATE SET description = $2, currencies = $3, default_currency = $4,
+            preset_amounts = $5, starts_at = $6, ends_at = $7;
+        "#,
+        campaign.slug,
+        campaign.description,
+        &campaign
+            .currencies
+            .iter()
+            .map(|currency| currency.as_str().to_owned())
+            .collect::<Vec<_>>(),
+        campaign.default_currency.as_str(),
+        &campaign.preset_amounts,
+        campaign.starts_at,
+        campaign.ends_at,
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Queues a receipt for a confirmed payment if the donor left an email at checkout
+///
+/// Receipts of a recurring donation carry the token of the link that cancels it
+///
+/// Returns `false` if there is nothing to send
+#[tracing::instrument(name = "write receipt to outbox", skip(unsubscribe_token, db_pool))]
+pub async fn write_receipt(
+    payment: &Payment,
+    unsubscribe_token: Option<&str>,
+    db_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO receipt_outbox (order_id, payment_id, email, amount, currency, campaign, paid_at, unsubscribe_token)
+        SELECT order_id, $2, receipt_email, $3, $4, campaign, $5, $6
+        FROM checkouts
+        WHERE order_id = $1 AND receipt_email IS NOT NULL
+        ON CONFLICT (order_id, payment_id) DO NOTHING;
+        "#,
+        payment.order_id,
+        // every charge of a subscription gets a receipt, other payments only have one
+        payment.payment_id.unwrap_or_default(),
+        payment.amount,
+        payment.currency.as_str(),
+        OffsetDateTime::now_utc(),
+        unsubscribe_token,
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Receipt waiting in the outbox along with the description of the campaign it was for
+#[derive(Debug, Clone, PartialEq)]
+pub struct Receipt {
+    pub id: i64,
+    pub order_id: String,
+    pub email: String,
+    pub amount: f64,
+    pub currency: String,
+    pub campaign: String,
+    pub paid_at: OffsetDateTime,
+    pub unsubscribe_token: Option<String>,
+}
+
+#[tracing::instrument(name = "read unsent receipts from database", skip(db_pool))]
+pub async fn read_unsent_receipts(
+    order_id: Option<&str>,
+    max_attempts: i32,
+    db_pool: &PgPool,
+) -> Result<Vec<Receipt>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT receipt_outbox.id, receipt_outbox.order_id, receipt_outbox.email, receipt_outbox.amount,
+            receipt_outbox.currency, receipt_outbox.paid_at, receipt_outbox.unsubscribe_token,
+            campaigns.description AS "description?"
+        FROM receipt_outbox
+        LEFT JOIN campaigns ON campaigns.slug = receipt_outbox.campaign
+        WHERE receipt_outbox.sent_at IS NULL AND receipt_outbox.attempts < $1
+        AND ($2::VARCHAR IS NULL OR receipt_outbox.order_id = $2)
+        ORDER BY receipt_outbox.id;
+        "#,
+        max_attempts,
+        order_id,
+    )
+    .fetch_all(db_pool)
+    .await
+    .map(|rows| {
+        rows.into_iter()
+            .map(|row| Receipt {
+                id: row.id,
+                order_id: row.order_id,
+                email: row.email,
+                amount: row.amount,
+                currency: row.currency,
+                // the general campaign works without being configured
+                campaign: row
+                    .description
+                    .unwrap_or_else(|| Campaign::general().description),
+                paid_at: row.paid_at,
+                unsubscribe_token: row.unsubscribe_token,
+            })
+            .collect()
+    })
+}
+
+/// Locks an unsent receipt until `transaction` ends so that it is only sent once
+///
+/// Returns `false` if the receipt was sent meanwhile or is being sent by someone else
+#[tracing::instrument(name = "lock unsent receipt in database", skip(transaction))]
+pub async fn lock_unsent_receipt(
+    id: i64,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        "SELECT id FROM receipt_outbox WHERE id = $1 AND sent_at IS NULL FOR UPDATE SKIP LOCKED;",
+        id
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .map(|row| row.is_some())
+}
+
+#[tracing::instrument(name = "write receipt as sent in database", skip(transaction))]
+pub async fn write_receipt_as_sent(
+    id: i64,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE receipt_outbox
+        SET sent_at = $2, attempts = attempts + 1, last_error = NULL
+        WHERE id = $1;
+        "#,
+        id,
+        OffsetDateTime::now_utc(),
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "write receipt as failed in database", skip(transaction))]
+pub async fn write_receipt_as_failed(
+    id: i64,
+    error: &str,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE receipt_outbox
+        SET attempts = attempts + 1, last_error = $2
+        WHERE id = $1;
+        "#,
+        id,
+        error,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "write subscription as cancelled in database", skip(db_pool))]
+pub async fn write_subscription_as_cancelled(
+    order_id: &str,
+    db_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = $1, next_charge_at = NULL, updated_at = $2
+        WHERE order_id = $3;
+        "#,
+        PaymentStatus::Unsubscribed.as_str(),
+        OffsetDateTime::now_utc(),
+        order_id,
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
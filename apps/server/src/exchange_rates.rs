@@ -0,0 +1,220 @@
+use serde::Serialize;
+
+use crate::{data::CurrencyTotal, liq_pay::Currency};
+
+/// Number of decimal places kept in normalized amounts
+const AMOUNT_PRECISION: i32 = 2;
+
+/// Value of one unit of `currency` in `base_currency`, entered by finance
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ExchangeRate {
+    pub currency: Currency,
+    pub base_currency: Currency,
+    pub rate: f64,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ImportError {
+    #[error("Line {line} must have the columns currency,base_currency,rate.")]
+    InvalidLine { line: usize },
+
+    #[error("Line {line}: {message}")]
+    InvalidCurrency { line: usize, message: String },
+
+    #[error("Line {line} must have a positive rate.")]
+    InvalidRate { line: usize },
+
+    #[error("No exchange rates were provided.")]
+    Empty,
+}
+
+/// Parses rates from CSV lines of `currency,base_currency,rate` with an optional header
+///
+/// # Errors
+///
+/// Will return `ImportError` for the first line that is not a valid exchange rate
+pub fn parse_csv(csv: &str) -> Result<Vec<ExchangeRate>, ImportError> {
+    let rates = csv
+        .lines()
+        .enumerate()
+        .map(|(index, content)| (index + 1, content.trim()))
+        .filter(|(_, content)| !content.is_empty())
+        .filter(|(line, content)| !(*line == 1 && content.starts_with("currency")))
+        .map(|(line, content)| parse_line(line, content))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if rates.is_empty() {
+        return Err(ImportError::Empty);
+    }
+
+    Ok(rates)
+}
+
+fn parse_line(line: usize, content: &str) -> Result<ExchangeRate, ImportError> {
+    let [currency, base_currency, rate] = content
+        .split(',')
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .try_into()
+        .map_err(|_| ImportError::InvalidLine { line })?;
+
+    let parse_currency = |currency: &str| {
+        currency
+            .parse::<Currency>()
+            .map_err(|message| ImportError::InvalidCurrency { line, message })
+    };
+
+    let rate = rate
+        .parse::<f64>()
+        .ok()
+        .filter(|rate| rate.is_finite() && *rate > 0.0)
+        .ok_or(ImportError::InvalidRate { line })?;
+
+    Ok(ExchangeRate {
+        currency: parse_currency(currency)?,
+        base_currency: parse_currency(base_currency)?,
+        rate,
+    })
+}
+
+/// Rate to convert `from` into `to`, using the inverse of a rate entered the other way around
+fn conversion_rate(from: &Currency, to: &Currency, rates: &[ExchangeRate]) -> Option<f64> {
+    if from == to {
+        return Some(1.0);
+    }
+
+    rates
+        .iter()
+        .find(|rate| &rate.currency == from && &rate.base_currency == to)
+        .map(|rate| rate.rate)
+        .or_else(|| {
+            rates
+                .iter()
+                .find(|rate| &rate.currency == to && &rate.base_currency == from)
+                .map(|rate| 1.0 / rate.rate)
+        })
+}
+
+/// Donations of every currency expressed in a single reporting currency
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct NormalizedTotal {
+    pub currency: Currency,
+    pub amount: f64,
+    /// Currencies left out of `amount` because there is no rate for them
+    pub missing_rates: Vec<String>,
+}
+
+pub fn normalize(
+    totals: &[CurrencyTotal],
+    rates: &[ExchangeRate],
+    reporting_currency: &Currency,
+) -> NormalizedTotal {
+    let mut amount = 0.0;
+    let mut missing_rates = vec![];
+
+    for total in totals {
+        let rate = total
+            .currency
+            .parse::<Currency>()
+            .ok()
+            .and_then(|currency| conversion_rate(&currency, reporting_currency, rates));
+
+        match rate {
+            Some(rate) => amount += total.amount * rate,
+            None => missing_rates.push(total.currency.clone()),
+        }
+    }
+
+    let scale = 10_f64.powi(AMOUNT_PRECISION);
+
+    NormalizedTotal {
+        currency: reporting_currency.clone(),
+        amount: (amount * scale).round() / scale,
+        missing_rates,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn total(currency: &str, amount: f64) -> CurrencyTotal {
+        CurrencyTotal {
+            currency: currency.to_owned(),
+            amount,
+            donations: 1,
+        }
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let rates =
+            parse_csv("currency,base_currency,rate\nUAH,USD,0.024\n\neur, usd, 1.08\n").unwrap();
+
+        assert_eq!(
+            rates,
+            vec![
+                ExchangeRate {
+                    currency: Currency::UAH,
+                    base_currency: Currency::USD,
+                    rate: 0.024,
+                },
+                ExchangeRate {
+                    currency: Currency::EUR,
+                    base_currency: Currency::USD,
+                    rate: 1.08,
+                },
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("UAH,USD", ImportError::InvalidLine { line: 1 })]
+    #[case("UAH,USD,0.02\nGBP,USD,1.2", ImportError::InvalidCurrency { line: 2, message: "GBP is not a supported currency.".to_owned() })]
+    #[case("UAH,USD,-1", ImportError::InvalidRate { line: 1 })]
+    #[case("UAH,USD,abc", ImportError::InvalidRate { line: 1 })]
+    #[case("currency,base_currency,rate\n", ImportError::Empty)]
+    fn test_parse_csv_fails(#[case] csv: &str, #[case] expected: ImportError) {
+        assert_eq!(parse_csv(csv), Err(expected));
+    }
+
+    #[test]
+    fn test_normalize_uses_direct_and_inverse_rates() {
+        let rates = vec![
+            ExchangeRate {
+                currency: Currency::UAH,
+                base_currency: Currency::USD,
+                rate: 0.025,
+            },
+            ExchangeRate {
+                currency: Currency::USD,
+                base_currency: Currency::EUR,
+                rate: 0.8,
+            },
+        ];
+        let totals = vec![
+            total("EUR", 40.0),
+            total("UAH", 1_000.0),
+            total("USD", 10.0),
+        ];
+
+        assert_eq!(
+            normalize(&totals, &rates, &Currency::USD),
+            NormalizedTotal {
+                currency: Currency::USD,
+                amount: 85.0,
+                missing_rates: vec![],
+            }
+        );
+        assert_eq!(
+            normalize(&totals, &rates, &Currency::EUR),
+            NormalizedTotal {
+                currency: Currency::EUR,
+                amount: 48.0,
+                missing_rates: vec!["UAH".to_owned()],
+            }
+        );
+    }
+}
@@ -0,0 +1,444 @@
+use askama::Template;
+
+use crate::{
+    campaign::Campaign,
+    liq_pay::{InputError, Language, SubscribePeriod},
+};
+use serde::Deserialize;
+
+/// Copy of the donor facing pages in one language
+pub struct Strings {
+    lang: &'static str,
+    title: &'static str,
+    switch_language: &'static str,
+    amount: &'static str,
+    frequency: &'static str,
+    one_off: &'static str,
+    monthly: &'static str,
+    currency: &'static str,
+    email: &'static str,
+    donate: &'static str,
+    daily: &'static str,
+    weekly: &'static str,
+    yearly: &'static str,
+    cancel_question: &'static str,
+    cancel_donation: &'static str,
+    cancelled: &'static str,
+    invalid_form: &'static str,
+    invalid_amount: &'static str,
+    amount_too_small: &'static str,
+    amount_too_large: &'static str,
+    currency_not_accepted: &'static str,
+    checkout_unavailable: &'static str,
+    invalid_email: &'static str,
+    too_many_checkouts: &'static str,
+}
+
+const EN: Strings = Strings {
+    lang: "en",
+    title: "Stand with Ukraine",
+    switch_language: "Українською",
+    amount: "Amount",
+    frequency: "Frequency",
+    one_off: "One-off",
+    monthly: "Monthly",
+    currency: "Currency",
+    email: "Email for a receipt (optional)",
+    donate: "Donate",
+    daily: "Daily",
+    weekly: "Weekly",
+    yearly: "Yearly",
+    cancel_question: "Cancel your recurring donation?",
+    cancel_donation: "Cancel donation",
+    cancelled: "Your recurring donation has been cancelled. Thank you for your support!",
+    invalid_form: "Please check the form and try again.",
+    invalid_amount: "Enter a positive amount with at most two decimal places.",
+    amount_too_small: "The smallest donation is",
+    amount_too_large: "The largest donation is",
+    currency_not_accepted: "This currency is not accepted.",
+    checkout_unavailable: "Donations in this currency are not available right now.",
+    invalid_email: "Enter a valid email address.",
+    too_many_checkouts: "Too many attempts, please try again in a few minutes.",
+};
+
+const UA: Strings = Strings {
+    lang: "uk",
+    title: "Підтримай Україну",
+    switch_language: "In English",
+    amount: "Сума",
+    frequency: "Періодичність",
+    one_off: "Разово",
+    monthly: "Щомісяця",
+    currency: "Валюта",
+    email: "Email для квитанції (необовʼязково)",
+    donate: "Задонатити",
+    daily: "Щодня",
+    weekly: "Щотижня",
+    yearly: "Щороку",
+    cancel_question: "Скасувати регулярний донат?",
+    cancel_donation: "Скасувати донат",
+    cancelled: "Ваш регулярний донат скасовано. Дякуємо за підтримку!",
+    invalid_form: "Перевірте форму та спробуйте ще раз.",
+    invalid_amount: "Введіть додатну суму з не більше ніж двома знаками після коми.",
+    amount_too_small: "Найменший донат —",
+    amount_too_large: "Найбільший донат —",
+    currency_not_accepted: "Ця валюта не приймається.",
+    checkout_unavailable: "Донати в цій валюті зараз недоступні.",
+    invalid_email: "Введіть коректну email адресу.",
+    too_many_checkouts: "Забагато спроб, спробуйте ще раз за кілька хвилин.",
+};
+
+const PL: Strings = Strings {
+    lang: "pl",
+    title: "Wspieraj Ukrainę",
+    switch_language: "In English",
+    amount: "Kwota",
+    frequency: "Częstotliwość",
+    one_off: "Jednorazowo",
+    monthly: "Co miesiąc",
+    currency: "Waluta",
+    email: "E-mail do potwierdzenia (opcjonalnie)",
+    donate: "Wesprzyj",
+    daily: "Codziennie",
+    weekly: "Co tydzień",
+    yearly: "Co rok",
+    cancel_question: "Anulować regularną darowiznę?",
+    cancel_donation: "Anuluj darowiznę",
+    cancelled: "Twoja regularna darowizna została anulowana. Dziękujemy za wsparcie!",
+    invalid_form: "Sprawdź formularz i spróbuj ponownie.",
+    invalid_amount: "Podaj dodatnią kwotę z maksymalnie dwoma miejscami po przecinku.",
+    amount_too_small: "Najmniejsza darowizna to",
+    amount_too_large: "Największa darowizna to",
+    currency_not_accepted: "Ta waluta nie jest akceptowana.",
+    checkout_unavailable: "Darowizny w tej walucie są obecnie niedostępne.",
+    invalid_email: "Podaj poprawny adres e-mail.",
+    too_many_checkouts: "Zbyt wiele prób, spróbuj ponownie za kilka minut.",
+};
+
+const DE: Strings = Strings {
+    lang: "de",
+    title: "Solidarität mit der Ukraine",
+    switch_language: "In English",
+    amount: "Betrag",
+    frequency: "Häufigkeit",
+    one_off: "Einmalig",
+    monthly: "Monatlich",
+    currency: "Währung",
+    email: "E-Mail für die Quittung (optional)",
+    donate: "Spenden",
+    daily: "Täglich",
+    weekly: "Wöchentlich",
+    yearly: "Jährlich",
+    cancel_question: "Regelmäßige Spende beenden?",
+    cancel_donation: "Spende beenden",
+    cancelled: "Deine regelmäßige Spende wurde beendet. Danke für deine Unterstützung!",
+    invalid_form: "Bitte prüfe das Formular und versuche es erneut.",
+    invalid_amount: "Gib einen positiven Betrag mit höchstens zwei Nachkommastellen ein.",
+    amount_too_small: "Die kleinste Spende beträgt",
+    amount_too_large: "Die größte Spende beträgt",
+    currency_not_accepted: "Diese Währung wird nicht akzeptiert.",
+    checkout_unavailable: "Spenden in dieser Währung sind gerade nicht möglich.",
+    invalid_email: "Gib eine gültige E-Mail-Adresse ein.",
+    too_many_checkouts: "Zu viele Versuche, bitte versuche es in ein paar Minuten erneut.",
+};
+
+const RO: Strings = Strings {
+    lang: "ro",
+    title: "Alături de Ucraina",
+    switch_language: "In English",
+    amount: "Sumă",
+    frequency: "Frecvență",
+    one_off: "O singură dată",
+    monthly: "Lunar",
+    currency: "Monedă",
+    email: "E-mail pentru chitanță (opțional)",
+    donate: "Donează",
+    daily: "Zilnic",
+    weekly: "Săptămânal",
+    yearly: "Anual",
+    cancel_question: "Anulezi donația recurentă?",
+    cancel_donation: "Anulează donația",
+    cancelled: "Donația ta recurentă a fost anulată. Îți mulțumim pentru sprijin!",
+    invalid_form: "Verifică formularul și încearcă din nou.",
+    invalid_amount: "Introdu o sumă pozitivă cu cel mult două zecimale.",
+    amount_too_small: "Cea mai mică donație este",
+    amount_too_large: "Cea mai mare donație este",
+    currency_not_accepted: "Această monedă nu este acceptată.",
+    checkout_unavailable: "Donațiile în această monedă nu sunt disponibile acum.",
+    invalid_email: "Introdu o adresă de e-mail validă.",
+    too_many_checkouts: "Prea multe încercări, încearcă din nou peste câteva minute.",
+};
+
+/// Donation form that submits into the checkout flow
+#[derive(Template)]
+#[template(path = "pay.html")]
+pub struct LandingPage<'a> {
+    strings: &'static Strings,
+    language: &'static str,
+    other_language: &'static str,
+    campaign: &'a Campaign,
+    currency: &'static str,
+    values: FormValues,
+    error: Option<FieldError>,
+}
+
+impl<'a> LandingPage<'a> {
+    pub const fn new(campaign: &'a Campaign, language: &Language) -> Self {
+        let other_language = match language {
+            Language::EN => Language::UA,
+            // the other languages are for shoppers of storefronts abroad, who switch to English
+            Language::UA | Language::PL | Language::DE | Language::RO => Language::EN,
+        };
+
+        Self {
+            strings: strings(language),
+            language: language.as_str(),
+            other_language: other_language.as_str(),
+            campaign,
+            currency: campaign.default_currency.as_str(),
+            values: FormValues {
+                language: None,
+                amount: None,
+                action: None,
+                currency: None,
+                email: None,
+            },
+            error: None,
+        }
+    }
+
+    /// Shows the form again with the values the donor submitted and why they were not accepted
+    pub fn with_error(self, values: FormValues, error: &FormError) -> Self {
+        let currency = self
+            .campaign
+            .currencies
+            .iter()
+            .map(|currency| currency.as_str())
+            .find(|currency| values.currency.as_deref() == Some(*currency))
+            .unwrap_or(self.currency);
+
+        Self {
+            currency,
+            values,
+            error: Some(field_error(self.strings, error)),
+            ..self
+        }
+    }
+
+    fn error_for(&self, field: &str) -> Option<&str> {
+        self.error
+            .as_ref()
+            .filter(|error| error.field == Some(field))
+            .map(|error| error.message.as_str())
+    }
+
+    fn form_error(&self) -> Option<&str> {
+        self.error
+            .as_ref()
+            .filter(|error| error.field.is_none())
+            .map(|error| error.message.as_str())
+    }
+
+    fn subscribes(&self) -> bool {
+        self.values.action.as_deref() == Some("subscribe")
+    }
+}
+
+/// Donation form fields as they were submitted, before any of them is validated
+#[derive(Deserialize, Debug)]
+pub struct FormValues {
+    pub language: Option<String>,
+    amount: Option<String>,
+    action: Option<String>,
+    currency: Option<String>,
+    email: Option<String>,
+}
+
+/// Reason a submitted donation form is shown again instead of starting a checkout
+#[derive(Debug)]
+pub enum FormError<'e> {
+    /// The form could not be read at all
+    Invalid,
+    Input(&'e InputError),
+    /// No payment provider accepts the chosen currency
+    CheckoutUnavailable,
+    TooManyCheckouts,
+}
+
+/// Localized message shown next to the form field it is about, or above the form
+struct FieldError {
+    field: Option<&'static str>,
+    message: String,
+}
+
+fn field_error(strings: &'static Strings, error: &FormError) -> FieldError {
+    let (field, message) = match error {
+        FormError::Input(error @ (InputError::InvalidAmount | InputError::AmountTooPrecise)) => {
+            (Some(error.field()), strings.invalid_amount.to_owned())
+        }
+        FormError::Input(error @ InputError::AmountTooSmall { min, currency }) => (
+            Some(error.field()),
+            format!("{} {min} {currency}.", strings.amount_too_small),
+        ),
+        FormError::Input(error @ InputError::AmountTooLarge { max, currency }) => (
+            Some(error.field()),
+            format!("{} {max} {currency}.", strings.amount_too_large),
+        ),
+        FormError::Input(
+            error @ (InputError::CurrencyNotAllowed { .. }
+            | InputError::CurrencyNotAvailable { .. }),
+        ) => (
+            Some(error.field()),
+            strings.currency_not_accepted.to_owned(),
+        ),
+        FormError::Input(error @ InputError::InvalidEmail) => {
+            (Some(error.field()), strings.invalid_email.to_owned())
+        }
+        FormError::CheckoutUnavailable => {
+            (Some("currency"), strings.checkout_unavailable.to_owned())
+        }
+        FormError::TooManyCheckouts => (None, strings.too_many_checkouts.to_owned()),
+        // the remaining fields are not shown on the form, so only a crafted request gets here
+        FormError::Input(_) | FormError::Invalid => (None, strings.invalid_form.to_owned()),
+    };
+
+    FieldError { field, message }
+}
+
+/// Confirmation before a recurring donation is cancelled
+#[derive(Template)]
+#[template(path = "unsubscribe.html")]
+pub struct UnsubscribePage<'a> {
+    strings: &'static Strings,
+    language: &'static str,
+    period: &'static str,
+    token: &'a str,
+}
+
+impl<'a> UnsubscribePage<'a> {
+    pub const fn new(token: &'a str, period: &SubscribePeriod, language: &Language) -> Self {
+        let strings = strings(language);
+
+        Self {
+            strings,
+            language: language.as_str(),
+            period: period_label(strings, period),
+            token,
+        }
+    }
+}
+
+/// Result of cancelling a recurring donation
+#[derive(Template)]
+#[template(path = "unsubscribed.html")]
+pub struct UnsubscribedPage {
+    strings: &'static Strings,
+    period: &'static str,
+}
+
+impl UnsubscribedPage {
+    pub const fn new(period: &SubscribePeriod, language: &Language) -> Self {
+        let strings = strings(language);
+
+        Self {
+            strings,
+            period: period_label(strings, period),
+        }
+    }
+}
+
+const fn strings(language: &Language) -> &'static Strings {
+    match language {
+        Language::EN => &EN,
+        Language::UA => &UA,
+        Language::PL => &PL,
+        Language::DE => &DE,
+        Language::RO => &RO,
+    }
+}
+
+const fn period_label(strings: &'static Strings, period: &SubscribePeriod) -> &'static str {
+    match period {
+        SubscribePeriod::Day => strings.daily,
+        SubscribePeriod::Week => strings.weekly,
+        SubscribePeriod::Month => strings.monthly,
+        SubscribePeriod::Year => strings.yearly,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_landing_page_is_localized_and_escaped() {
+        let campaign = Campaign {
+            description: "Help <b>now</b>".to_owned(),
+            ..Campaign::general()
+        };
+
+        let page = LandingPage::new(&campaign, &Language::UA).render().unwrap();
+
+        assert!(page.contains("Підтримай Україну"));
+        assert!(page.contains(r#"name="language" value="ua""#));
+        assert!(page.contains("?language=en"));
+        assert!(page.contains("Help &lt;b&gt;now&lt;/b&gt;"));
+        assert!(page.contains(r#"<option value="UAH">UAH</option>"#));
+        assert!(page.contains(r#"<option value="USD" selected>USD</option>"#));
+    }
+
+    #[test]
+    fn test_landing_page_shows_submitted_values_with_error() {
+        let campaign = Campaign::general();
+        let values: FormValues = serde_urlencoded::from_str(
+            "language=pl&amount=5&action=subscribe&currency=UAH&email=%22%3E%3Cb%3E",
+        )
+        .unwrap();
+        let error = InputError::AmountTooSmall {
+            min: 10,
+            currency: "UAH",
+        };
+
+        let page = LandingPage::new(&campaign, &Language::PL)
+            .with_error(values, &FormError::Input(&error))
+            .render()
+            .unwrap();
+
+        assert!(page.contains("Najmniejsza darowizna to 10 UAH."));
+        assert!(page.contains(r#"value="5""#));
+        assert!(page.contains(r#"value="subscribe" checked"#));
+        assert!(page.contains(r#"<option value="UAH" selected>UAH</option>"#));
+        assert!(page.contains("&gt;&lt;b&gt;"));
+        assert!(!page.contains("<b>"));
+        assert!(!page.contains(r#"role="alert""#));
+
+        let values: FormValues = serde_urlencoded::from_str("language=en").unwrap();
+        let page = LandingPage::new(&campaign, &Language::EN)
+            .with_error(values, &FormError::TooManyCheckouts)
+            .render()
+            .unwrap();
+
+        assert!(page.contains("Too many attempts, please try again in a few minutes."));
+        assert!(page.contains(r#"role="alert""#));
+    }
+
+    #[test]
+    fn test_unsubscribe_pages_are_localized_and_escaped() {
+        let page = UnsubscribePage::new(r#""><b>"#, &SubscribePeriod::Week, &Language::UA)
+            .render()
+            .unwrap();
+
+        assert!(page.contains("Скасувати регулярний донат?"));
+        assert!(page.contains("Щотижня"));
+        assert!(page.contains("&gt;&lt;b&gt;"));
+        assert!(!page.contains("<b>"));
+        assert!(page.contains(r#"name="language" value="ua""#));
+
+        let page = UnsubscribedPage::new(&SubscribePeriod::Year, &Language::EN)
+            .render()
+            .unwrap();
+
+        assert!(page.contains("Your recurring donation has been cancelled."));
+        assert!(page.contains("Yearly"));
+    }
+}
@@ -1,9 +1,19 @@
+pub mod abuse;
 pub mod authentication;
 pub mod bigcommerce;
+pub mod cache;
+pub mod campaign;
 pub mod configuration;
 pub mod data;
+pub mod exchange_rates;
+pub mod landing;
+pub mod links;
 pub mod liq_pay;
+pub mod payments;
+pub mod receipts;
+pub mod reconciliation;
 pub mod routes;
 pub mod startup;
 pub mod state;
+pub mod stripe;
 pub mod telemetry;
@@ -0,0 +1,169 @@
+use std::io::Cursor;
+
+use anyhow::Context;
+use image::{ImageFormat, Luma};
+use qrcode::{
+    render::{svg, unicode},
+    types::QrError,
+    QrCode,
+};
+use time::OffsetDateTime;
+
+use crate::{
+    abuse::CheckoutGuard,
+    campaign::Campaign,
+    liq_pay::{InputError, InputQuery},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum LinkError {
+    #[error("Link options must be a query string like amount=500&currency=UAH&action=subscribe&language=ua: {0}")]
+    InvalidOptions(#[from] serde_urlencoded::de::Error),
+
+    #[error(transparent)]
+    InvalidInput(#[from] InputError),
+}
+
+/// Link to `/pay` with the options of a donation, e.g. for a newsletter
+///
+/// Every visit creates a new checkout, so the link can be shared with any number of donors
+/// unlike a signed checkout, which the provider only accepts a single payment for.
+///
+/// # Errors
+///
+/// Will return `LinkError` if the options cannot be parsed or are not a valid checkout
+pub fn generate_link(
+    options: &str,
+    base_url: &str,
+    guard: &CheckoutGuard,
+    now: OffsetDateTime,
+) -> Result<String, LinkError> {
+    let query: InputQuery = serde_urlencoded::from_str(options)?;
+    query.validate(now)?;
+    guard.validate(&query)?;
+    Campaign::general().validate(&query)?;
+
+    let params: Vec<(String, String)> = serde_urlencoded::from_str(options)?;
+    let options =
+        serde_urlencoded::to_string(params).expect("Decoded query parameters should encode again");
+
+    Ok(format!("{base_url}/pay?{options}"))
+}
+
+/// QR code of `content` drawn with block characters for dark terminals
+///
+/// # Errors
+///
+/// Will return `QrError` if `content` is too long to fit in a QR code
+pub fn terminal_qr_code(content: &str) -> Result<String, QrError> {
+    Ok(QrCode::new(content)?
+        .render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .build())
+}
+
+/// # Errors
+///
+/// Will return `QrError` if `content` is too long to fit in a QR code
+pub fn svg_qr_code(content: &str) -> Result<String, QrError> {
+    Ok(QrCode::new(content)?
+        .render::<svg::Color>()
+        .min_dimensions(256, 256)
+        .build())
+}
+
+/// # Errors
+///
+/// Will return `anyhow::Error` if `content` does not fit in a QR code or the image cannot be encoded
+pub fn png_qr_code(content: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let image = QrCode::new(content)
+        .context("Failed to create QR code")?
+        .render::<Luma<u8>>()
+        .min_dimensions(256, 256)
+        .build();
+
+    let mut png = Cursor::new(vec![]);
+    image
+        .write_to(&mut png, ImageFormat::Png)
+        .context("Failed to encode QR code")?;
+
+    Ok(png.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    const BASE_URL: &str = "https://example.com";
+
+    #[test]
+    fn test_generate_link_points_to_pay() {
+        let link = generate_link(
+            "amount=500&currency=UAH&action=subscribe&periodicity=month&language=ua&email=donor@example.com",
+            BASE_URL,
+            &CheckoutGuard::for_tests(),
+            datetime!(2024-12-01 12:00 UTC),
+        )
+        .unwrap();
+
+        assert_eq!(
+            link,
+            "https://example.com/pay?amount=500&currency=UAH&action=subscribe&periodicity=month&language=ua&email=donor%40example.com"
+        );
+    }
+
+    #[test]
+    fn test_generate_link_validates_options() {
+        let now = datetime!(2024-12-01 12:00 UTC);
+
+        assert!(matches!(
+            generate_link("amount=500", BASE_URL, &CheckoutGuard::for_tests(), now),
+            Err(LinkError::InvalidOptions(_))
+        ));
+        assert!(matches!(
+            generate_link(
+                "amount=0&currency=UAH&action=pay&language=ua",
+                BASE_URL,
+                &CheckoutGuard::for_tests(),
+                now
+            ),
+            Err(LinkError::InvalidInput(InputError::InvalidAmount))
+        ));
+        assert!(matches!(
+            generate_link(
+                "amount=50000&currency=UAH&action=pay&language=ua",
+                BASE_URL,
+                &CheckoutGuard::for_tests(),
+                now
+            ),
+            Err(LinkError::InvalidInput(InputError::AmountTooLarge { .. }))
+        ));
+        assert!(matches!(
+            generate_link(
+                "amount=50&currency=EUR&action=pay&language=en",
+                BASE_URL,
+                &CheckoutGuard::for_tests(),
+                now
+            ),
+            Err(LinkError::InvalidInput(
+                InputError::CurrencyNotAvailable { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_qr_codes_render() {
+        assert!(terminal_qr_code("https://example.com")
+            .unwrap()
+            .contains('█'));
+        assert!(svg_qr_code("https://example.com")
+            .unwrap()
+            .starts_with("<?xml"));
+        assert!(png_qr_code("https://example.com")
+            .unwrap()
+            .starts_with(b"\x89PNG"));
+    }
+}
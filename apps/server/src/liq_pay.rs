@@ -1,10 +1,24 @@
 use ::time::OffsetDateTime;
+use anyhow::Context;
+use axum::{async_trait, http::HeaderMap};
 use base64::engine::general_purpose::STANDARD as encoder;
 use base64::Engine;
+use constant_time_eq::constant_time_eq;
+use email_address::EmailAddress;
+use reqwest::Client;
 use secrecy::{ExposeSecret, Secret};
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{DeserializeOwned, IntoDeserializer},
+    Deserialize, Deserializer, Serialize,
+};
 use sha1::{Digest, Sha1};
-use time::{format_description::FormatItem, macros::format_description};
+use time::{format_description::FormatItem, macros::format_description, Date, Duration, Month};
+
+use crate::{
+    campaign::Campaign,
+    data::Charity,
+    payments::{CallbackError, Checkout, CheckoutError, PaymentProvider, Provider},
+};
 
 #[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -14,24 +28,211 @@ pub enum Action {
     PayDonate,
 }
 
-#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
 pub enum Currency {
     USD,
     EUR,
     UAH,
+    PLN,
+    RON,
 }
 
-#[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
-#[serde(rename_all = "lowercase")]
+impl Currency {
+    /// Currencies that `LiqPay` accepts, the others can only be checked out with Stripe
+    pub const LIQ_PAY: [Self; 3] = [Self::USD, Self::EUR, Self::UAH];
+
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::USD => "USD",
+            Self::EUR => "EUR",
+            Self::UAH => "UAH",
+            Self::PLN => "PLN",
+            Self::RON => "RON",
+        }
+    }
+
+    /// Smallest amount the providers accept for a single checkout in whole units,
+    /// the largest one is configured in `PayLimits`
+    pub const fn min_amount(&self) -> u32 {
+        match self {
+            Self::USD | Self::EUR => 1,
+            Self::UAH => 10,
+            Self::PLN | Self::RON => 2,
+        }
+    }
+
+    pub fn is_supported_by_liq_pay(&self) -> bool {
+        Self::LIQ_PAY.contains(self)
+    }
+}
+
+impl std::str::FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "USD" => Ok(Self::USD),
+            "EUR" => Ok(Self::EUR),
+            "UAH" => Ok(Self::UAH),
+            "PLN" => Ok(Self::PLN),
+            "RON" => Ok(Self::RON),
+            other => Err(format!("{other} is not a supported currency.")),
+        }
+    }
+}
+
+/// Number of decimal places accepted in an amount
+const AMOUNT_PRECISION: i32 = 2;
+
+/// Longest store hash that can be stored with a checkout
+const STORE_HASH_MAX_LENGTH: usize = 25;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase", try_from = "String")]
 pub enum Language {
     UA,
     EN,
+    PL,
+    DE,
+    RO,
 }
 
-#[derive(Debug, Serialize, Eq, PartialEq)]
+impl Language {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::UA => "ua",
+            Self::EN => "en",
+            Self::PL => "pl",
+            Self::DE => "de",
+            Self::RO => "ro",
+        }
+    }
+
+    /// `LiqPay` checkout pages are only translated to Ukrainian and English
+    pub const fn liq_pay_code(&self) -> &'static str {
+        match self {
+            Self::UA => "ua",
+            Self::EN | Self::PL | Self::DE | Self::RO => "en",
+        }
+    }
+
+    /// Stripe checkout pages are not translated to Ukrainian, so they follow the browser instead
+    pub const fn stripe_locale(&self) -> &'static str {
+        match self {
+            Self::UA => "auto",
+            Self::EN => "en",
+            Self::PL => "pl",
+            Self::DE => "de",
+            Self::RO => "ro",
+        }
+    }
+}
+
+/// Accepts our language codes as well as storefront locales such as `pl-PL` or `uk_UA`
+impl std::str::FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let language = s.split(['-', '_']).next().unwrap_or_default();
+
+        match language.to_lowercase().as_str() {
+            "ua" | "uk" => Ok(Self::UA),
+            "en" => Ok(Self::EN),
+            "pl" => Ok(Self::PL),
+            "de" => Ok(Self::DE),
+            "ro" => Ok(Self::RO),
+            _ => Err(format!("{s} is not a supported language.")),
+        }
+    }
+}
+
+impl TryFrom<String> for Language {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum SubscribePeriod {
+    Day,
+    Week,
+    #[default]
     Month,
+    Year,
+}
+
+impl SubscribePeriod {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+            Self::Year => "year",
+        }
+    }
+
+    /// Date of the charge following one made at `from`
+    pub fn next_charge_date(&self, from: OffsetDateTime) -> OffsetDateTime {
+        match self {
+            Self::Day => from + Duration::days(1),
+            Self::Week => from + Duration::weeks(1),
+            Self::Month => {
+                let (year, month) = match from.month() {
+                    Month::December => (from.year() + 1, Month::January),
+                    month => (from.year(), month.next()),
+                };
+
+                from.replace_date(clamped_date(year, month, from.day()))
+            }
+            Self::Year => {
+                from.replace_date(clamped_date(from.year() + 1, from.month(), from.day()))
+            }
+        }
+    }
+}
+
+impl TryFrom<&str> for SubscribePeriod {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "day" => Ok(Self::Day),
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            "year" => Ok(Self::Year),
+            other => Err(format!("{other} is not a supported subscription period.")),
+        }
+    }
+}
+
+fn clamped_date(year: i32, month: Month, day: u8) -> Date {
+    let day = day.min(time::util::days_in_year_month(year, month));
+
+    Date::from_calendar_date(year, month, day).unwrap()
+}
+
+time::serde::format_description!(date_format, Date, "[year]-[month]-[day]");
+
+/// Longest email address that can be stored with a checkout
+const EMAIL_MAX_LENGTH: usize = 254;
+
+/// Whether `store_hash` has the format of the hashes BigCommerce assigns to stores
+pub fn is_valid_store_hash(store_hash: &str) -> bool {
+    !store_hash.is_empty()
+        && store_hash.len() <= STORE_HASH_MAX_LENGTH
+        && store_hash
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Forms submit an empty field when the donor leaves an optional input blank
+fn deserialize_non_empty<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.filter(|value| !value.trim().is_empty()))
 }
 
 #[derive(Debug, Deserialize)]
@@ -40,24 +241,187 @@ pub struct InputQuery {
     pub currency: Currency,
     pub amount: f64,
     pub action: Action,
+    pub periodicity: Option<SubscribePeriod>,
+    #[serde(default, with = "date_format::option")]
+    pub start_date: Option<Date>,
+    pub provider: Option<Provider>,
+    /// Store whose widget referred the donor
+    pub store_hash: Option<String>,
+    /// Widget charity the donor chose to support
+    pub source: Option<Charity>,
+    /// Address the receipt is sent to once the payment is confirmed
+    #[serde(default, deserialize_with = "deserialize_non_empty")]
+    pub email: Option<String>,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum InputError {
+    #[error("Amount must be a positive number.")]
+    InvalidAmount,
+
+    #[error("Amount must be at least {min} {currency}.")]
+    AmountTooSmall { min: u32, currency: &'static str },
+
+    #[error("Amount must be at most {max} {currency}.")]
+    AmountTooLarge { max: u32, currency: &'static str },
+
+    #[error("Amount cannot have more than {AMOUNT_PRECISION} decimal places.")]
+    AmountTooPrecise,
+
+    #[error("Subscription options can only be used with the subscribe action.")]
+    SubscriptionOptionsWithoutSubscribe,
+
+    #[error("Subscription start date cannot be in the past.")]
+    StartDateInPast,
+
+    #[error("Subscription start date cannot be more than a year ahead.")]
+    StartDateTooFar,
+
+    #[error("{currency} is not accepted by this campaign.")]
+    CurrencyNotAllowed { currency: &'static str },
+
+    #[error("{currency} is not accepted.")]
+    CurrencyNotAvailable { currency: &'static str },
+
+    #[error("Checkout is not available in the {language} language.")]
+    LanguageNotAvailable { language: &'static str },
+
+    #[error("Store hash must be up to {STORE_HASH_MAX_LENGTH} letters, digits or dashes.")]
+    InvalidStoreHash,
+
+    #[error("Email must be a valid address of up to {EMAIL_MAX_LENGTH} characters.")]
+    InvalidEmail,
+}
+
+impl InputError {
+    /// Name of the query parameter that caused the error
+    pub const fn field(&self) -> &'static str {
+        match self {
+            Self::InvalidAmount
+            | Self::AmountTooSmall { .. }
+            | Self::AmountTooLarge { .. }
+            | Self::AmountTooPrecise => "amount",
+            Self::SubscriptionOptionsWithoutSubscribe => "action",
+            Self::StartDateInPast | Self::StartDateTooFar => "start_date",
+            Self::CurrencyNotAllowed { .. } | Self::CurrencyNotAvailable { .. } => "currency",
+            Self::LanguageNotAvailable { .. } => "language",
+            Self::InvalidStoreHash => "store_hash",
+            Self::InvalidEmail => "email",
+        }
+    }
+}
+
+#[cfg(test)]
+impl InputQuery {
+    /// One-off donation of 100 USD that tests change the fields they are about in
+    pub const fn for_tests() -> Self {
+        Self {
+            language: Language::EN,
+            currency: Currency::USD,
+            amount: 100.0,
+            action: Action::Pay,
+            periodicity: None,
+            start_date: None,
+            provider: None,
+            store_hash: None,
+            source: None,
+            email: None,
+        }
+    }
+}
+
+impl InputQuery {
+    /// # Errors
+    ///
+    /// Will return `InputError` if the query cannot produce a valid checkout request at `now`
+    pub fn validate(&self, now: OffsetDateTime) -> Result<(), InputError> {
+        self.validate_amount()?;
+
+        if self.action != Action::Subscribe
+            && (self.periodicity.is_some() || self.start_date.is_some())
+        {
+            return Err(InputError::SubscriptionOptionsWithoutSubscribe);
+        }
+
+        if let Some(start_date) = self.start_date {
+            if start_date < now.date() {
+                return Err(InputError::StartDateInPast);
+            }
+
+            if start_date > SubscribePeriod::Year.next_charge_date(now).date() {
+                return Err(InputError::StartDateTooFar);
+            }
+        }
+
+        if let Some(store_hash) = &self.store_hash {
+            if !is_valid_store_hash(store_hash) {
+                return Err(InputError::InvalidStoreHash);
+            }
+        }
+
+        if let Some(email) = &self.email {
+            if email.len() > EMAIL_MAX_LENGTH || !EmailAddress::is_valid(email) {
+                return Err(InputError::InvalidEmail);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_amount(&self) -> Result<(), InputError> {
+        if !self.amount.is_finite() || self.amount <= 0.0 {
+            return Err(InputError::InvalidAmount);
+        }
+
+        let min = self.currency.min_amount();
+
+        if self.amount < f64::from(min) {
+            return Err(InputError::AmountTooSmall {
+                min,
+                currency: self.currency.as_str(),
+            });
+        }
+
+        let scaled = self.amount * 10_f64.powi(AMOUNT_PRECISION);
+        if (scaled - scaled.round()).abs() > 1e-6 {
+            return Err(InputError::AmountTooPrecise);
+        }
+
+        Ok(())
+    }
+
+    /// First charge of a subscription, either `now` or the start of the requested day
+    pub fn subscription_start(&self, now: OffsetDateTime) -> OffsetDateTime {
+        self.start_date
+            .map_or(now, |date| date.midnight().assume_utc().max(now))
+    }
 }
 
 #[derive(Clone)]
 pub struct HttpAPI {
     public_key: Secret<String>,
     private_key: Secret<String>,
+    api_base_url: String,
+    result_url: String,
+    server_url: String,
+    sandbox: bool,
+    http_client: Client,
 }
 
 #[derive(Debug, Serialize)]
 pub struct BaseFields {
     public_key: String,
-    language: Language,
+    language: &'static str,
     action: Action,
     version: usize,
     amount: f64,
     currency: Currency,
     description: String,
     order_id: String,
+    result_url: String,
+    server_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sandbox: Option<usize>,
 }
 
 #[derive(Debug, Serialize)]
@@ -78,60 +442,383 @@ pub enum CheckoutRequest {
     },
 }
 
+/// Checkout request in the form accepted by the `LiqPay` checkout page and widget
+#[derive(Debug, Clone, Serialize)]
+pub struct SignedCheckout {
+    pub data: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackForm {
+    pub data: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    Success,
+    Failure,
+    Error,
+    Reversed,
+    Subscribed,
+    Unsubscribed,
+    Sandbox,
+    Processing,
+    Prepared,
+    WaitAccept,
+    WaitSecure,
+    HoldWait,
+    /// Any of the verification or waiting statuses that require further action from the donor
+    #[serde(other)]
+    Pending,
+}
+
+impl PaymentStatus {
+    /// Statuses of charges where the donor's money was received, a refunded charge is `Reversed` instead
+    pub const CONFIRMED: [Self; 1] = [Self::Success];
+
+    /// Statuses that describe a subscription rather than one of its charges
+    pub const SUBSCRIPTION: [Self; 2] = [Self::Subscribed, Self::Unsubscribed];
+
+    /// Statuses that are only replaced by a later outcome such as a refund
+    pub const FINAL: [Self; 7] = [
+        Self::Success,
+        Self::Failure,
+        Self::Error,
+        Self::Reversed,
+        Self::Subscribed,
+        Self::Unsubscribed,
+        Self::Sandbox,
+    ];
+
+    pub fn is_final(&self) -> bool {
+        Self::FINAL.contains(self)
+    }
+
+    pub fn is_charge(&self) -> bool {
+        !Self::SUBSCRIPTION.contains(self)
+    }
+
+    /// Whether a recorded charge in this status can be updated to `next`,
+    /// so that late or repeated callbacks never change the outcome of a charge other than by a refund
+    pub fn can_change_to(&self, next: &Self) -> bool {
+        if !next.is_charge() {
+            return false;
+        }
+
+        match self {
+            Self::Success | Self::Sandbox => next == self || next == &Self::Reversed,
+            status if status.is_final() => next == status,
+            _ => true,
+        }
+    }
+
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Failure => "failure",
+            Self::Error => "error",
+            Self::Reversed => "reversed",
+            Self::Subscribed => "subscribed",
+            Self::Unsubscribed => "unsubscribed",
+            Self::Sandbox => "sandbox",
+            Self::Processing => "processing",
+            Self::Prepared => "prepared",
+            Self::WaitAccept => "wait_accept",
+            Self::WaitSecure => "wait_secure",
+            Self::HoldWait => "hold_wait",
+            Self::Pending => "pending",
+        }
+    }
+}
+
+impl std::str::FromStr for PaymentStatus {
+    type Err = serde::de::value::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::deserialize(s.into_deserializer())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(from = "PaymentFields")]
+pub struct Payment {
+    pub order_id: String,
+    pub payment_id: Option<i64>,
+    pub action: String,
+    pub status: PaymentStatus,
+    /// Status exactly as reported by the provider, since every status that is not known is read as `Pending`
+    pub raw_status: String,
+    pub amount: f64,
+    pub currency: Currency,
+    pub err_code: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PaymentFields {
+    order_id: String,
+    payment_id: Option<i64>,
+    action: String,
+    status: String,
+    amount: f64,
+    currency: Currency,
+    err_code: Option<String>,
+}
+
+impl From<PaymentFields> for Payment {
+    fn from(fields: PaymentFields) -> Self {
+        Self {
+            order_id: fields.order_id,
+            payment_id: fields.payment_id,
+            action: fields.action,
+            status: fields.status.parse().unwrap_or(PaymentStatus::Pending),
+            raw_status: fields.status,
+            amount: fields.amount,
+            currency: fields.currency,
+            err_code: fields.err_code,
+        }
+    }
+}
+
+impl Payment {
+    pub fn is_subscription(&self) -> bool {
+        self.action == "subscribe" || self.action == "regular"
+    }
+
+    /// Status and next charge date of the subscription this payment belongs to,
+    /// keeping a charge that is still scheduled in the future (e.g. a delayed start date)
+    pub fn subscription_state(
+        &self,
+        now: OffsetDateTime,
+        periodicity: &SubscribePeriod,
+        scheduled_charge_at: Option<OffsetDateTime>,
+    ) -> (PaymentStatus, Option<OffsetDateTime>) {
+        match self.status {
+            PaymentStatus::Success | PaymentStatus::Subscribed | PaymentStatus::Sandbox => (
+                PaymentStatus::Subscribed,
+                Some(
+                    scheduled_charge_at
+                        .filter(|charge_at| *charge_at > now)
+                        .unwrap_or_else(|| periodicity.next_charge_date(now)),
+                ),
+            ),
+            ref status => (status.clone(), None),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RequestResponse {
+    pub status: PaymentStatus,
+    pub err_description: Option<String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum DecodeError {
+    #[error("Signature does not match data.")]
+    InvalidSignature,
+
+    #[error("Data is not valid base64.")]
+    InvalidEncoding(#[source] base64::DecodeError),
+
+    #[error("Data is not a valid payment.")]
+    InvalidPayment(#[source] serde_json::Error),
+}
+
+impl CheckoutRequest {
+    const fn shared(&self) -> &BaseFields {
+        match self {
+            Self::Subscription { shared, .. } | Self::Pay { shared } => shared,
+        }
+    }
+
+    pub fn order_id(&self) -> &str {
+        self.shared().order_id.as_str()
+    }
+
+    pub const fn amount(&self) -> f64 {
+        self.shared().amount
+    }
+
+    pub const fn currency(&self) -> &Currency {
+        &self.shared().currency
+    }
+}
+
 const API_VERSION: usize = 3;
+const PAYMENT_NOT_FOUND: &str = "payment_not_found";
 const DATE_TIME_FORMAT: &[FormatItem<'_>] =
     format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
 
 impl HttpAPI {
-    pub fn new(public_key: Secret<String>, private_key: Secret<String>) -> Self {
+    pub fn new(
+        public_key: Secret<String>,
+        private_key: Secret<String>,
+        api_base_url: String,
+        result_url: String,
+        server_url: String,
+        sandbox: bool,
+        timeout: std::time::Duration,
+    ) -> Self {
+        let http_client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("LiqPay api client could not be built");
+
         Self {
             public_key,
             private_key,
+            api_base_url,
+            result_url,
+            server_url,
+            sandbox,
+            http_client,
         }
     }
 
-    #[tracing::instrument(name = "generate request payload", skip(self))]
+    fn get_checkout_url(&self) -> String {
+        format!("{}/api/{}/checkout", self.api_base_url, API_VERSION)
+    }
+
+    fn get_request_url(&self) -> String {
+        format!("{}/api/request", self.api_base_url)
+    }
+
+    #[tracing::instrument(name = "generate request payload", skip(self, campaign))]
     pub fn generate_request_payload(
         &self,
         query: InputQuery,
-        description: &str,
+        campaign: &Campaign,
     ) -> CheckoutRequest {
+        let subscription_start = query.subscription_start(OffsetDateTime::now_utc());
         let shared = BaseFields {
             public_key: self.public_key.expose_secret().clone(),
-            language: query.language,
+            language: query.language.liq_pay_code(),
             action: query.action.clone(),
             version: API_VERSION,
             amount: query.amount,
             currency: query.currency,
-            description: description.to_owned(),
+            description: campaign.description.clone(),
             order_id: uuid::Uuid::new_v4().into(),
+            result_url: self.result_url.clone(),
+            server_url: self.server_url.clone(),
+            sandbox: self.sandbox.then_some(1),
         };
 
         match query.action {
             Action::Subscribe => CheckoutRequest::Subscription {
                 shared,
                 subscribe: 1,
-                subscribe_periodicity: SubscribePeriod::Month,
-                subscribe_date_start: OffsetDateTime::now_utc().format(DATE_TIME_FORMAT).unwrap(),
+                subscribe_periodicity: query.periodicity.unwrap_or_default(),
+                subscribe_date_start: subscription_start.format(DATE_TIME_FORMAT).unwrap(),
             },
             Action::Pay | Action::PayDonate => CheckoutRequest::Pay { shared },
         }
     }
 
+    /// Encodes `request` into the fields expected by both the checkout page and the embedded widget
+    #[tracing::instrument(name = "sign checkout", skip(self))]
+    pub fn sign(&self, request: &CheckoutRequest) -> SignedCheckout {
+        let data = serde_json::to_string(request).unwrap();
+        let data = encoder.encode(data);
+        let signature = self.signature(&data);
+
+        SignedCheckout { data, signature }
+    }
+
     #[tracing::instrument(name = "generate link", skip(self))]
     pub fn link(&self, request: CheckoutRequest) -> String {
-        let data = serde_json::to_string(&request).unwrap();
-        let data = encoder.encode(data);
+        self.signed_link(&self.sign(&request))
+    }
 
+    fn signed_link(&self, signed: &SignedCheckout) -> String {
         format!(
-            "https://www.liqpay.ua/api/{}/checkout?data={}&signature={}",
-            API_VERSION,
-            data,
-            self.signature(&data)
+            "{}?data={}&signature={}",
+            self.get_checkout_url(),
+            signed.data,
+            signed.signature
         )
     }
 
-    fn signature(&self, data: &String) -> String {
+    async fn request<T: DeserializeOwned>(
+        &self,
+        action: &str,
+        order_id: &str,
+    ) -> Result<T, anyhow::Error> {
+        let data = encoder.encode(
+            serde_json::json!({
+                "action": action,
+                "version": API_VERSION,
+                "public_key": self.public_key.expose_secret(),
+                "order_id": order_id,
+            })
+            .to_string(),
+        );
+        let signature = self.signature(&data);
+
+        self.http_client
+            .post(self.get_request_url())
+            .form(&[("data", data), ("signature", signature)])
+            .send()
+            .await
+            .with_context(|| format!("{action} request"))?
+            .error_for_status()?
+            .json::<T>()
+            .await
+            .with_context(|| format!("parse {action} response"))
+    }
+
+    /// # Errors
+    ///
+    /// Will return `anyhow::Error` if the request fails or LiqPay does not confirm the subscription was cancelled
+    #[tracing::instrument(name = "unsubscribe", skip(self))]
+    pub async fn unsubscribe(&self, order_id: &str) -> Result<(), anyhow::Error> {
+        let response: RequestResponse = self.request("unsubscribe", order_id).await?;
+
+        match response.status {
+            PaymentStatus::Unsubscribed => Ok(()),
+            status => Err(anyhow::anyhow!(
+                "Subscription was not cancelled, status: {}, error: {}",
+                status.as_str(),
+                response.err_description.unwrap_or_default()
+            )),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Will return `anyhow::Error` if the request fails or the response is not a payment
+    #[tracing::instrument(name = "get payment status", skip(self))]
+    pub async fn status(&self, order_id: &str) -> Result<Option<Payment>, anyhow::Error> {
+        let response: serde_json::Value = self.request("status", order_id).await?;
+
+        if response["err_code"] == PAYMENT_NOT_FOUND {
+            return Ok(None);
+        }
+
+        serde_json::from_value(response)
+            .map(Some)
+            .context("parse payment from status response")
+    }
+
+    /// # Errors
+    ///
+    /// Will return `DecodeError` if `signature` was not produced from `data` by our private key
+    /// or `data` cannot be decoded into a `Payment`
+    #[tracing::instrument(name = "decode payment", skip(self, data, signature))]
+    pub fn decode(&self, data: &str, signature: &str) -> Result<Payment, DecodeError> {
+        if !constant_time_eq(self.signature(data).as_bytes(), signature.as_bytes()) {
+            return Err(DecodeError::InvalidSignature);
+        }
+
+        let data = encoder.decode(data).map_err(DecodeError::InvalidEncoding)?;
+
+        serde_json::from_slice(&data).map_err(DecodeError::InvalidPayment)
+    }
+
+    pub fn signature(&self, data: &str) -> String {
         let mut hasher = Sha1::new();
         hasher.update(format!(
             "{}{}{}",
@@ -143,38 +830,111 @@ impl HttpAPI {
     }
 }
 
+#[async_trait]
+impl PaymentProvider for HttpAPI {
+    async fn create_checkout(
+        &self,
+        query: InputQuery,
+        campaign: &Campaign,
+    ) -> Result<Checkout, CheckoutError> {
+        if !query.currency.is_supported_by_liq_pay() {
+            return Err(CheckoutError::Unsupported(
+                "This currency is only supported by Stripe.",
+            ));
+        }
+
+        let request = self.generate_request_payload(query, campaign);
+        let order_id = request.order_id().to_owned();
+        let signed = self.sign(&request);
+
+        Ok(Checkout {
+            order_id,
+            url: self.signed_link(&signed),
+            signed: Some(signed),
+        })
+    }
+
+    fn verify_callback(
+        &self,
+        _headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Option<Payment>, CallbackError> {
+        let form: CallbackForm = serde_urlencoded::from_bytes(body)
+            .map_err(|error| CallbackError::InvalidPayload(error.into()))?;
+
+        match self.decode(&form.data, &form.signature) {
+            Ok(payment) => Ok(Some(payment)),
+            Err(DecodeError::InvalidSignature) => Err(CallbackError::InvalidSignature),
+            Err(error) => Err(CallbackError::InvalidPayload(error.into())),
+        }
+    }
+
+    async fn query_status(&self, order_id: &str) -> Result<Option<Payment>, anyhow::Error> {
+        self.status(order_id).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use assert_json_diff::assert_json_include;
     use rstest::rstest;
     use serde_json::json;
+    use time::macros::{date, datetime};
 
     use super::*;
 
+    fn test_client(private_key: &str) -> HttpAPI {
+        HttpAPI::new(
+            Secret::new("public_key".to_string()),
+            Secret::new(private_key.to_string()),
+            "https://www.liqpay.ua".to_owned(),
+            "https://example.com/thank-you".to_owned(),
+            "https://example.com/pay/callback".to_owned(),
+            true,
+            std::time::Duration::from_secs(1),
+        )
+    }
+
     #[test]
     fn test_create_link_subscribe() {
-        let client = HttpAPI::new(
-            Secret::new("public_key".to_string()),
-            Secret::new("private_key".to_string()),
-        );
+        let client = test_client("private_key");
 
         let checkout_request = client.generate_request_payload(
             InputQuery {
-                amount: 100.0,
                 language: Language::UA,
                 currency: Currency::UAH,
                 action: Action::Subscribe,
+                periodicity: Some(SubscribePeriod::Year),
+                ..InputQuery::for_tests()
             },
-            "Stand with Ukraine",
+            &Campaign::general(),
         );
 
         let link = client.link(checkout_request);
 
-        assert_eq!(
-            link.starts_with("https://www.liqpay.ua/api/3/checkout?data="),
-            true
+        assert!(link.starts_with("https://www.liqpay.ua/api/3/checkout?data="));
+        assert!(link.contains("&signature="));
+    }
+
+    #[test]
+    fn test_request_payload_includes_checkout_urls_and_sandbox() {
+        let client = test_client("private_key");
+
+        let checkout_request = client.generate_request_payload(
+            InputQuery {
+                ..InputQuery::for_tests()
+            },
+            &Campaign::general(),
+        );
+
+        assert_json_include!(
+            actual: serde_json::to_value(&checkout_request).unwrap(),
+            expected: json!({
+                "result_url":"https://example.com/thank-you",
+                "server_url":"https://example.com/pay/callback",
+                "sandbox":1
+            })
         );
-        assert_eq!(link.contains("&signature="), true);
     }
 
     #[test]
@@ -182,13 +942,16 @@ mod tests {
         let request = CheckoutRequest::Subscription {
             shared: BaseFields {
                 public_key: "public_key".to_owned(),
-                language: Language::UA,
+                language: Language::UA.liq_pay_code(),
                 action: Action::Subscribe,
                 version: 3,
                 amount: 100.00,
                 currency: Currency::USD,
                 description: "stand with ukraine".to_owned(),
                 order_id: "1234".to_owned(),
+                result_url: "https://example.com/thank-you".to_owned(),
+                server_url: "https://example.com/pay/callback".to_owned(),
+                sandbox: None,
             },
             subscribe: 1,
             subscribe_periodicity: SubscribePeriod::Month,
@@ -206,6 +969,8 @@ mod tests {
                 "currency":"USD",
                 "description":"stand with ukraine",
                 "order_id":"1234",
+                "result_url":"https://example.com/thank-you",
+                "server_url":"https://example.com/pay/callback",
                 "subscribe":1,
                 "subscribe_periodicity":"month",
                 "subscribe_date_start":"2024-02-23 15:26:00"
@@ -217,13 +982,16 @@ mod tests {
         let request = CheckoutRequest::Pay {
             shared: BaseFields {
                 public_key: "public_key".to_owned(),
-                language: Language::UA,
+                language: Language::UA.liq_pay_code(),
                 action: Action::PayDonate,
                 version: 3,
                 amount: 100.00,
                 currency: Currency::USD,
                 description: "stand with ukraine".to_owned(),
                 order_id: "1234".to_owned(),
+                result_url: "https://example.com/thank-you".to_owned(),
+                server_url: "https://example.com/pay/callback".to_owned(),
+                sandbox: None,
             },
         };
 
@@ -237,35 +1005,383 @@ mod tests {
                 "amount":100.0,
                 "currency":"USD",
                 "description":"stand with ukraine",
-                "order_id":"1234"
+                "order_id":"1234",
+                "result_url":"https://example.com/thank-you",
+                "server_url":"https://example.com/pay/callback"
             })
         );
     }
 
     #[test]
     fn test_create_link_pay() {
-        let client = HttpAPI::new(
-            Secret::new("public_key".to_string()),
-            Secret::new("private_key".to_string()),
-        );
+        let client = test_client("private_key");
 
         let checkout_request = client.generate_request_payload(
             InputQuery {
-                amount: 100.0,
-                language: Language::EN,
-                currency: Currency::USD,
-                action: Action::Pay,
+                ..InputQuery::for_tests()
             },
-            "Stand with Ukraine",
+            &Campaign::general(),
         );
 
         let link = client.link(checkout_request);
 
+        assert!(link.starts_with("https://www.liqpay.ua/api/3/checkout?data="));
+        assert!(link.contains("&signature="));
+    }
+
+    fn encode_test_payment(client: &HttpAPI, payment: &serde_json::Value) -> (String, String) {
+        let data = encoder.encode(payment.to_string());
+        let signature = client.signature(&data);
+
+        (data, signature)
+    }
+
+    #[test]
+    fn test_decode_payment() {
+        let client = test_client("private_key");
+
+        let (data, signature) = encode_test_payment(
+            &client,
+            &json!({
+                "order_id": "1234",
+                "payment_id": 5678,
+                "action": "pay",
+                "status": "success",
+                "amount": 100.0,
+                "currency": "UAH",
+                "type": "buy",
+                "version": 3
+            }),
+        );
+
+        let payment = client.decode(&data, &signature).unwrap();
+
+        assert_eq!(payment.order_id, "1234");
+        assert_eq!(payment.payment_id, Some(5678));
+        assert_eq!(payment.status, PaymentStatus::Success);
+        assert_eq!(payment.amount, 100.0);
+        assert_eq!(payment.currency, Currency::UAH);
+    }
+
+    #[test]
+    fn test_decode_payment_keeps_unknown_status() {
+        let client = test_client("private_key");
+
+        let (data, signature) = encode_test_payment(
+            &client,
+            &json!({
+                "order_id": "1234",
+                "payment_id": 5678,
+                "action": "pay",
+                "status": "3ds_verify",
+                "amount": 100.0,
+                "currency": "UAH"
+            }),
+        );
+
+        let payment = client.decode(&data, &signature).unwrap();
+
+        assert_eq!(payment.status, PaymentStatus::Pending);
+        assert_eq!(payment.raw_status, "3ds_verify");
+    }
+
+    #[test]
+    fn test_decode_payment_fails_with_invalid_signature() {
+        let client = test_client("private_key");
+        let other_client = test_client("other_private_key");
+
+        let (data, signature) = encode_test_payment(&other_client, &json!({"order_id": "1234"}));
+
+        assert!(matches!(
+            client.decode(&data, &signature),
+            Err(DecodeError::InvalidSignature)
+        ));
+        assert!(matches!(
+            client.decode(&data, ""),
+            Err(DecodeError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_decode_payment_fails_with_invalid_data() {
+        let client = test_client("private_key");
+
+        let signature = client.signature("not base64!");
+        assert!(matches!(
+            client.decode("not base64!", &signature),
+            Err(DecodeError::InvalidEncoding(_))
+        ));
+
+        let (data, signature) = encode_test_payment(&client, &json!({"order_id": "1234"}));
+        assert!(matches!(
+            client.decode(&data, &signature),
+            Err(DecodeError::InvalidPayment(_))
+        ));
+    }
+
+    #[rstest]
+    #[case(PaymentStatus::Success, "success")]
+    #[case(PaymentStatus::Failure, "failure")]
+    #[case(PaymentStatus::Error, "error")]
+    #[case(PaymentStatus::Reversed, "reversed")]
+    #[case(PaymentStatus::Subscribed, "subscribed")]
+    #[case(PaymentStatus::Unsubscribed, "unsubscribed")]
+    #[case(PaymentStatus::Sandbox, "sandbox")]
+    #[case(PaymentStatus::WaitAccept, "wait_accept")]
+    #[case(PaymentStatus::Pending, "3ds_verify")]
+    #[case(PaymentStatus::Pending, "otp_verify")]
+    fn test_payment_status_new(#[case] status: PaymentStatus, #[case] status_string: &str) {
         assert_eq!(
-            link.starts_with("https://www.liqpay.ua/api/3/checkout?data="),
-            true
+            serde_json::from_value::<PaymentStatus>(status_string.into()).unwrap(),
+            status
         );
-        assert_eq!(link.contains("&signature="), true);
+    }
+
+    #[rstest]
+    #[case(
+        "subscribe",
+        PaymentStatus::Subscribed,
+        PaymentStatus::Subscribed,
+        true
+    )]
+    #[case("regular", PaymentStatus::Success, PaymentStatus::Subscribed, true)]
+    #[case(
+        "subscribe",
+        PaymentStatus::Unsubscribed,
+        PaymentStatus::Unsubscribed,
+        false
+    )]
+    #[case("regular", PaymentStatus::Failure, PaymentStatus::Failure, false)]
+    fn test_payment_subscription_state(
+        #[case] action: &str,
+        #[case] status: PaymentStatus,
+        #[case] expected_status: PaymentStatus,
+        #[case] has_next_charge: bool,
+    ) {
+        let payment = Payment {
+            order_id: "1234".to_owned(),
+            payment_id: None,
+            action: action.to_owned(),
+            raw_status: status.as_str().to_owned(),
+            status,
+            amount: 100.0,
+            currency: Currency::UAH,
+            err_code: None,
+        };
+        let now = datetime!(2024-01-15 10:00 UTC);
+
+        assert!(payment.is_subscription());
+        assert_eq!(
+            payment.subscription_state(now, &SubscribePeriod::Month, None),
+            (
+                expected_status,
+                has_next_charge.then_some(datetime!(2024-02-15 10:00 UTC))
+            )
+        );
+    }
+
+    #[test]
+    fn test_payment_subscription_state_keeps_future_charge() {
+        let payment = Payment {
+            order_id: "1234".to_owned(),
+            payment_id: None,
+            action: "subscribe".to_owned(),
+            status: PaymentStatus::Subscribed,
+            raw_status: "subscribed".to_owned(),
+            amount: 100.0,
+            currency: Currency::UAH,
+            err_code: None,
+        };
+        let now = datetime!(2024-01-15 10:00 UTC);
+
+        assert_eq!(
+            payment.subscription_state(
+                now,
+                &SubscribePeriod::Week,
+                Some(datetime!(2024-03-01 00:00 UTC))
+            ),
+            (
+                PaymentStatus::Subscribed,
+                Some(datetime!(2024-03-01 00:00 UTC))
+            )
+        );
+        assert_eq!(
+            payment.subscription_state(
+                now,
+                &SubscribePeriod::Week,
+                Some(datetime!(2024-01-15 09:59 UTC))
+            ),
+            (
+                PaymentStatus::Subscribed,
+                Some(datetime!(2024-01-22 10:00 UTC))
+            )
+        );
+    }
+
+    #[rstest]
+    #[case(SubscribePeriod::Day, datetime!(2024-01-31 10:00 UTC), datetime!(2024-02-01 10:00 UTC))]
+    #[case(SubscribePeriod::Week, datetime!(2024-12-28 10:00 UTC), datetime!(2025-01-04 10:00 UTC))]
+    #[case(SubscribePeriod::Month, datetime!(2024-01-15 10:00 UTC), datetime!(2024-02-15 10:00 UTC))]
+    #[case(SubscribePeriod::Month, datetime!(2024-01-31 10:00 UTC), datetime!(2024-02-29 10:00 UTC))]
+    #[case(SubscribePeriod::Month, datetime!(2024-12-31 10:00 UTC), datetime!(2025-01-31 10:00 UTC))]
+    #[case(SubscribePeriod::Year, datetime!(2024-02-29 10:00 UTC), datetime!(2025-02-28 10:00 UTC))]
+    fn test_subscribe_period_next_charge_date(
+        #[case] periodicity: SubscribePeriod,
+        #[case] from: OffsetDateTime,
+        #[case] expected: OffsetDateTime,
+    ) {
+        assert_eq!(periodicity.next_charge_date(from), expected);
+    }
+
+    #[rstest]
+    #[case(SubscribePeriod::Day, "day")]
+    #[case(SubscribePeriod::Week, "week")]
+    #[case(SubscribePeriod::Month, "month")]
+    #[case(SubscribePeriod::Year, "year")]
+    fn test_subscribe_period_new(#[case] periodicity: SubscribePeriod, #[case] value: &str) {
+        assert_eq!(
+            serde_json::from_value::<SubscribePeriod>(value.into()).unwrap(),
+            periodicity
+        );
+        assert_eq!(SubscribePeriod::try_from(value).unwrap(), periodicity);
+        assert_eq!(periodicity.as_str(), value);
+    }
+
+    #[rstest]
+    #[case(Action::Subscribe, Some(SubscribePeriod::Year), Some(date!(2024-01-15)), Ok(()))]
+    #[case(Action::Subscribe, None, Some(date!(2025-01-15)), Ok(()))]
+    #[case(Action::Subscribe, None, None, Ok(()))]
+    #[case(Action::Pay, None, None, Ok(()))]
+    #[case(
+        Action::Pay,
+        Some(SubscribePeriod::Year),
+        None,
+        Err(InputError::SubscriptionOptionsWithoutSubscribe)
+    )]
+    #[case(
+        Action::PayDonate,
+        None,
+        Some(date!(2024-02-01)),
+        Err(InputError::SubscriptionOptionsWithoutSubscribe)
+    )]
+    #[case(
+        Action::Subscribe,
+        None,
+        Some(date!(2024-01-14)),
+        Err(InputError::StartDateInPast)
+    )]
+    #[case(
+        Action::Subscribe,
+        None,
+        Some(date!(2025-01-16)),
+        Err(InputError::StartDateTooFar)
+    )]
+    fn test_input_query_validate_subscription(
+        #[case] action: Action,
+        #[case] periodicity: Option<SubscribePeriod>,
+        #[case] start_date: Option<Date>,
+        #[case] expected: Result<(), InputError>,
+    ) {
+        let query = InputQuery {
+            action,
+            periodicity,
+            start_date,
+            ..InputQuery::for_tests()
+        };
+
+        assert_eq!(query.validate(datetime!(2024-01-15 10:00 UTC)), expected);
+    }
+
+    #[rstest]
+    #[case(Currency::USD, 1.0, Ok(()))]
+    #[case(Currency::USD, 19.99, Ok(()))]
+    #[case(Currency::EUR, 20_000.0, Ok(()))]
+    #[case(Currency::UAH, 0.1 + 10.2, Ok(()))]
+    #[case(Currency::USD, 0.0, Err(InputError::InvalidAmount))]
+    #[case(Currency::USD, -5.0, Err(InputError::InvalidAmount))]
+    #[case(Currency::USD, f64::NAN, Err(InputError::InvalidAmount))]
+    #[case(Currency::USD, f64::INFINITY, Err(InputError::InvalidAmount))]
+    #[case(
+        Currency::UAH,
+        5.0,
+        Err(InputError::AmountTooSmall { min: 10, currency: "UAH" })
+    )]
+    #[case(Currency::EUR, 10.001, Err(InputError::AmountTooPrecise))]
+    fn test_input_query_validate_amount(
+        #[case] currency: Currency,
+        #[case] amount: f64,
+        #[case] expected: Result<(), InputError>,
+    ) {
+        let query = InputQuery {
+            currency,
+            amount,
+            ..InputQuery::for_tests()
+        };
+
+        assert_eq!(query.validate(datetime!(2024-01-15 10:00 UTC)), expected);
+    }
+
+    #[rstest]
+    #[case("abc123", Ok(()))]
+    #[case("test-store", Ok(()))]
+    #[case("", Err(InputError::InvalidStoreHash))]
+    #[case("store/hash", Err(InputError::InvalidStoreHash))]
+    #[case("a1234567890123456789012345", Err(InputError::InvalidStoreHash))]
+    fn test_input_query_validate_store_hash(
+        #[case] store_hash: &str,
+        #[case] expected: Result<(), InputError>,
+    ) {
+        let query = InputQuery {
+            store_hash: Some(store_hash.to_owned()),
+            ..InputQuery::for_tests()
+        };
+
+        assert_eq!(query.validate(datetime!(2024-01-15 10:00 UTC)), expected);
+    }
+
+    #[rstest]
+    #[case("donor@example.com", Ok(()))]
+    #[case("donor", Err(InputError::InvalidEmail))]
+    #[case(&format!("{}@example.com", "a".repeat(250)), Err(InputError::InvalidEmail))]
+    fn test_input_query_validate_email(
+        #[case] email: &str,
+        #[case] expected: Result<(), InputError>,
+    ) {
+        let query = InputQuery {
+            email: Some(email.to_owned()),
+            ..InputQuery::for_tests()
+        };
+
+        assert_eq!(query.validate(datetime!(2024-01-15 10:00 UTC)), expected);
+    }
+
+    #[test]
+    fn test_input_query_ignores_empty_email() {
+        let query: InputQuery =
+            serde_urlencoded::from_str("language=en&currency=USD&amount=10&action=pay&email=")
+                .unwrap();
+
+        assert_eq!(query.email, None);
+    }
+
+    #[test]
+    fn test_input_query_deserialization() {
+        let query: InputQuery = serde_json::from_value(json!({
+            "language": "en",
+            "currency": "USD",
+            "amount": 100.0,
+            "action": "subscribe",
+            "periodicity": "year",
+            "start_date": "2024-05-01",
+            "store_hash": "abc123",
+            "source": "new-ukraine"
+        }))
+        .unwrap();
+
+        assert_eq!(query.periodicity, Some(SubscribePeriod::Year));
+        assert_eq!(query.start_date, Some(date!(2024 - 05 - 01)));
+        assert_eq!(query.store_hash.as_deref(), Some("abc123"));
+        assert!(matches!(query.source, Some(Charity::NewUkraine)));
     }
 
     #[rstest]
@@ -282,10 +1398,49 @@ mod tests {
     #[rstest]
     #[case(Language::UA, "ua")]
     #[case(Language::EN, "en")]
+    #[case(Language::UA, "uk-UA")]
+    #[case(Language::PL, "pl_PL")]
+    #[case(Language::DE, "de")]
+    #[case(Language::RO, "RO-ro")]
     fn test_language_new(#[case] language: Language, #[case] language_string: &str) {
         assert_eq!(
             serde_json::from_value::<Language>(language_string.into()).unwrap(),
             language
         );
     }
+
+    #[test]
+    fn test_language_new_fails_for_unsupported_locale() {
+        assert!(serde_json::from_value::<Language>("fr-FR".into()).is_err());
+    }
+
+    #[rstest]
+    #[case(PaymentStatus::Pending, PaymentStatus::Success, true)]
+    #[case(PaymentStatus::Success, PaymentStatus::Reversed, true)]
+    #[case(PaymentStatus::Success, PaymentStatus::Processing, false)]
+    #[case(PaymentStatus::Reversed, PaymentStatus::Success, false)]
+    #[case(PaymentStatus::Reversed, PaymentStatus::Reversed, true)]
+    #[case(PaymentStatus::Success, PaymentStatus::Success, true)]
+    #[case(PaymentStatus::Success, PaymentStatus::Failure, false)]
+    #[case(PaymentStatus::Success, PaymentStatus::Error, false)]
+    #[case(PaymentStatus::Success, PaymentStatus::Unsubscribed, false)]
+    #[case(PaymentStatus::Failure, PaymentStatus::Success, false)]
+    #[case(PaymentStatus::Failure, PaymentStatus::Failure, true)]
+    #[case(PaymentStatus::Pending, PaymentStatus::Unsubscribed, false)]
+    #[case(PaymentStatus::Subscribed, PaymentStatus::Unsubscribed, false)]
+    fn test_payment_status_can_change_to(
+        #[case] status: PaymentStatus,
+        #[case] next: PaymentStatus,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(status.can_change_to(&next), expected);
+    }
+
+    #[rstest]
+    #[case("reversed", PaymentStatus::Reversed)]
+    #[case("wait_accept", PaymentStatus::WaitAccept)]
+    #[case("3ds_verify", PaymentStatus::Pending)]
+    fn test_payment_status_from_str(#[case] status: &str, #[case] expected: PaymentStatus) {
+        assert_eq!(status.parse::<PaymentStatus>().unwrap(), expected);
+    }
 }
@@ -1,6 +1,15 @@
 #![deny(unused_extern_crates)]
 
-use swu_app::{configuration::Configuration, startup::Application, telemetry::init_tracing};
+use swu_app::{
+    abuse::CheckoutGuard,
+    configuration::Configuration,
+    links::{generate_link, svg_qr_code, terminal_qr_code},
+    receipts::send_receipts,
+    reconciliation::reconcile,
+    startup::Application,
+    telemetry::init_tracing,
+};
+use time::OffsetDateTime;
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
@@ -8,6 +17,61 @@ async fn main() -> std::io::Result<()> {
 
     let configuration =
         Configuration::generate_from_environment().expect("Failed to read configuration.");
+
+    // the reconciliation job runs from the same image as the server
+    if std::env::args().nth(1).as_deref() == Some("reconcile") {
+        let state = configuration.get_app_state();
+        let summary = reconcile(
+            OffsetDateTime::now_utc(),
+            &configuration.reconciliation,
+            &state.payments,
+            &state.jwt_secret,
+            &state.db_pool,
+        )
+        .await
+        .map_err(std::io::Error::other)?;
+
+        tracing::info!(?summary, "reconciliation finished");
+
+        // receipts whose sending failed after the callback are retried on every run
+        if let Some(mailer) = &state.mailer {
+            let summary = send_receipts(mailer, None, &state.db_pool)
+                .await
+                .map_err(std::io::Error::other)?;
+
+            tracing::info!(?summary, "receipts sent");
+        }
+
+        return Ok(());
+    }
+
+    // links for newsletters and print are made without starting the server
+    if std::env::args().nth(1).as_deref() == Some("link") {
+        let options = std::env::args().nth(2).unwrap_or_default();
+        let link = generate_link(
+            &options,
+            &configuration.application.base_url,
+            &CheckoutGuard::new(
+                configuration.pay_limits.clone(),
+                configuration.checkout.clone(),
+            ),
+            OffsetDateTime::now_utc(),
+        )
+        .map_err(std::io::Error::other)?;
+
+        println!("{link}\n");
+        println!(
+            "{}",
+            terminal_qr_code(&link).map_err(std::io::Error::other)?
+        );
+
+        if let Some(svg_path) = std::env::args().nth(3) {
+            std::fs::write(svg_path, svg_qr_code(&link).map_err(std::io::Error::other)?)?;
+        }
+
+        return Ok(());
+    }
+
     let application = Application::build(configuration).await?;
 
     application.run_until_stopped().await?;
@@ -0,0 +1,256 @@
+use anyhow::Context;
+use axum::{async_trait, http::HeaderMap};
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::{
+    authentication::create_unsubscribe_token,
+    campaign::Campaign,
+    data::{read_subscription, write_donation, write_receipt, write_subscription},
+    liq_pay::{
+        Currency, HttpAPI as LiqPayHttpAPI, InputQuery, Payment, PaymentStatus, SignedCheckout,
+        SubscribePeriod,
+    },
+    stripe::HttpAPI as StripeHttpAPI,
+};
+
+/// Checkout generated by a provider that the donor is sent to
+#[derive(Debug)]
+pub struct Checkout {
+    pub order_id: String,
+    pub url: String,
+    /// Payload for providers whose checkout can be embedded on the page instead
+    pub signed: Option<SignedCheckout>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CheckoutError {
+    #[error("{0}")]
+    Unsupported(&'static str),
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CallbackError {
+    #[error("Callback signature is invalid.")]
+    InvalidSignature,
+
+    #[error("Callback payload is invalid.")]
+    InvalidPayload(#[source] anyhow::Error),
+}
+
+/// Payments are described with the `liq_pay` types as it was the first supported provider
+#[async_trait]
+pub trait PaymentProvider: Send + Sync {
+    async fn create_checkout(
+        &self,
+        query: InputQuery,
+        campaign: &Campaign,
+    ) -> Result<Checkout, CheckoutError>;
+
+    /// Returns `None` for authentic callbacks that do not describe a payment
+    fn verify_callback(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Option<Payment>, CallbackError>;
+
+    async fn query_status(&self, order_id: &str) -> Result<Option<Payment>, anyhow::Error>;
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    LiqPay,
+    Stripe,
+}
+
+impl Provider {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::LiqPay => "liqpay",
+            Self::Stripe => "stripe",
+        }
+    }
+}
+
+impl TryFrom<&str> for Provider {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "liqpay" => Ok(Self::LiqPay),
+            "stripe" => Ok(Self::Stripe),
+            other => Err(format!("{other} is not a supported payment provider.")),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Payments {
+    pub liq_pay: LiqPayHttpAPI,
+    pub stripe: StripeHttpAPI,
+    stripe_currencies: Vec<Currency>,
+}
+
+impl Payments {
+    pub fn new(
+        liq_pay: LiqPayHttpAPI,
+        stripe: StripeHttpAPI,
+        stripe_currencies: Vec<Currency>,
+    ) -> Self {
+        Self {
+            liq_pay,
+            stripe,
+            stripe_currencies,
+        }
+    }
+
+    /// Provider requested by the donor, otherwise the one configured for the currency
+    pub fn select(&self, query: &InputQuery) -> Provider {
+        query.provider.unwrap_or_else(|| {
+            if self.stripe_currencies.contains(&query.currency)
+                || !query.currency.is_supported_by_liq_pay()
+            {
+                Provider::Stripe
+            } else {
+                Provider::LiqPay
+            }
+        })
+    }
+
+    pub fn get(&self, provider: Provider) -> &dyn PaymentProvider {
+        match provider {
+            Provider::LiqPay => &self.liq_pay,
+            Provider::Stripe => &self.stripe,
+        }
+    }
+}
+
+/// Applies a payment reported by a provider to its donation and subscription
+///
+/// Charges are recorded as donations while the setup and cancellation of a subscription only change the subscription
+///
+/// Returns `false` if the payment was ignored because the donation or subscription already has a status it cannot change from
+///
+/// # Errors
+///
+/// Will return `anyhow::Error` if the payment could not be saved
+#[tracing::instrument(name = "record payment", skip(jwt_secret, db_pool))]
+pub async fn record_payment(
+    payment: &Payment,
+    provider: Provider,
+    jwt_secret: &Secret<String>,
+    db_pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let applied = if payment.status.is_charge() {
+        write_donation(payment, provider, db_pool)
+            .await
+            .context("Failed to save donation")?
+    } else {
+        true
+    };
+
+    // a subscription that was only set up has not charged the donor yet
+    if applied && payment.status == PaymentStatus::Success {
+        // a receipt that cannot be queued must never fail recording the payment itself
+        let unsubscribe_token = payment
+            .is_subscription()
+            .then(|| create_unsubscribe_token(&payment.order_id, jwt_secret))
+            .transpose()
+            .unwrap_or_else(|error| {
+                tracing::warn!("Failed to create unsubscribe token: {error:?}");
+                None
+            });
+
+        if let Err(error) = write_receipt(payment, unsubscribe_token.as_deref(), db_pool).await {
+            tracing::warn!("Failed to queue receipt: {error:?}");
+        }
+    }
+
+    if !applied || !payment.is_subscription() {
+        return Ok(applied);
+    }
+
+    let subscription = read_subscription(&payment.order_id, db_pool)
+        .await
+        .context("Failed to read subscription")?;
+    // a charge reported after the donor cancelled is still recorded, but it does not resume the subscription
+    if subscription
+        .as_ref()
+        .is_some_and(|subscription| subscription.status == PaymentStatus::Unsubscribed.as_str())
+    {
+        return Ok(payment.status.is_charge());
+    }
+
+    let periodicity = subscription
+        .as_ref()
+        .and_then(|subscription| SubscribePeriod::try_from(subscription.periodicity.as_str()).ok())
+        .unwrap_or_default();
+
+    let (status, next_charge_at) = payment.subscription_state(
+        OffsetDateTime::now_utc(),
+        &periodicity,
+        subscription.and_then(|subscription| subscription.next_charge_at),
+    );
+
+    write_subscription(payment, &status, next_charge_at, db_pool)
+        .await
+        .context("Failed to save subscription")?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use secrecy::Secret;
+
+    use super::*;
+
+    fn test_payments() -> Payments {
+        Payments::new(
+            LiqPayHttpAPI::new(
+                Secret::new("public_key".to_owned()),
+                Secret::new("private_key".to_owned()),
+                "https://www.liqpay.ua".to_owned(),
+                "https://example.com".to_owned(),
+                "https://example.com/pay/callback".to_owned(),
+                true,
+                std::time::Duration::from_secs(1),
+            ),
+            StripeHttpAPI::new(
+                Secret::new("secret_key".to_owned()),
+                Secret::new("webhook_secret".to_owned()),
+                "https://api.stripe.com".to_owned(),
+                "https://example.com".to_owned(),
+                std::time::Duration::from_secs(1),
+            ),
+            vec![Currency::EUR],
+        )
+    }
+
+    #[rstest]
+    #[case(Currency::UAH, None, Provider::LiqPay)]
+    #[case(Currency::EUR, None, Provider::Stripe)]
+    #[case(Currency::EUR, Some(Provider::LiqPay), Provider::LiqPay)]
+    #[case(Currency::USD, Some(Provider::Stripe), Provider::Stripe)]
+    #[case(Currency::PLN, None, Provider::Stripe)]
+    fn test_select_provider(
+        #[case] currency: Currency,
+        #[case] provider: Option<Provider>,
+        #[case] expected: Provider,
+    ) {
+        let query = InputQuery {
+            currency,
+            provider,
+            ..InputQuery::for_tests()
+        };
+
+        assert_eq!(test_payments().select(&query), expected);
+    }
+}
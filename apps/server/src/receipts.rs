@@ -0,0 +1,204 @@
+use anyhow::Context;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use time::macros::format_description;
+
+use crate::{
+    configuration::Smtp,
+    data::{
+        lock_unsent_receipt, read_unsent_receipts, write_receipt_as_failed, write_receipt_as_sent,
+        Receipt,
+    },
+};
+
+/// Receipts that keep failing are left in the outbox for an operator to look at
+pub const MAX_ATTEMPTS: i32 = 5;
+
+/// Sends receipts to donors through the configured SMTP server
+#[derive(Clone)]
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    /// Address of this server that cancellation links point to
+    base_url: String,
+}
+
+impl Mailer {
+    /// # Panics
+    ///
+    /// Will panic if the sender address or the SMTP host are not valid
+    pub fn new(smtp: &Smtp, base_url: &str) -> Self {
+        let mut transport = if smtp.tls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)
+                .expect("SMTP host is not valid")
+        } else {
+            // plain connections are only meant for a local SMTP sink
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&smtp.host)
+        }
+        .port(smtp.port)
+        .timeout(Some(std::time::Duration::from_millis(smtp.timeout.into())));
+
+        if !smtp.username.is_empty() {
+            transport = transport.credentials(Credentials::new(
+                smtp.username.clone(),
+                smtp.password.expose_secret().clone(),
+            ));
+        }
+
+        Self {
+            transport: transport.build(),
+            from: smtp
+                .from
+                .parse()
+                .expect("SMTP sender is not a valid mailbox"),
+            base_url: base_url.to_owned(),
+        }
+    }
+
+    async fn send(&self, receipt: &Receipt) -> Result<(), anyhow::Error> {
+        let (subject, body) = render(receipt, &self.base_url);
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(receipt.email.parse().context("Invalid donor email")?)
+            .subject(subject)
+            .body(body)
+            .context("Failed to build receipt email")?;
+
+        self.transport
+            .send(message)
+            .await
+            .context("Failed to send receipt email")?;
+
+        Ok(())
+    }
+}
+
+fn render(receipt: &Receipt, base_url: &str) -> (String, String) {
+    let date = receipt
+        .paid_at
+        .format(format_description!("[year]-[month]-[day]"))
+        .unwrap_or_default();
+
+    let mut body = format!(
+        "Thank you for standing with Ukraine.\n\n\
+        Amount: {:.2} {}\n\
+        Date: {date}\n\
+        Campaign: {}\n\
+        Order id: {}\n\n\
+        Please keep this email as the receipt of your donation.\n",
+        receipt.amount, receipt.currency, receipt.campaign, receipt.order_id
+    );
+    if let Some(token) = &receipt.unsubscribe_token {
+        body.push_str(&format!(
+            "\nTo stop your recurring donation open this link:\n{base_url}/pay/unsubscribe?token={token}\n"
+        ));
+    }
+
+    (
+        format!("Receipt for your donation {}", receipt.order_id),
+        body,
+    )
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub sent: usize,
+    pub failed: usize,
+}
+
+/// Sends the receipts waiting in the outbox, only the ones of `order_id` if given
+///
+/// # Errors
+///
+/// Will return `anyhow::Error` if the outbox cannot be read or updated
+#[tracing::instrument(name = "send receipts", skip(mailer, db_pool))]
+pub async fn send_receipts(
+    mailer: &Mailer,
+    order_id: Option<&str>,
+    db_pool: &PgPool,
+) -> Result<Summary, anyhow::Error> {
+    let receipts = read_unsent_receipts(order_id, MAX_ATTEMPTS, db_pool)
+        .await
+        .context("Failed to read unsent receipts")?;
+
+    let mut summary = Summary::default();
+    for receipt in receipts {
+        // callbacks repeated by the provider and the reconciliation job may send at the same time
+        let mut transaction = db_pool
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+        if !lock_unsent_receipt(receipt.id, &mut transaction)
+            .await
+            .context("Failed to lock receipt")?
+        {
+            continue;
+        }
+
+        match mailer.send(&receipt).await {
+            Ok(()) => {
+                write_receipt_as_sent(receipt.id, &mut transaction)
+                    .await
+                    .context("Failed to mark receipt as sent")?;
+                summary.sent += 1;
+            }
+            Err(error) => {
+                tracing::warn!(order_id = receipt.order_id, "{error:?}");
+                write_receipt_as_failed(receipt.id, &format!("{error:#}"), &mut transaction)
+                    .await
+                    .context("Failed to mark receipt as failed")?;
+                summary.failed += 1;
+            }
+        }
+
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit receipt")?;
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn test_receipt(unsubscribe_token: Option<&str>) -> Receipt {
+        Receipt {
+            id: 1,
+            order_id: "order-1".to_owned(),
+            email: "donor@example.com".to_owned(),
+            amount: 100.5,
+            currency: "UAH".to_owned(),
+            campaign: "Warm clothes for the winter".to_owned(),
+            paid_at: datetime!(2025-01-03 12:00 UTC),
+            unsubscribe_token: unsubscribe_token.map(ToOwned::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_render_receipt() {
+        let (subject, body) = render(&test_receipt(None), "https://example.com");
+
+        assert_eq!(subject, "Receipt for your donation order-1");
+        assert!(body.contains("Amount: 100.50 UAH\n"));
+        assert!(body.contains("Date: 2025-01-03\n"));
+        assert!(body.contains("Campaign: Warm clothes for the winter\n"));
+        assert!(body.contains("Order id: order-1\n"));
+        assert!(!body.contains("unsubscribe"));
+    }
+
+    #[test]
+    fn test_render_receipt_with_cancellation_link() {
+        let (_, body) = render(&test_receipt(Some("token")), "https://example.com");
+
+        assert!(body.contains("https://example.com/pay/unsubscribe?token=token\n"));
+    }
+}
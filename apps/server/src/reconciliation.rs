@@ -0,0 +1,72 @@
+use secrecy::Secret;
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    configuration::Reconciliation,
+    data::read_pending_checkouts,
+    payments::{record_payment, Payments},
+};
+
+/// Outcome of a reconciliation run
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Pending checkouts that were looked up with their provider
+    pub checked: usize,
+    /// Payments that were found and recorded
+    pub recorded: usize,
+    /// Lookups that failed and will be retried on the next run
+    pub failed: usize,
+}
+
+/// Queries the provider of every checkout that is still pending and records the payments it reports
+///
+/// # Errors
+///
+/// Will return `anyhow::Error` if pending checkouts cannot be read
+#[tracing::instrument(
+    name = "reconcile pending checkouts",
+    skip(payments, jwt_secret, db_pool)
+)]
+pub async fn reconcile(
+    now: OffsetDateTime,
+    configuration: &Reconciliation,
+    payments: &Payments,
+    jwt_secret: &Secret<String>,
+    db_pool: &PgPool,
+) -> Result<Summary, anyhow::Error> {
+    let checkouts = read_pending_checkouts(
+        now - Duration::hours(configuration.lookback_hours.into()),
+        now - Duration::minutes(configuration.pending_after_minutes.into()),
+        db_pool,
+    )
+    .await?;
+
+    let mut summary = Summary::default();
+
+    for (order_id, provider) in checkouts {
+        summary.checked += 1;
+
+        let payment = match payments.get(provider).query_status(&order_id).await {
+            Ok(Some(payment)) => payment,
+            // the donor never completed the checkout
+            Ok(None) => continue,
+            Err(error) => {
+                tracing::warn!(order_id, "failed to query payment status: {error:?}");
+                summary.failed += 1;
+                continue;
+            }
+        };
+
+        match record_payment(&payment, provider, jwt_secret, db_pool).await {
+            Ok(true) => summary.recorded += 1,
+            Ok(false) => {}
+            Err(error) => {
+                tracing::warn!(order_id, "failed to record payment: {error:?}");
+                summary.failed += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
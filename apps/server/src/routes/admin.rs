@@ -0,0 +1,200 @@
+use crate::{
+    authentication::AdminAuth,
+    campaign::{Campaign, CampaignError, CampaignSettings},
+    data::{
+        read_campaign, read_donation_status_changes, read_donations, read_exchange_rates,
+        write_campaign, write_exchange_rates, DonationRecord, DonationStatusChange,
+    },
+    exchange_rates::{parse_csv, ImportError},
+    state::{AppState, SharedState},
+};
+
+use anyhow::Context;
+use axum::{
+    extract::{rejection::JsonRejection, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+
+pub fn router() -> Router<SharedState> {
+    Router::new()
+        .route("/campaigns/:slug", get(get_campaign).put(put_campaign))
+        .route("/donations/:order_id", get(get_donation))
+        .route(
+            "/exchange-rates",
+            get(get_exchange_rates).put(import_exchange_rates),
+        )
+}
+
+#[derive(thiserror::Error, Debug)]
+enum CampaignAdminError {
+    #[error("Campaign not found.")]
+    NotFound,
+
+    #[error(transparent)]
+    InvalidBody(#[from] JsonRejection),
+
+    #[error(transparent)]
+    InvalidCampaign(#[from] CampaignError),
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for CampaignAdminError {
+    #[tracing::instrument(name = "campaign admin error")]
+    fn into_response(self) -> Response {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND.into_response(),
+            Self::InvalidBody(rejection) => rejection.into_response(),
+            Self::InvalidCampaign(error) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, error.to_string()).into_response()
+            }
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
+
+#[tracing::instrument(name = "get campaign", skip(_admin, db_pool))]
+async fn get_campaign(
+    _admin: AdminAuth,
+    Path(slug): Path<String>,
+    State(AppState { db_pool, .. }): State<AppState>,
+) -> Result<Response, CampaignAdminError> {
+    let campaign = read_campaign(&slug, &db_pool)
+        .await?
+        .ok_or(CampaignAdminError::NotFound)?;
+
+    Ok(Json(campaign).into_response())
+}
+
+/// Creates a campaign or replaces its settings, it takes effect on the next checkout
+#[tracing::instrument(name = "put campaign", skip(_admin, db_pool, settings))]
+async fn put_campaign(
+    _admin: AdminAuth,
+    Path(slug): Path<String>,
+    State(AppState { db_pool, .. }): State<AppState>,
+    settings: Result<Json<CampaignSettings>, JsonRejection>,
+) -> Result<Response, CampaignAdminError> {
+    let Json(settings) = settings?;
+    let campaign = Campaign::from_settings(&slug, settings)?;
+
+    write_campaign(&campaign, &db_pool)
+        .await
+        .context("Failed to write campaign")?;
+
+    Ok(Json(campaign).into_response())
+}
+
+#[derive(thiserror::Error, Debug)]
+enum DonationLookupError {
+    #[error("Donation not found.")]
+    NotFound,
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for DonationLookupError {
+    #[tracing::instrument(name = "donation lookup error")]
+    fn into_response(self) -> Response {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+        .into_response()
+    }
+}
+
+/// Every charge of an order, a subscription has one for each payment
+#[derive(Serialize)]
+struct DonationDetails {
+    order_id: String,
+    charges: Vec<DonationRecord>,
+    status_history: Vec<DonationStatusChange>,
+}
+
+#[tracing::instrument(name = "get donation", skip(_admin, db_pool))]
+async fn get_donation(
+    _admin: AdminAuth,
+    Path(order_id): Path<String>,
+    State(AppState { db_pool, .. }): State<AppState>,
+) -> Result<Response, DonationLookupError> {
+    let charges = read_donations(&order_id, &db_pool)
+        .await
+        .context("Failed to read donations")
+        .map_err(DonationLookupError::UnexpectedError)?;
+    if charges.is_empty() {
+        return Err(DonationLookupError::NotFound);
+    }
+
+    let status_history = read_donation_status_changes(&order_id, &db_pool)
+        .await
+        .context("Failed to read donation status changes")
+        .map_err(DonationLookupError::UnexpectedError)?;
+
+    Ok(Json(DonationDetails {
+        order_id,
+        charges,
+        status_history,
+    })
+    .into_response())
+}
+
+#[derive(thiserror::Error, Debug)]
+enum ExchangeRatesError {
+    #[error(transparent)]
+    InvalidImport(#[from] ImportError),
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for ExchangeRatesError {
+    #[tracing::instrument(name = "exchange rates error")]
+    fn into_response(self) -> Response {
+        match self {
+            Self::InvalidImport(error) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, error.to_string()).into_response()
+            }
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
+
+#[tracing::instrument(name = "get exchange rates", skip(_admin, db_pool))]
+async fn get_exchange_rates(
+    _admin: AdminAuth,
+    State(AppState { db_pool, .. }): State<AppState>,
+) -> Result<Response, ExchangeRatesError> {
+    let rates = read_exchange_rates(&db_pool).await?;
+
+    Ok(Json(rates).into_response())
+}
+
+/// Replaces the rates listed in a CSV body of `currency,base_currency,rate` lines
+#[tracing::instrument(
+    name = "import exchange rates",
+    skip(_admin, db_pool, donation_totals, csv)
+)]
+async fn import_exchange_rates(
+    _admin: AdminAuth,
+    State(AppState {
+        db_pool,
+        donation_totals,
+        ..
+    }): State<AppState>,
+    csv: String,
+) -> Result<Response, ExchangeRatesError> {
+    let rates = parse_csv(&csv)?;
+
+    write_exchange_rates(&rates, &db_pool)
+        .await
+        .context("Failed to write exchange rates")?;
+    donation_totals.clear();
+
+    Ok(Json(rates).into_response())
+}
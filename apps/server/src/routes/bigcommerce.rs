@@ -1,16 +1,26 @@
 use anyhow::Context;
 use axum::{
+    body::Bytes,
     extract::{Query, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Redirect, Response},
-    routing::get,
+    routing::{get, post},
     Router,
 };
 use serde::Deserialize;
+use sqlx::PgPool;
 
 use crate::{
     authentication::{create_jwt, Error},
-    data::{write_store_as_uninstalled, write_store_credentials},
+    bigcommerce::{
+        client::HttpAPI,
+        store::APIToken,
+        webhook::{Event, EventKind, TOKEN_HEADER},
+    },
+    data::{
+        delete_store_user, read_store_credentials, write_store_as_uninstalled,
+        write_store_credentials, write_store_information, write_store_user,
+    },
     state::{AppState, SharedState},
 };
 
@@ -19,6 +29,8 @@ pub fn router() -> Router<SharedState> {
         .route("/install", get(install))
         .route("/uninstall", get(uninstall))
         .route("/load", get(load))
+        .route("/remove_user", get(remove_user))
+        .route("/webhooks", post(webhooks))
 }
 
 #[derive(Deserialize)]
@@ -75,14 +87,36 @@ async fn install(
         .get_bigcommerce_store()
         .map_err(InstallError::UnexpectedError)?;
 
-    write_store_credentials(&store, &db_pool)
+    write_store_credentials(
+        &store,
+        &oauth_credentials.scope,
+        &oauth_credentials.user,
+        &db_pool,
+    )
+    .await
+    .context("Failed to store credentials in database")
+    .map_err(InstallError::UnexpectedError)?;
+
+    // webhooks and metadata keep the store up to date but the app works without them
+    if let Err(error) = bigcommerce_client.register_webhooks(&store).await {
+        tracing::warn!("Failed to register webhooks: {error:?}");
+    }
+    if let Err(error) = refresh_store_information(&bigcommerce_client, &store, &db_pool).await {
+        tracing::warn!("{error:?}");
+    }
+
+    write_store_user(store.get_store_hash(), &oauth_credentials.user, &db_pool)
         .await
-        .context("Failed to store credentials in database")
+        .context("Failed to store user in database")
         .map_err(InstallError::UnexpectedError)?;
 
-    let jwt = create_jwt(store.get_store_hash(), &jwt_secret)
-        .context("Failed to encode jwt token")
-        .map_err(InstallError::UnexpectedError)?;
+    let jwt = create_jwt(
+        store.get_store_hash(),
+        oauth_credentials.user.id,
+        &jwt_secret,
+    )
+    .context("Failed to encode jwt token")
+    .map_err(InstallError::UnexpectedError)?;
 
     Ok(Redirect::to(&generate_dashboard_url(
         &base_url,
@@ -121,12 +155,13 @@ impl IntoResponse for LoadError {
 
 #[tracing::instrument(
     name = "load request",
-    skip(query, bigcommerce_client, base_url, jwt_secret)
+    skip(query, bigcommerce_client, db_pool, base_url, jwt_secret)
 )]
 async fn load(
     Query(query): Query<LoadQuery>,
     State(AppState {
         bigcommerce_client,
+        db_pool,
         base_url,
         jwt_secret,
         ..
@@ -140,7 +175,12 @@ async fn load(
         .get_store_hash()
         .map_err(LoadError::UnexpectedError)?;
 
-    let jwt = create_jwt(store_hash, &jwt_secret)
+    write_store_user(store_hash, claims.get_user(), &db_pool)
+        .await
+        .context("Failed to store user in database")
+        .map_err(LoadError::UnexpectedError)?;
+
+    let jwt = create_jwt(store_hash, claims.get_user().id, &jwt_secret)
         .context("Failed to encode token")
         .map_err(LoadError::UnexpectedError)?;
 
@@ -176,6 +216,124 @@ async fn uninstall(
     Ok(StatusCode::OK.into_response())
 }
 
+#[tracing::instrument(name = "remove user request", skip(query, bigcommerce_client, db_pool))]
+async fn remove_user(
+    Query(query): Query<LoadQuery>,
+    State(AppState {
+        bigcommerce_client,
+        db_pool,
+        ..
+    }): State<AppState>,
+) -> Result<Response, LoadError> {
+    let claims = bigcommerce_client
+        .decode_jwt(&query.signed_payload_jwt)
+        .map_err(LoadError::InvalidCredentials)?;
+
+    let store_hash = claims
+        .get_store_hash()
+        .map_err(LoadError::UnexpectedError)?;
+
+    delete_store_user(store_hash, claims.get_user().id, &db_pool)
+        .await
+        .context("Failed to remove user from database")
+        .map_err(LoadError::UnexpectedError)?;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+async fn refresh_store_information(
+    bigcommerce_client: &HttpAPI,
+    store: &APIToken,
+    db_pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let information = bigcommerce_client
+        .get_store_information(store)
+        .await
+        .context("Failed to get store information")?;
+
+    write_store_information(store.get_store_hash(), &information, db_pool)
+        .await
+        .context("Failed to set store information")
+}
+
+#[derive(thiserror::Error, Debug)]
+enum WebhookError {
+    #[error("Invalid webhook.")]
+    InvalidWebhook(#[source] anyhow::Error),
+
+    #[error("Invalid credentials.")]
+    InvalidCredentials,
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for WebhookError {
+    #[tracing::instrument(name = "webhook error")]
+    fn into_response(self) -> Response {
+        match self {
+            Self::InvalidWebhook(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+        .into_response()
+    }
+}
+
+#[tracing::instrument(
+    name = "webhook request",
+    skip(bigcommerce_client, db_pool, headers, body),
+    fields(scope=tracing::field::Empty, store_hash=tracing::field::Empty)
+)]
+async fn webhooks(
+    State(AppState {
+        bigcommerce_client,
+        db_pool,
+        ..
+    }): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, WebhookError> {
+    let event: Event = serde_json::from_slice(&body)
+        .context("Failed to parse webhook")
+        .map_err(WebhookError::InvalidWebhook)?;
+    let store_hash = event
+        .get_store_hash()
+        .map_err(WebhookError::InvalidWebhook)?;
+
+    tracing::Span::current()
+        .record("scope", tracing::field::display(&event.scope))
+        .record("store_hash", tracing::field::display(store_hash));
+
+    // only webhooks we registered carry the token of the store they are about
+    let token = headers
+        .get(TOKEN_HEADER)
+        .and_then(|token| token.to_str().ok())
+        .ok_or(WebhookError::InvalidCredentials)?;
+    if !bigcommerce_client.verify_webhook_token(store_hash, token) {
+        return Err(WebhookError::InvalidCredentials);
+    }
+
+    match event.kind() {
+        EventKind::AppUninstalled => {
+            write_store_as_uninstalled(store_hash, &db_pool)
+                .await
+                .context("Failed to set store as uninstalled")?;
+        }
+        // channel sites can change the storefront url
+        EventKind::InformationUpdated | EventKind::ChannelChanged => {
+            let store = read_store_credentials(store_hash, &db_pool)
+                .await
+                .context("Failed to read store credentials")?;
+
+            refresh_store_information(&bigcommerce_client, &store, &db_pool).await?;
+        }
+        EventKind::Unknown => tracing::warn!("ignoring webhook that was not registered"),
+    }
+
+    Ok(StatusCode::OK.into_response())
+}
+
 fn generate_dashboard_url(base_url: &str, token: &str, store_hash: &str) -> String {
     format!("{base_url}/dashboard/?token={token}&store-id={store_hash}")
 }
@@ -2,6 +2,7 @@ use axum::{response::Response, routing::get, Router};
 
 use crate::state::SharedState;
 
+mod admin;
 mod bigcommerce;
 mod pay;
 mod widget;
@@ -16,4 +17,5 @@ pub fn router() -> Router<SharedState> {
         .nest("/pay", pay::router())
         .nest("/api", widget::router())
         .nest("/bigcommerce", bigcommerce::router())
+        .nest("/admin", admin::router())
 }
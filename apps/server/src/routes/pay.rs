@@ -1,23 +1,893 @@
-use crate::liq_pay::InputQuery;
+use crate::abuse::{AbuseError, CheckoutGuard, ClientIp};
+use crate::authentication::{decode_unsubscribe_token, Error};
+use crate::campaign::{Campaign, GENERAL_CAMPAIGN};
+use crate::data::{
+    read_campaign, read_subscription, write_checkout, write_prepared_subscription,
+    write_subscription_as_cancelled, Subscription,
+};
+use crate::landing::{FormError, FormValues, LandingPage, UnsubscribePage, UnsubscribedPage};
+use crate::links::{png_qr_code, svg_qr_code};
+use crate::liq_pay::{
+    Action, Currency, InputError, InputQuery, Language, PaymentStatus, SignedCheckout,
+    SubscribePeriod,
+};
+use crate::payments::{record_payment, CallbackError, Checkout, CheckoutError, Payments, Provider};
+use crate::receipts::{send_receipts, Mailer};
 use crate::state::{AppState, SharedState};
-use axum::extract::{Query, State};
-use axum::response::Redirect;
-use axum::routing::get;
-use axum::Router;
+use anyhow::Context;
+use askama::Template;
+use axum::body::Bytes;
+use axum::extract::rejection::{QueryRejection, RawFormRejection};
+use axum::extract::{Path, Query, RawForm, RawQuery, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::routing::{get, post};
+use axum::{Form, Json, Router};
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::net::IpAddr;
+use time::OffsetDateTime;
+use tower_http::cors::CorsLayer;
 
 pub fn router() -> Router<SharedState> {
-    Router::new().route("/", get(pay))
+    // embedded checkouts are requested by front ends served from other origins
+    let embedded_router = Router::new()
+        .route("/checkout", get(embedded_checkout))
+        .route("/c/:slug/checkout", get(embedded_campaign_checkout))
+        .layer(CorsLayer::permissive());
+
+    Router::new()
+        .route("/", get(pay))
+        .route("/", post(submit_pay))
+        .route("/c/:slug", get(pay_campaign))
+        .route("/c/:slug", post(submit_pay_campaign))
+        .route("/qr", get(pay_qr))
+        .route("/c/:slug/qr", get(pay_campaign_qr))
+        .route("/callback", post(liq_pay_callback))
+        .route("/callback/:provider", post(provider_callback))
+        .route("/unsubscribe", get(confirm_unsubscribe))
+        .route("/unsubscribe", post(unsubscribe))
+        .merge(embedded_router)
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: &'static str,
+    field: Option<&'static str>,
+    message: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+enum PayError {
+    #[error("{0}")]
+    InvalidQuery(String),
+
+    #[error(transparent)]
+    InvalidInput(#[from] InputError),
+
+    #[error("Campaign not found.")]
+    CampaignNotFound,
+
+    #[error("{0}")]
+    UnsupportedCheckout(&'static str),
+
+    #[error(transparent)]
+    TooManyCheckouts(AbuseError),
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl From<CheckoutError> for PayError {
+    fn from(error: CheckoutError) -> Self {
+        match error {
+            CheckoutError::Unsupported(message) => Self::UnsupportedCheckout(message),
+            CheckoutError::UnexpectedError(error) => Self::UnexpectedError(error),
+        }
+    }
+}
+
+impl IntoResponse for PayError {
+    #[tracing::instrument(name = "pay error")]
+    fn into_response(self) -> Response {
+        let (status, body) = match &self {
+            Self::InvalidQuery(message) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody {
+                    error: "invalid_query",
+                    field: None,
+                    message: message.clone(),
+                },
+            ),
+            Self::InvalidInput(error) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorBody {
+                    error: "invalid_input",
+                    field: Some(error.field()),
+                    message: error.to_string(),
+                },
+            ),
+            Self::CampaignNotFound => (
+                StatusCode::NOT_FOUND,
+                ErrorBody {
+                    error: "campaign_not_found",
+                    field: None,
+                    message: self.to_string(),
+                },
+            ),
+            Self::UnsupportedCheckout(message) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorBody {
+                    error: "unsupported_checkout",
+                    field: Some("provider"),
+                    message: (*message).to_owned(),
+                },
+            ),
+            Self::TooManyCheckouts(error) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                ErrorBody {
+                    error: "too_many_checkouts",
+                    field: None,
+                    message: error.to_string(),
+                },
+            ),
+            Self::UnexpectedError(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+
+        (status, Json(body)).into_response()
+    }
+}
+
+impl PayError {
+    /// Problem to show on the landing page form instead of the JSON body API clients get
+    fn form_error(&self) -> Option<(StatusCode, FormError<'_>)> {
+        match self {
+            Self::InvalidQuery(_) => Some((StatusCode::BAD_REQUEST, FormError::Invalid)),
+            Self::InvalidInput(error) => {
+                Some((StatusCode::UNPROCESSABLE_ENTITY, FormError::Input(error)))
+            }
+            Self::UnsupportedCheckout(_) => Some((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                FormError::CheckoutUnavailable,
+            )),
+            Self::TooManyCheckouts(_) => {
+                Some((StatusCode::TOO_MANY_REQUESTS, FormError::TooManyCheckouts))
+            }
+            Self::CampaignNotFound | Self::UnexpectedError(_) => None,
+        }
+    }
 }
 
-#[tracing::instrument(name = "pay request", skip(query, liq_pay_client))]
+/// Query of a visitor opening the landing page instead of a checkout link
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct LandingQuery {
+    language: Option<Language>,
+}
+
+fn landing_page(
+    campaign: &Campaign,
+    query: LandingQuery,
+    guard: &CheckoutGuard,
+) -> Result<Response, PayError> {
+    let campaign = landing_campaign(campaign, guard);
+
+    let page = LandingPage::new(&campaign, &query.language.unwrap_or(Language::EN))
+        .render()
+        .context("Failed to render landing page")
+        .map_err(PayError::UnexpectedError)?;
+
+    Ok(Html(page).into_response())
+}
+
+/// Campaign as the landing page offers it, narrowed to the currencies checkouts accept
+fn landing_campaign(campaign: &Campaign, guard: &CheckoutGuard) -> Campaign {
+    let currencies: Vec<Currency> = campaign
+        .currencies
+        .iter()
+        .filter(|currency| guard.accepts_currency(currency))
+        .cloned()
+        .collect();
+    let default_currency = if currencies.contains(&campaign.default_currency) {
+        campaign.default_currency.clone()
+    } else {
+        // the browser preselects the first option when the default is not configured
+        currencies
+            .first()
+            .unwrap_or(&campaign.default_currency)
+            .clone()
+    };
+    Campaign {
+        currencies,
+        default_currency,
+        ..campaign.clone()
+    }
+}
+
+/// Starts a checkout for a submitted landing page form, showing the form again when it cannot
+async fn submit_form(
+    client: IpAddr,
+    form: Result<RawForm, RawFormRejection>,
+    campaign: &Campaign,
+    payments: &Payments,
+    guard: &CheckoutGuard,
+    db_pool: &PgPool,
+) -> Result<Response, PayError> {
+    let RawForm(body) = form.map_err(|rejection| PayError::InvalidQuery(rejection.body_text()))?;
+    let values: FormValues = serde_urlencoded::from_bytes(&body)
+        .map_err(|error| PayError::InvalidQuery(error.to_string()))?;
+
+    let result = match serde_urlencoded::from_bytes::<InputQuery>(&body) {
+        Ok(query) => checkout(client, query, campaign, payments, guard, db_pool).await,
+        Err(error) => Err(PayError::InvalidQuery(error.to_string())),
+    };
+
+    let error = match result {
+        Ok((_, checkout)) => return Ok(Redirect::to(&checkout.url).into_response()),
+        Err(error) => error,
+    };
+    let Some((status, form_error)) = error.form_error() else {
+        return Err(error);
+    };
+    tracing::info!(%error, "Donation form was not accepted");
+
+    let language = values
+        .language
+        .clone()
+        .and_then(|language| Language::try_from(language).ok())
+        .unwrap_or(Language::EN);
+    let campaign = landing_campaign(campaign, guard);
+
+    let page = LandingPage::new(&campaign, &language)
+        .with_error(values, &form_error)
+        .render()
+        .context("Failed to render landing page")
+        .map_err(PayError::UnexpectedError)?;
+
+    Ok((status, Html(page)).into_response())
+}
+
+async fn read_general_campaign(db_pool: &PgPool) -> Result<Campaign, PayError> {
+    // the general campaign can be customized but donations must keep working without it
+    Ok(read_campaign(GENERAL_CAMPAIGN, db_pool)
+        .await
+        .map_err(PayError::UnexpectedError)?
+        .unwrap_or_else(Campaign::general))
+}
+
+async fn read_active_campaign(slug: &str, db_pool: &PgPool) -> Result<Campaign, PayError> {
+    read_campaign(slug, db_pool)
+        .await
+        .map_err(PayError::UnexpectedError)?
+        .filter(|campaign| campaign.is_active(OffsetDateTime::now_utc()))
+        .ok_or(PayError::CampaignNotFound)
+}
+
+#[tracing::instrument(
+    name = "pay request",
+    skip(landing, query, payments, checkout_guard, db_pool)
+)]
 async fn pay(
-    Query(query): Query<InputQuery>,
-    State(AppState { liq_pay_client, .. }): State<AppState>,
-) -> Redirect {
-    let checkout_request = liq_pay_client
-        .generate_request_payload(query, "Support BigCommerce colleagues defending Ukraine");
+    ClientIp(client): ClientIp,
+    landing: Result<Query<LandingQuery>, QueryRejection>,
+    query: Result<Query<InputQuery>, QueryRejection>,
+    State(AppState {
+        payments,
+        checkout_guard,
+        db_pool,
+        ..
+    }): State<AppState>,
+) -> Result<Response, PayError> {
+    let campaign = read_general_campaign(&db_pool).await?;
+
+    if let Ok(Query(landing)) = landing {
+        return landing_page(&campaign, landing, &checkout_guard);
+    }
+
+    let Query(query) = query.map_err(|rejection| PayError::InvalidQuery(rejection.body_text()))?;
+
+    let (_, checkout) = checkout(
+        client,
+        query,
+        &campaign,
+        &payments,
+        &checkout_guard,
+        &db_pool,
+    )
+    .await?;
+
+    Ok(Redirect::to(&checkout.url).into_response())
+}
+
+#[tracing::instrument(
+    name = "pay form submission",
+    skip(form, payments, checkout_guard, db_pool)
+)]
+async fn submit_pay(
+    ClientIp(client): ClientIp,
+    State(AppState {
+        payments,
+        checkout_guard,
+        db_pool,
+        ..
+    }): State<AppState>,
+    form: Result<RawForm, RawFormRejection>,
+) -> Result<Response, PayError> {
+    let campaign = read_general_campaign(&db_pool).await?;
+
+    submit_form(
+        client,
+        form,
+        &campaign,
+        &payments,
+        &checkout_guard,
+        &db_pool,
+    )
+    .await
+}
+
+#[tracing::instrument(
+    name = "campaign pay request",
+    skip(landing, query, payments, checkout_guard, db_pool)
+)]
+async fn pay_campaign(
+    ClientIp(client): ClientIp,
+    Path(slug): Path<String>,
+    landing: Result<Query<LandingQuery>, QueryRejection>,
+    query: Result<Query<InputQuery>, QueryRejection>,
+    State(AppState {
+        payments,
+        checkout_guard,
+        db_pool,
+        ..
+    }): State<AppState>,
+) -> Result<Response, PayError> {
+    let campaign = read_active_campaign(&slug, &db_pool).await?;
+
+    if let Ok(Query(landing)) = landing {
+        return landing_page(&campaign, landing, &checkout_guard);
+    }
+
+    let Query(query) = query.map_err(|rejection| PayError::InvalidQuery(rejection.body_text()))?;
+
+    let (_, checkout) = checkout(
+        client,
+        query,
+        &campaign,
+        &payments,
+        &checkout_guard,
+        &db_pool,
+    )
+    .await?;
+
+    Ok(Redirect::to(&checkout.url).into_response())
+}
+
+#[tracing::instrument(
+    name = "campaign pay form submission",
+    skip(form, payments, checkout_guard, db_pool)
+)]
+async fn submit_pay_campaign(
+    ClientIp(client): ClientIp,
+    Path(slug): Path<String>,
+    State(AppState {
+        payments,
+        checkout_guard,
+        db_pool,
+        ..
+    }): State<AppState>,
+    form: Result<RawForm, RawFormRejection>,
+) -> Result<Response, PayError> {
+    let campaign = read_active_campaign(&slug, &db_pool).await?;
+
+    submit_form(
+        client,
+        form,
+        &campaign,
+        &payments,
+        &checkout_guard,
+        &db_pool,
+    )
+    .await
+}
+
+/// Image format of a QR code, SVG unless PNG is asked for
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum QrFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+#[derive(Deserialize, Debug)]
+struct QrQuery {
+    #[serde(default)]
+    format: QrFormat,
+}
+
+/// QR codes are printed, so they are only made for links that `/pay` would accept
+fn qr_donation_query(
+    raw_query: Option<&str>,
+    campaign: &Campaign,
+    guard: &CheckoutGuard,
+) -> Result<String, PayError> {
+    let invalid_query =
+        |error: serde_urlencoded::de::Error| PayError::InvalidQuery(error.to_string());
+
+    let params: Vec<(String, String)> =
+        serde_urlencoded::from_str(raw_query.unwrap_or_default()).map_err(invalid_query)?;
+    let donation_query = serde_urlencoded::to_string(
+        params
+            .iter()
+            .filter(|(key, _)| key != "format")
+            .collect::<Vec<_>>(),
+    )
+    .context("Failed to encode donation query")
+    .map_err(PayError::UnexpectedError)?;
+
+    if serde_urlencoded::from_str::<LandingQuery>(&donation_query).is_err() {
+        let query: InputQuery =
+            serde_urlencoded::from_str(&donation_query).map_err(invalid_query)?;
+        query.validate(OffsetDateTime::now_utc())?;
+        guard.validate(&query)?;
+        campaign.validate(&query)?;
+    }
+
+    Ok(donation_query)
+}
+
+fn qr_code(format: QrFormat, path: &str, donation_query: &str) -> Result<Response, PayError> {
+    let url = if donation_query.is_empty() {
+        path.to_owned()
+    } else {
+        format!("{path}?{donation_query}")
+    };
+
+    let (content_type, body) = match format {
+        QrFormat::Svg => (
+            "image/svg+xml",
+            svg_qr_code(&url)
+                .context("Failed to create QR code")?
+                .into_bytes(),
+        ),
+        QrFormat::Png => ("image/png", png_qr_code(&url)?),
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[tracing::instrument(
+    name = "pay qr code request",
+    skip(query, raw_query, base_url, checkout_guard, db_pool)
+)]
+async fn pay_qr(
+    query: Result<Query<QrQuery>, QueryRejection>,
+    RawQuery(raw_query): RawQuery,
+    State(AppState {
+        base_url,
+        checkout_guard,
+        db_pool,
+        ..
+    }): State<AppState>,
+) -> Result<Response, PayError> {
+    let Query(query) = query.map_err(|rejection| PayError::InvalidQuery(rejection.body_text()))?;
+
+    let campaign = read_general_campaign(&db_pool).await?;
+    let donation_query = qr_donation_query(raw_query.as_deref(), &campaign, &checkout_guard)?;
+
+    qr_code(query.format, &format!("{base_url}/pay"), &donation_query)
+}
+
+#[tracing::instrument(
+    name = "campaign pay qr code request",
+    skip(query, raw_query, base_url, checkout_guard, db_pool)
+)]
+async fn pay_campaign_qr(
+    Path(slug): Path<String>,
+    query: Result<Query<QrQuery>, QueryRejection>,
+    RawQuery(raw_query): RawQuery,
+    State(AppState {
+        base_url,
+        checkout_guard,
+        db_pool,
+        ..
+    }): State<AppState>,
+) -> Result<Response, PayError> {
+    let Query(query) = query.map_err(|rejection| PayError::InvalidQuery(rejection.body_text()))?;
+
+    let campaign = read_active_campaign(&slug, &db_pool).await?;
+    let donation_query = qr_donation_query(raw_query.as_deref(), &campaign, &checkout_guard)?;
+
+    qr_code(
+        query.format,
+        &format!("{base_url}/pay/c/{}", campaign.slug),
+        &donation_query,
+    )
+}
+
+#[tracing::instrument(
+    name = "checkout",
+    skip(client, query, campaign, payments, guard, db_pool),
+    fields(campaign=campaign.slug, provider=tracing::field::Empty)
+)]
+async fn checkout(
+    client: IpAddr,
+    query: InputQuery,
+    campaign: &Campaign,
+    payments: &Payments,
+    guard: &CheckoutGuard,
+    db_pool: &PgPool,
+) -> Result<(Provider, Checkout), PayError> {
+    let now = OffsetDateTime::now_utc();
+    query.validate(now).map_err(PayError::InvalidInput)?;
+    guard.validate(&query).map_err(PayError::InvalidInput)?;
+    campaign.validate(&query).map_err(PayError::InvalidInput)?;
+    guard
+        .check(client, query.amount)
+        .map_err(PayError::TooManyCheckouts)?;
+
+    let provider = payments.select(&query);
+    tracing::Span::current().record("provider", tracing::field::display(provider.as_str()));
+
+    let (amount, currency) = (query.amount, query.currency.clone());
+    let (store_hash, source) = (query.store_hash.clone(), query.source.clone());
+    let receipt_email = query.email.clone();
+    let subscription = (query.action == Action::Subscribe).then(|| {
+        (
+            query.periodicity.clone().unwrap_or_default(),
+            query.subscription_start(now),
+        )
+    });
+
+    let checkout = payments
+        .get(provider)
+        .create_checkout(query, campaign)
+        .await?;
+
+    write_checkout(
+        &checkout.order_id,
+        provider,
+        &campaign.slug,
+        store_hash.as_deref(),
+        source.as_ref(),
+        receipt_email.as_deref(),
+        db_pool,
+    )
+    .await
+    .context("Failed to save checkout")
+    .map_err(PayError::UnexpectedError)?;
+
+    if let Some((periodicity, start)) = subscription {
+        write_prepared_subscription(
+            &checkout.order_id,
+            amount,
+            &currency,
+            &periodicity,
+            start,
+            db_pool,
+        )
+        .await
+        .context("Failed to save subscription")
+        .map_err(PayError::UnexpectedError)?;
+    }
+
+    Ok((provider, checkout))
+}
+
+/// Checkout for front ends that keep donors on the page with the `LiqPay` widget
+#[derive(Serialize)]
+struct EmbeddedCheckout {
+    provider: Provider,
+    order_id: String,
+    url: String,
+    #[serde(flatten)]
+    signed: Option<SignedCheckout>,
+}
+
+impl From<(Provider, Checkout)> for EmbeddedCheckout {
+    fn from((provider, checkout): (Provider, Checkout)) -> Self {
+        Self {
+            provider,
+            order_id: checkout.order_id,
+            url: checkout.url,
+            signed: checkout.signed,
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "embedded checkout request",
+    skip(query, payments, checkout_guard, db_pool)
+)]
+async fn embedded_checkout(
+    ClientIp(client): ClientIp,
+    query: Result<Query<InputQuery>, QueryRejection>,
+    State(AppState {
+        payments,
+        checkout_guard,
+        db_pool,
+        ..
+    }): State<AppState>,
+) -> Result<Response, PayError> {
+    let Query(query) = query.map_err(|rejection| PayError::InvalidQuery(rejection.body_text()))?;
+
+    let campaign = read_general_campaign(&db_pool).await?;
+    let checkout = checkout(
+        client,
+        query,
+        &campaign,
+        &payments,
+        &checkout_guard,
+        &db_pool,
+    )
+    .await?;
+
+    Ok(Json(EmbeddedCheckout::from(checkout)).into_response())
+}
+
+#[tracing::instrument(
+    name = "embedded campaign checkout request",
+    skip(query, payments, checkout_guard, db_pool)
+)]
+async fn embedded_campaign_checkout(
+    ClientIp(client): ClientIp,
+    Path(slug): Path<String>,
+    query: Result<Query<InputQuery>, QueryRejection>,
+    State(AppState {
+        payments,
+        checkout_guard,
+        db_pool,
+        ..
+    }): State<AppState>,
+) -> Result<Response, PayError> {
+    let Query(query) = query.map_err(|rejection| PayError::InvalidQuery(rejection.body_text()))?;
+
+    let campaign = read_active_campaign(&slug, &db_pool).await?;
+    let checkout = checkout(
+        client,
+        query,
+        &campaign,
+        &payments,
+        &checkout_guard,
+        &db_pool,
+    )
+    .await?;
+
+    Ok(Json(EmbeddedCheckout::from(checkout)).into_response())
+}
+
+#[derive(thiserror::Error, Debug)]
+enum PaymentCallbackError {
+    #[error("Invalid callback.")]
+    InvalidCallback(#[from] CallbackError),
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for PaymentCallbackError {
+    #[tracing::instrument(name = "payment callback error")]
+    fn into_response(self) -> Response {
+        match self {
+            Self::InvalidCallback(_) => StatusCode::BAD_REQUEST,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+        .into_response()
+    }
+}
+
+// liq pay callbacks were configured before other providers were supported
+async fn liq_pay_callback(
+    State(AppState {
+        payments,
+        jwt_secret,
+        db_pool,
+        mailer,
+        ..
+    }): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, PaymentCallbackError> {
+    callback(
+        Provider::LiqPay,
+        &payments,
+        &jwt_secret,
+        &db_pool,
+        mailer.as_ref(),
+        &headers,
+        &body,
+    )
+    .await
+}
+
+async fn provider_callback(
+    Path(provider): Path<Provider>,
+    State(AppState {
+        payments,
+        jwt_secret,
+        db_pool,
+        mailer,
+        ..
+    }): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, PaymentCallbackError> {
+    callback(
+        provider,
+        &payments,
+        &jwt_secret,
+        &db_pool,
+        mailer.as_ref(),
+        &headers,
+        &body,
+    )
+    .await
+}
+
+#[tracing::instrument(
+    name = "payment callback",
+    skip(payments, jwt_secret, db_pool, mailer, headers, body),
+    fields(order_id=tracing::field::Empty, status=tracing::field::Empty)
+)]
+async fn callback(
+    provider: Provider,
+    payments: &Payments,
+    jwt_secret: &Secret<String>,
+    db_pool: &PgPool,
+    mailer: Option<&Mailer>,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Response, PaymentCallbackError> {
+    let Some(payment) = payments
+        .get(provider)
+        .verify_callback(headers, body)
+        .map_err(PaymentCallbackError::InvalidCallback)?
+    else {
+        return Ok(StatusCode::OK.into_response());
+    };
+
+    tracing::Span::current()
+        .record("order_id", tracing::field::display(&payment.order_id))
+        .record("status", tracing::field::display(payment.status.as_str()));
+
+    let applied = record_payment(&payment, provider, jwt_secret, db_pool)
+        .await
+        .map_err(PaymentCallbackError::UnexpectedError)?;
+
+    if !applied {
+        tracing::warn!("ignoring status that cannot replace the recorded donation status");
+    }
+
+    if let Some(mailer) = mailer.filter(|_| applied) {
+        // the provider only waits for the callback, receipts left unsent are retried by `reconcile`
+        let (mailer, db_pool, order_id) = (mailer.clone(), db_pool.clone(), payment.order_id);
+        tokio::spawn(async move {
+            if let Err(error) = send_receipts(&mailer, Some(&order_id), &db_pool).await {
+                tracing::error!("{error:?}");
+            }
+        });
+    }
+
+    Ok(StatusCode::OK.into_response())
+}
+
+#[derive(Deserialize)]
+struct UnsubscribeQuery {
+    token: String,
+    language: Option<Language>,
+}
+
+#[derive(thiserror::Error, Debug)]
+enum UnsubscribeError {
+    #[error("Invalid credentials.")]
+    InvalidCredentials(#[source] Error),
+
+    #[error("Subscription not found.")]
+    NotFound,
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for UnsubscribeError {
+    #[tracing::instrument(name = "unsubscribe error")]
+    fn into_response(self) -> Response {
+        match self {
+            Self::InvalidCredentials(_) => StatusCode::UNAUTHORIZED,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+        .into_response()
+    }
+}
+
+// links are opened from email clients that prefetch urls, so cancelling requires a form submission
+#[tracing::instrument(name = "confirm unsubscribe request", skip(query, jwt_secret, db_pool))]
+async fn confirm_unsubscribe(
+    Query(query): Query<UnsubscribeQuery>,
+    State(AppState {
+        jwt_secret,
+        db_pool,
+        ..
+    }): State<AppState>,
+) -> Result<Response, UnsubscribeError> {
+    let order_id = decode_unsubscribe_token(&query.token, &jwt_secret)
+        .map_err(UnsubscribeError::InvalidCredentials)?;
+
+    let subscription = read_subscription(&order_id, &db_pool)
+        .await
+        .context("Failed to read subscription")
+        .map_err(UnsubscribeError::UnexpectedError)?
+        .ok_or(UnsubscribeError::NotFound)?;
+
+    let page = UnsubscribePage::new(
+        &query.token,
+        &subscription_period(&subscription),
+        &query.language.unwrap_or(Language::EN),
+    )
+    .render()
+    .context("Failed to render unsubscribe page")?;
+
+    Ok(Html(page).into_response())
+}
+
+fn subscription_period(subscription: &Subscription) -> SubscribePeriod {
+    SubscribePeriod::try_from(subscription.periodicity.as_str()).unwrap_or_default()
+}
+
+#[tracing::instrument(
+    name = "unsubscribe request",
+    skip(form, jwt_secret, db_pool, payments),
+    fields(order_id=tracing::field::Empty)
+)]
+async fn unsubscribe(
+    State(AppState {
+        jwt_secret,
+        db_pool,
+        payments,
+        ..
+    }): State<AppState>,
+    Form(form): Form<UnsubscribeQuery>,
+) -> Result<Response, UnsubscribeError> {
+    let order_id = decode_unsubscribe_token(&form.token, &jwt_secret)
+        .map_err(UnsubscribeError::InvalidCredentials)?;
+
+    tracing::Span::current().record("order_id", tracing::field::display(&order_id));
+
+    let subscription = read_subscription(&order_id, &db_pool)
+        .await
+        .context("Failed to read subscription")
+        .map_err(UnsubscribeError::UnexpectedError)?
+        .ok_or(UnsubscribeError::NotFound)?;
+
+    if subscription.status != PaymentStatus::Unsubscribed.as_str() {
+        payments
+            .liq_pay
+            .unsubscribe(&order_id)
+            .await
+            .map_err(UnsubscribeError::UnexpectedError)?;
+
+        write_subscription_as_cancelled(&order_id, &db_pool)
+            .await
+            .context("Failed to set subscription as cancelled")
+            .map_err(UnsubscribeError::UnexpectedError)?;
+    }
 
-    let url = liq_pay_client.link(checkout_request);
+    let page = UnsubscribedPage::new(
+        &subscription_period(&subscription),
+        &form.language.unwrap_or(Language::EN),
+    )
+    .render()
+    .context("Failed to render unsubscribed page")?;
 
-    Redirect::to(&url)
+    Ok(Html(page).into_response())
 }
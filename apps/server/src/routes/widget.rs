@@ -1,22 +1,28 @@
 use crate::{
     authentication::AuthClaims,
+    bigcommerce::auth::{has_scope, SCRIPTS_SCOPE},
+    campaign::GENERAL_CAMPAIGN,
     data::{
-        read_store_credentials, read_store_published, read_widget_configuration,
-        write_charity_visited_event, write_general_feedback, write_store_published,
-        write_universal_widget_event, write_unpublish_feedback, write_widget_configuration,
-        write_widget_event, CharityEvent, FeedbackForm, UniversalConfiguratorEvent,
-        WidgetConfiguration, WidgetEvent,
+        read_campaign, read_donation_totals, read_exchange_rates, read_store_credentials,
+        read_store_donation_totals, read_store_published, read_store_scope,
+        read_widget_configuration, write_charity_visited_event, write_general_feedback,
+        write_store_published, write_universal_widget_event, write_unpublish_feedback,
+        write_widget_configuration, write_widget_event, CharityEvent, DonationSummary,
+        FeedbackForm, SourceTotal, UniversalConfiguratorEvent, WidgetConfiguration, WidgetEvent,
     },
+    exchange_rates::normalize,
+    liq_pay::{is_valid_store_hash, Currency},
     state::{AppState, SharedState},
 };
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 use tower_http::cors::CorsLayer;
 
 use anyhow::Context;
 use axum::{
     extract::{Query, State},
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     routing::{delete, get, post},
     Json, Router,
@@ -29,19 +35,22 @@ pub fn router() -> Router<SharedState> {
         .route("/publish", post(publish_widget))
         .route("/publish", get(get_published_status))
         .route("/publish", delete(remove_widget))
-        .route("/preview", get(preview_widget));
+        .route("/preview", get(preview_widget))
+        .route("/donations", get(get_store_donations));
 
     let cors = CorsLayer::permissive();
 
+    // layers only wrap the routes registered before them
     let v2_router = Router::new()
-        .layer(cors)
         .route("/widget-event", post(log_widget_event))
         .route("/charity-event", post(log_charity_event))
         .route("/feedback-form", post(submit_general_feedback))
         .route(
             "/universal-event",
             post(submit_universal_configurator_event),
-        );
+        )
+        .route("/donations/summary", get(get_donation_summary))
+        .layer(cors);
 
     Router::new().nest("/v1", v1_router).nest("/v2", v2_router)
 }
@@ -92,6 +101,9 @@ async fn get_widget_configuration(
 
 #[derive(thiserror::Error, Debug)]
 enum PublishError {
+    #[error("The app was installed without the Store Content - Modify scope, reinstall it with the scripts scope to publish the widget.")]
+    MissingScriptsScope,
+
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -100,15 +112,31 @@ impl IntoResponse for PublishError {
     #[tracing::instrument(name = "publish error")]
     fn into_response(self) -> Response {
         match self {
-            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::MissingScriptsScope => (StatusCode::FORBIDDEN, self.to_string()).into_response(),
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
         }
-        .into_response()
     }
 }
 
+/// Fails before calling the Scripts API, which only answers with a bare 403 without the scope
+async fn require_scripts_scope(store_hash: &str, db_pool: &PgPool) -> Result<(), PublishError> {
+    let scope = read_store_scope(store_hash, db_pool)
+        .await
+        .context("Failed to get store scope")
+        .map_err(PublishError::UnexpectedError)?;
+
+    // stores installed before scopes were recorded are left to the Scripts API
+    if scope.is_some_and(|scope| !has_scope(&scope, SCRIPTS_SCOPE)) {
+        return Err(PublishError::MissingScriptsScope);
+    }
+
+    Ok(())
+}
+
 #[tracing::instrument(
     name = "publish widget",
-    skip(auth, db_pool, base_url, bigcommerce_client)
+    skip(auth, db_pool, base_url, bigcommerce_client),
+    fields(store_hash = %auth.sub, user_id = ?auth.user_id)
 )]
 async fn publish_widget(
     auth: AuthClaims,
@@ -120,6 +148,8 @@ async fn publish_widget(
     }): State<AppState>,
 ) -> Result<Response, PublishError> {
     let store_hash = auth.sub.as_str();
+    require_scripts_scope(store_hash, &db_pool).await?;
+
     let widget_configuration = read_widget_configuration(store_hash, &db_pool)
         .await
         .map_err(PublishError::UnexpectedError)?;
@@ -148,7 +178,7 @@ async fn publish_widget(
     }
     .map_err(PublishError::UnexpectedError)?;
 
-    write_store_published(store_hash, true, &db_pool)
+    write_store_published(store_hash, true, auth.user_id, &db_pool)
         .await
         .context("Failed to set store as published")
         .map_err(PublishError::UnexpectedError)?;
@@ -163,7 +193,8 @@ struct Feedback {
 
 #[tracing::instrument(
     name = "remove widget",
-    skip(auth, db_pool, bigcommerce_client, feedback)
+    skip(auth, db_pool, bigcommerce_client, feedback),
+    fields(store_hash = %auth.sub, user_id = ?auth.user_id)
 )]
 async fn remove_widget(
     auth: AuthClaims,
@@ -175,6 +206,7 @@ async fn remove_widget(
     Query(feedback): Query<Feedback>,
 ) -> Result<Response, PublishError> {
     let store_hash = auth.sub.as_str();
+    require_scripts_scope(store_hash, &db_pool).await?;
 
     let store = read_store_credentials(store_hash, &db_pool)
         .await
@@ -187,13 +219,13 @@ async fn remove_widget(
         .context("Failed to remove scripts in BigCommerce")
         .map_err(PublishError::UnexpectedError)?;
 
-    write_store_published(store_hash, false, &db_pool)
+    write_store_published(store_hash, false, auth.user_id, &db_pool)
         .await
         .context("Failed to set store as not published")
         .map_err(PublishError::UnexpectedError)?;
 
     if let Some(reason) = feedback.reason {
-        write_unpublish_feedback(store_hash, reason.as_str(), &db_pool)
+        write_unpublish_feedback(store_hash, reason.as_str(), auth.user_id, &db_pool)
             .await
             .context("Failed to record unpublish feedback")
             .map_err(PublishError::UnexpectedError)?;
@@ -289,3 +321,112 @@ async fn log_widget_event(
 
     StatusCode::OK.into_response()
 }
+
+#[derive(Deserialize, Debug)]
+struct DonationSummaryQuery {
+    campaign: Option<String>,
+    store_hash: Option<String>,
+    reporting_currency: Option<Currency>,
+}
+
+#[derive(thiserror::Error, Debug)]
+enum DonationSummaryError {
+    #[error("Store hash must be letters, digits or dashes.")]
+    InvalidStoreHash,
+
+    #[error("Campaign not found.")]
+    CampaignNotFound,
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for DonationSummaryError {
+    #[tracing::instrument(name = "donation summary error")]
+    fn into_response(self) -> Response {
+        match self {
+            Self::InvalidStoreHash => (StatusCode::BAD_REQUEST, self.to_string()).into_response(),
+            Self::CampaignNotFound => (StatusCode::NOT_FOUND, self.to_string()).into_response(),
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
+
+#[tracing::instrument(name = "get donation summary", skip(db_pool, donation_totals))]
+async fn get_donation_summary(
+    Query(query): Query<DonationSummaryQuery>,
+    State(AppState {
+        db_pool,
+        donation_totals,
+        ..
+    }): State<AppState>,
+) -> Result<Response, DonationSummaryError> {
+    // anyone can call this endpoint, so only keys of real stores and campaigns reach the cache
+    if query
+        .store_hash
+        .as_deref()
+        .is_some_and(|store_hash| !is_valid_store_hash(store_hash))
+    {
+        return Err(DonationSummaryError::InvalidStoreHash);
+    }
+
+    let key = (query.campaign, query.store_hash, query.reporting_currency);
+
+    let summary = if let Some(summary) = donation_totals.get(&key) {
+        summary
+    } else {
+        if let Some(campaign) = key.0.as_deref().filter(|slug| *slug != GENERAL_CAMPAIGN) {
+            read_campaign(campaign, &db_pool)
+                .await?
+                .ok_or(DonationSummaryError::CampaignNotFound)?;
+        }
+
+        let totals = read_donation_totals(key.0.as_deref(), key.1.as_deref(), &db_pool)
+            .await
+            .context("Failed to read donation totals")
+            .map_err(DonationSummaryError::UnexpectedError)?;
+
+        let total = match &key.2 {
+            Some(reporting_currency) => {
+                let rates = read_exchange_rates(&db_pool)
+                    .await
+                    .map_err(DonationSummaryError::UnexpectedError)?;
+                Some(normalize(&totals, &rates, reporting_currency))
+            }
+            None => None,
+        };
+
+        let summary = DonationSummary { totals, total };
+        donation_totals.insert(key, summary.clone());
+        summary
+    };
+
+    Ok((
+        [(
+            header::CACHE_CONTROL,
+            format!("public, max-age={}", donation_totals.ttl().as_secs()),
+        )],
+        Json(summary),
+    )
+        .into_response())
+}
+
+#[derive(Serialize)]
+struct StoreDonations {
+    totals: Vec<SourceTotal>,
+}
+
+#[tracing::instrument(name = "get store donations", skip(auth, db_pool))]
+async fn get_store_donations(
+    auth: AuthClaims,
+    State(AppState { db_pool, .. }): State<AppState>,
+) -> Result<Response, DonationSummaryError> {
+    let store_hash = auth.sub.as_str();
+
+    let totals = read_store_donation_totals(store_hash, &db_pool)
+        .await
+        .context("Failed to read store donation totals")
+        .map_err(DonationSummaryError::UnexpectedError)?;
+
+    Ok(Json(StoreDonations { totals }).into_response())
+}
@@ -1,16 +1,25 @@
 use crate::configuration::{Configuration, Database};
 use crate::routes;
 use crate::state::SharedState;
+use axum::extract::connect_info::IntoMakeServiceWithConnectInfo;
+use axum::extract::ConnectInfo;
+use axum::middleware::AddExtension;
 use axum::serve::Serve;
 use axum::Router;
 use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, OtelInResponseLayer};
 use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use tokio::signal;
 
+type Server = Serve<
+    IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    AddExtension<Router, ConnectInfo<SocketAddr>>,
+>;
+
 pub struct Application {
     port: u16,
-    server: Serve<Router, Router>,
+    server: Server,
 }
 
 impl Application {
@@ -53,14 +62,18 @@ pub fn get_connection_pool(configuration: &Database) -> PgPool {
 
 #[allow(clippy::default_constructed_unit_structs)]
 // reason = "`OtelInResponseLayer` struct is an external dependency that might change"
-pub fn build_server(listener: TcpListener, shared_state: SharedState) -> Serve<Router, Router> {
+pub fn build_server(listener: TcpListener, shared_state: SharedState) -> Server {
     let app = Router::new()
         .merge(routes::router())
         .layer(OtelInResponseLayer::default())
         .layer(OtelAxumLayer::default())
         .with_state(shared_state);
 
-    axum::serve(listener, app)
+    // the peer address identifies clients that are not behind the load balancer
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
 }
 
 async fn shutdown_signal() {
@@ -5,7 +5,8 @@ use secrecy::Secret;
 use sqlx::PgPool;
 
 use crate::{
-    bigcommerce::client::HttpAPI as BigCommerceHttpAPI, liq_pay::HttpAPI as LiqPayHttpAPI,
+    abuse::CheckoutGuard, bigcommerce::client::HttpAPI as BigCommerceHttpAPI, cache::TtlCache,
+    data::DonationSummary, liq_pay::Currency, payments::Payments, receipts::Mailer,
 };
 
 #[allow(clippy::module_name_repetitions)]
@@ -15,10 +16,19 @@ pub struct AppState {
     pub db_pool: PgPool,
     pub base_url: String,
     pub jwt_secret: Secret<String>,
+    pub admin_api_key: Secret<String>,
     pub bigcommerce_client: BigCommerceHttpAPI,
-    pub liq_pay_client: LiqPayHttpAPI,
+    pub payments: Payments,
+    pub donation_totals: DonationTotalsCache,
+    pub checkout_guard: CheckoutGuard,
+    /// Missing while no SMTP server is configured, receipts then wait in the outbox
+    pub mailer: Option<Mailer>,
 }
 
+/// Donation summaries keyed by the campaign and store they are filtered by and the reporting currency
+pub type DonationTotalsCache =
+    TtlCache<(Option<String>, Option<String>, Option<Currency>), DonationSummary>;
+
 #[allow(clippy::module_name_repetitions)]
 // reason="`SharedState` is clearer than just `Shared` and it is widespread across the app"
 pub type SharedState = Arc<AppState>;
@@ -28,3 +38,9 @@ impl FromRef<SharedState> for AppState {
         shared_state.as_ref().clone()
     }
 }
+
+impl FromRef<SharedState> for CheckoutGuard {
+    fn from_ref(shared_state: &SharedState) -> Self {
+        shared_state.checkout_guard.clone()
+    }
+}
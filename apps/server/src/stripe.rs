@@ -0,0 +1,391 @@
+use anyhow::Context;
+use axum::{async_trait, http::HeaderMap};
+use constant_time_eq::constant_time_eq;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sha2::Sha256;
+use time::OffsetDateTime;
+
+use crate::{
+    campaign::Campaign,
+    liq_pay::{Action, InputQuery, Payment, PaymentStatus},
+    payments::{CallbackError, Checkout, CheckoutError, PaymentProvider},
+};
+
+/// How old a webhook signature can be before it is considered a replay
+const SIGNATURE_TOLERANCE_SECONDS: i64 = 300;
+
+#[derive(Clone)]
+pub struct HttpAPI {
+    secret_key: Secret<String>,
+    webhook_secret: Secret<String>,
+    api_base_url: String,
+    result_url: String,
+    http_client: Client,
+}
+
+#[derive(Deserialize)]
+struct SessionResponse {
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct Event {
+    #[serde(rename = "type")]
+    kind: String,
+    data: EventData,
+}
+
+#[derive(Deserialize)]
+struct EventData {
+    object: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct CheckoutSession {
+    client_reference_id: String,
+    amount_total: i64,
+    currency: String,
+    payment_status: String,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    data: Vec<PaymentIntent>,
+}
+
+#[derive(Deserialize)]
+struct PaymentIntent {
+    amount: i64,
+    currency: String,
+    status: String,
+}
+
+impl HttpAPI {
+    pub fn new(
+        secret_key: Secret<String>,
+        webhook_secret: Secret<String>,
+        api_base_url: String,
+        result_url: String,
+        timeout: std::time::Duration,
+    ) -> Self {
+        let http_client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("Stripe api client could not be built");
+
+        Self {
+            secret_key,
+            webhook_secret,
+            api_base_url,
+            result_url,
+            http_client,
+        }
+    }
+
+    fn get_checkout_sessions_route(&self) -> String {
+        format!("{}/v1/checkout/sessions", self.api_base_url)
+    }
+
+    fn get_payment_intents_search_route(&self) -> String {
+        format!("{}/v1/payment_intents/search", self.api_base_url)
+    }
+
+    pub fn signature(&self, timestamp: i64, body: &[u8]) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(self.webhook_secret.expose_secret().as_bytes())
+                .expect("HMAC can take key of any size");
+        mac.update(format!("{timestamp}.").as_bytes());
+        mac.update(body);
+
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    fn verify_signature(&self, header: &str, body: &[u8]) -> Result<(), CallbackError> {
+        let mut timestamp = None;
+        let mut signatures = vec![];
+
+        for (key, value) in header.split(',').filter_map(|part| part.split_once('=')) {
+            match key {
+                "t" => timestamp = value.parse::<i64>().ok(),
+                "v1" => signatures.push(value),
+                _ => {}
+            }
+        }
+
+        let timestamp = timestamp.ok_or(CallbackError::InvalidSignature)?;
+        if (OffsetDateTime::now_utc().unix_timestamp() - timestamp).abs()
+            > SIGNATURE_TOLERANCE_SECONDS
+        {
+            return Err(CallbackError::InvalidSignature);
+        }
+
+        let expected = self.signature(timestamp, body);
+        if signatures
+            .iter()
+            .any(|signature| constant_time_eq(expected.as_bytes(), signature.as_bytes()))
+        {
+            Ok(())
+        } else {
+            Err(CallbackError::InvalidSignature)
+        }
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+// reason="stripe amounts are in minor units and well below f64 precision limits"
+fn from_minor_units(amount: i64) -> f64 {
+    amount as f64 / 100.0
+}
+
+#[async_trait]
+impl PaymentProvider for HttpAPI {
+    #[tracing::instrument(name = "create stripe checkout", skip(self, campaign))]
+    async fn create_checkout(
+        &self,
+        query: InputQuery,
+        campaign: &Campaign,
+    ) -> Result<Checkout, CheckoutError> {
+        if query.action == Action::Subscribe {
+            return Err(CheckoutError::Unsupported(
+                "Recurring donations are only supported by LiqPay.",
+            ));
+        }
+
+        let order_id = uuid::Uuid::new_v4().to_string();
+        let unit_amount = format!("{:.0}", query.amount * 100.0);
+        let currency = query.currency.as_str().to_lowercase();
+
+        let session = self
+            .http_client
+            .post(self.get_checkout_sessions_route())
+            .bearer_auth(self.secret_key.expose_secret())
+            .form(&[
+                ("mode", "payment"),
+                ("locale", query.language.stripe_locale()),
+                ("success_url", self.result_url.as_str()),
+                ("cancel_url", self.result_url.as_str()),
+                ("client_reference_id", order_id.as_str()),
+                ("payment_intent_data[metadata][order_id]", order_id.as_str()),
+                ("line_items[0][quantity]", "1"),
+                ("line_items[0][price_data][currency]", currency.as_str()),
+                (
+                    "line_items[0][price_data][unit_amount]",
+                    unit_amount.as_str(),
+                ),
+                (
+                    "line_items[0][price_data][product_data][name]",
+                    campaign.description.as_str(),
+                ),
+            ])
+            .send()
+            .await
+            .context("create checkout session request")?
+            .error_for_status()
+            .context("create checkout session response")?
+            .json::<SessionResponse>()
+            .await
+            .context("parse checkout session response")?;
+
+        Ok(Checkout {
+            order_id,
+            url: session.url,
+            signed: None,
+        })
+    }
+
+    fn verify_callback(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Option<Payment>, CallbackError> {
+        let header = headers
+            .get("Stripe-Signature")
+            .and_then(|header| header.to_str().ok())
+            .ok_or(CallbackError::InvalidSignature)?;
+        self.verify_signature(header, body)?;
+
+        let event: Event = serde_json::from_slice(body)
+            .map_err(|error| CallbackError::InvalidPayload(error.into()))?;
+
+        let status = match event.kind.as_str() {
+            "checkout.session.completed" | "checkout.session.async_payment_succeeded" => None,
+            "checkout.session.async_payment_failed" => Some(PaymentStatus::Failure),
+            _ => return Ok(None),
+        };
+
+        let session: CheckoutSession = serde_json::from_value(event.data.object)
+            .map_err(|error| CallbackError::InvalidPayload(error.into()))?;
+
+        let status = status.unwrap_or(match session.payment_status.as_str() {
+            "paid" | "no_payment_required" => PaymentStatus::Success,
+            _ => PaymentStatus::Processing,
+        });
+
+        Ok(Some(Payment {
+            order_id: session.client_reference_id,
+            payment_id: None,
+            action: "pay".to_owned(),
+            status,
+            raw_status: session.payment_status,
+            amount: from_minor_units(session.amount_total),
+            currency: session
+                .currency
+                .parse()
+                .map_err(|error: String| CallbackError::InvalidPayload(anyhow::anyhow!(error)))?,
+            err_code: None,
+        }))
+    }
+
+    #[tracing::instrument(name = "get stripe payment status", skip(self))]
+    async fn query_status(&self, order_id: &str) -> Result<Option<Payment>, anyhow::Error> {
+        let response = self
+            .http_client
+            .get(self.get_payment_intents_search_route())
+            .bearer_auth(self.secret_key.expose_secret())
+            .query(&[("query", format!("metadata['order_id']:'{order_id}'"))])
+            .send()
+            .await
+            .context("search payment intents request")?
+            .error_for_status()?
+            .json::<SearchResponse>()
+            .await
+            .context("parse search payment intents response")?;
+
+        let Some(payment_intent) = response.data.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let status = match payment_intent.status.as_str() {
+            "succeeded" => PaymentStatus::Success,
+            "processing" => PaymentStatus::Processing,
+            "requires_capture" => PaymentStatus::HoldWait,
+            "canceled" => PaymentStatus::Failure,
+            _ => PaymentStatus::Pending,
+        };
+
+        Ok(Some(Payment {
+            order_id: order_id.to_owned(),
+            payment_id: None,
+            action: "pay".to_owned(),
+            status,
+            raw_status: payment_intent.status,
+            amount: from_minor_units(payment_intent.amount),
+            currency: payment_intent
+                .currency
+                .parse()
+                .map_err(anyhow::Error::msg)?,
+            err_code: None,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use serde_json::json;
+
+    use super::*;
+    use crate::liq_pay::Currency;
+
+    fn test_client() -> HttpAPI {
+        HttpAPI::new(
+            Secret::new("secret_key".to_owned()),
+            Secret::new("webhook_secret".to_owned()),
+            "https://api.stripe.com".to_owned(),
+            "https://example.com".to_owned(),
+            std::time::Duration::from_secs(1),
+        )
+    }
+
+    fn signed_headers(client: &HttpAPI, timestamp: i64, body: &[u8]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "Stripe-Signature",
+            HeaderValue::from_str(&format!(
+                "t={timestamp},v1={}",
+                client.signature(timestamp, body)
+            ))
+            .unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_verify_callback_completed_session() {
+        let client = test_client();
+        let body = json!({
+            "type": "checkout.session.completed",
+            "data": {
+                "object": {
+                    "client_reference_id": "1234",
+                    "amount_total": 12345,
+                    "currency": "eur",
+                    "payment_status": "paid"
+                }
+            }
+        })
+        .to_string();
+        let headers = signed_headers(
+            &client,
+            OffsetDateTime::now_utc().unix_timestamp(),
+            body.as_bytes(),
+        );
+
+        let payment = client
+            .verify_callback(&headers, body.as_bytes())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(payment.order_id, "1234");
+        assert_eq!(payment.status, PaymentStatus::Success);
+        assert_eq!(payment.amount, 123.45);
+        assert_eq!(payment.currency, Currency::EUR);
+    }
+
+    #[test]
+    fn test_verify_callback_ignores_other_events() {
+        let client = test_client();
+        let body = json!({"type": "customer.created", "data": {"object": {}}}).to_string();
+        let headers = signed_headers(
+            &client,
+            OffsetDateTime::now_utc().unix_timestamp(),
+            body.as_bytes(),
+        );
+
+        assert!(client
+            .verify_callback(&headers, body.as_bytes())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_verify_callback_fails_with_invalid_or_stale_signature() {
+        let client = test_client();
+        let body = json!({"type": "customer.created", "data": {"object": {}}}).to_string();
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        let headers = signed_headers(&client, now, b"other body");
+        assert!(matches!(
+            client.verify_callback(&headers, body.as_bytes()),
+            Err(CallbackError::InvalidSignature)
+        ));
+
+        let headers = signed_headers(&client, now - 3600, body.as_bytes());
+        assert!(matches!(
+            client.verify_callback(&headers, body.as_bytes()),
+            Err(CallbackError::InvalidSignature)
+        ));
+
+        assert!(matches!(
+            client.verify_callback(&HeaderMap::new(), body.as_bytes()),
+            Err(CallbackError::InvalidSignature)
+        ));
+    }
+}
@@ -0,0 +1,310 @@
+use secrecy::ExposeSecret;
+use serde_json::{json, Value};
+
+use crate::helpers;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_donation_lookup_returns_status_history() {
+    let app = helpers::spawn_app().await;
+
+    for status in ["success", "reversed"] {
+        app.send_liq_pay_callback(&json!({
+            "order_id": "order-1",
+            "payment_id": 1234,
+            "action": "pay",
+            "status": status,
+            "amount": 100.0,
+            "currency": "UAH"
+        }))
+        .await;
+    }
+
+    let response = app
+        .test_client
+        .get(app.test_server_url("/admin/donations/order-1"))
+        .bearer_auth(app.admin_api_key.expose_secret())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let donation = response.json::<Value>().await.unwrap();
+    assert_eq!(donation["order_id"], "order-1");
+    assert_eq!(donation["charges"].as_array().unwrap().len(), 1);
+    assert_eq!(donation["charges"][0]["payment_id"], 1234);
+    assert_eq!(donation["charges"][0]["status"], "reversed");
+    assert_eq!(donation["charges"][0]["provider"], "liqpay");
+    assert_eq!(
+        donation["status_history"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|change| (change["previous_status"].clone(), change["status"].clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (Value::Null, json!("success")),
+            (json!("success"), json!("reversed"))
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_donation_lookup_keeps_provider_statuses() {
+    let app = helpers::spawn_app().await;
+
+    for status in ["3ds_verify", "otp_verify", "success"] {
+        app.send_liq_pay_callback(&json!({
+            "order_id": "order-1",
+            "payment_id": 1234,
+            "action": "pay",
+            "status": status,
+            "amount": 100.0,
+            "currency": "UAH"
+        }))
+        .await;
+    }
+
+    let donation = app
+        .test_client
+        .get(app.test_server_url("/admin/donations/order-1"))
+        .bearer_auth(app.admin_api_key.expose_secret())
+        .send()
+        .await
+        .expect("Failed to execute the request")
+        .json::<Value>()
+        .await
+        .unwrap();
+
+    assert_eq!(donation["charges"][0]["raw_status"], "success");
+    assert_eq!(
+        donation["status_history"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|change| (change["status"].clone(), change["raw_status"].clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (json!("pending"), json!("3ds_verify")),
+            (json!("pending"), json!("otp_verify")),
+            (json!("success"), json!("success"))
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_donation_lookup_fails_for_unknown_order() {
+    let app = helpers::spawn_app().await;
+
+    let response = app
+        .test_client
+        .get(app.test_server_url("/admin/donations/unknown"))
+        .bearer_auth(app.admin_api_key.expose_secret())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_donation_lookup_requires_admin_api_key() {
+    let app = helpers::spawn_app().await;
+
+    for token in ["wrong-key".to_owned(), app.generate_local_jwt_token()] {
+        let response = app
+            .test_client
+            .get(app.test_server_url("/admin/donations/order-1"))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .test_client
+        .get(app.test_server_url("/admin/donations/order-1"))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_exchange_rate_import_replaces_rates() {
+    let app = helpers::spawn_app().await;
+
+    for csv in [
+        "currency,base_currency,rate\nUAH,USD,0.02\nEUR,USD,1.1\n",
+        "UAH,USD,0.025\n",
+    ] {
+        let response = app
+            .test_client
+            .put(app.test_server_url("/admin/exchange-rates"))
+            .bearer_auth(app.admin_api_key.expose_secret())
+            .body(csv)
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let response = app
+        .test_client
+        .get(app.test_server_url("/admin/exchange-rates"))
+        .bearer_auth(app.admin_api_key.expose_secret())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.json::<Value>().await.unwrap(),
+        json!([
+            {"currency": "EUR", "base_currency": "USD", "rate": 1.1},
+            {"currency": "UAH", "base_currency": "USD", "rate": 0.025}
+        ])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_exchange_rate_import_rejects_invalid_csv() {
+    let app = helpers::spawn_app().await;
+
+    let response = app
+        .test_client
+        .put(app.test_server_url("/admin/exchange-rates"))
+        .bearer_auth(app.admin_api_key.expose_secret())
+        .body("UAH,USD,0.025\nGBP,USD,1.2\n")
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 422);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "Line 2: GBP is not a supported currency."
+    );
+
+    let response = app
+        .test_client
+        .put(app.test_server_url("/admin/exchange-rates"))
+        .bearer_auth("wrong-key")
+        .body("UAH,USD,0.025\n")
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_campaign_update_changes_landing_page() {
+    let app = helpers::spawn_app().await;
+    app.insert_campaign("winter", "Warm clothes for the winter", &["UAH"], None)
+        .await;
+
+    let campaign = json!({
+        "description": "Generators for the winter",
+        "currencies": ["UAH", "EUR"],
+        "default_currency": "EUR",
+        "preset_amounts": [20.0, 100.0],
+        "ends_at": "2100-03-01T00:00:00Z"
+    });
+    let response = app
+        .test_client
+        .put(app.test_server_url("/admin/campaigns/winter"))
+        .bearer_auth(app.admin_api_key.expose_secret())
+        .json(&campaign)
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .test_client
+        .get(app.test_server_url("/admin/campaigns/winter"))
+        .bearer_auth(app.admin_api_key.expose_secret())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.json::<Value>().await.unwrap(),
+        json!({
+            "slug": "winter",
+            "description": "Generators for the winter",
+            "currencies": ["UAH", "EUR"],
+            "default_currency": "EUR",
+            "preset_amounts": [20.0, 100.0],
+            "starts_at": null,
+            "ends_at": "2100-03-01T00:00:00Z"
+        })
+    );
+
+    let page = app
+        .test_client
+        .get(app.test_server_url("/pay/c/winter"))
+        .send()
+        .await
+        .expect("Failed to execute the request")
+        .text()
+        .await
+        .unwrap();
+
+    assert!(page.contains("Generators for the winter"));
+    assert!(page.contains(r#"<option value="UAH">UAH</option>"#));
+    assert!(page.contains(r#"<option value="EUR" selected>EUR</option>"#));
+    assert!(page.contains(">100</button>"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_campaign_update_rejects_invalid_campaign() {
+    let app = helpers::spawn_app().await;
+
+    let response = app
+        .test_client
+        .put(app.test_server_url("/admin/campaigns/spring"))
+        .bearer_auth(app.admin_api_key.expose_secret())
+        .json(&json!({
+            "description": "Spring fundraiser",
+            "currencies": ["UAH"],
+            "default_currency": "USD",
+            "preset_amounts": [100.0]
+        }))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 422);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "Default currency USD is not one of the accepted currencies."
+    );
+
+    let response = app
+        .test_client
+        .get(app.test_server_url("/admin/campaigns/spring"))
+        .bearer_auth("wrong-key")
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .test_client
+        .get(app.test_server_url("/admin/campaigns/spring"))
+        .bearer_auth(app.admin_api_key.expose_secret())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 404);
+}
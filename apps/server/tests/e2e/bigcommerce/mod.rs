@@ -1,9 +1,14 @@
 use crate::{
     helpers::{create_test_server_client_no_redirect, spawn_app},
-    mocks::get_oauth2_token_mock,
+    mocks::{
+        create_webhook_mock, get_oauth2_token_mock, get_store_information_mock, get_webhooks_mock,
+        update_webhook_mock,
+    },
 };
 use secrecy::Secret;
+use serde_json::json;
 use swu_app::{
+    authentication::decode_token,
     bigcommerce::{auth::User, store::APIToken},
     data::write_store_credentials,
 };
@@ -14,7 +19,7 @@ async fn install_request_fails_without_bigcommerce_response() {
     let client = create_test_server_client_no_redirect();
 
     let response = client
-        .get(&app.test_server_url("/bigcommerce/install"))
+        .get(app.test_server_url("/bigcommerce/install"))
         .query(&[
             ("context", "stores/STORE_HASH"),
             ("scope", "test-scope"),
@@ -47,7 +52,7 @@ async fn install_request_fails_without_query_parameters() {
 
     let response = app
         .test_client
-        .get(&app.test_server_url("/bigcommerce/install"))
+        .get(app.test_server_url("/bigcommerce/install"))
         .query(&[("code", "test")])
         .send()
         .await
@@ -61,7 +66,7 @@ async fn install_request_fails_without_query_parameters() {
 
     let response = app
         .test_client
-        .get(&app.test_server_url("/bigcommerce/install"))
+        .get(app.test_server_url("/bigcommerce/install"))
         .query(&[("scope", "test")])
         .send()
         .await
@@ -83,9 +88,25 @@ async fn install_request_succeeds() {
         .expect(1)
         .mount(&app.bigcommerce_server)
         .await;
+    get_webhooks_mock("STORE_HASH", "ACCESS_TOKEN", json!([]))
+        .expect(1)
+        .mount(&app.bigcommerce_server)
+        .await;
+    create_webhook_mock(
+        "STORE_HASH",
+        "ACCESS_TOKEN",
+        json!({
+            "destination": app.bc_webhook_url,
+            "is_active": true,
+            "headers": {"X-SWU-Webhook-Token": app.generate_bc_webhook_token("STORE_HASH")}
+        }),
+    )
+    .expect(3)
+    .mount(&app.bigcommerce_server)
+    .await;
 
     let response = client
-        .get(&app.test_server_url("/bigcommerce/install"))
+        .get(app.test_server_url("/bigcommerce/install"))
         .query(&[
             ("context", "stores/STORE_HASH"),
             ("scope", "test-scope"),
@@ -109,7 +130,7 @@ async fn install_request_succeeds() {
 
     let row = sqlx::query!(
         r#"
-        SELECT store_hash, access_token, uninstalled, published FROM stores
+        SELECT store_hash, access_token, uninstalled, published, scope, installed_by_user_id, installed_by_email FROM stores
         WHERE store_hash = 'STORE_HASH'
         "#
     )
@@ -119,6 +140,171 @@ async fn install_request_succeeds() {
 
     assert_eq!(row.access_token, "ACCESS_TOKEN");
     assert_eq!(row.store_hash, "STORE_HASH");
+    assert_eq!(row.scope.as_deref(), Some("store_v2_orders"));
+    assert_eq!(row.installed_by_user_id, Some(24654));
+    assert_eq!(
+        row.installed_by_email.as_deref(),
+        Some("merchant@mybigcommerce.com")
+    );
+
+    assert_eq!(
+        app.get_store_users("STORE_HASH").await,
+        vec![(24654, "merchant@mybigcommerce.com".to_owned())]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn install_request_registers_missing_webhooks() {
+    let app = spawn_app().await;
+    let client = create_test_server_client_no_redirect();
+
+    get_oauth2_token_mock(&app.bc_secret, &app.bc_redirect_uri)
+        .mount(&app.bigcommerce_server)
+        .await;
+    get_webhooks_mock(
+        "STORE_HASH",
+        "ACCESS_TOKEN",
+        json!([
+            {"id": 1, "scope": "store/app/uninstalled", "destination": app.bc_webhook_url, "is_active": true},
+            {"id": 2, "scope": "store/information/updated", "destination": app.bc_webhook_url, "is_active": false},
+            {"id": 3, "scope": "store/channel/*", "destination": "https://other.app/webhooks", "is_active": true}
+        ]),
+    )
+    .mount(&app.bigcommerce_server)
+    .await;
+    update_webhook_mock(
+        "STORE_HASH",
+        "ACCESS_TOKEN",
+        2,
+        json!({"scope": "store/information/updated", "is_active": true}),
+    )
+    .expect(1)
+    .mount(&app.bigcommerce_server)
+    .await;
+    create_webhook_mock(
+        "STORE_HASH",
+        "ACCESS_TOKEN",
+        json!({"scope": "store/channel/*", "destination": app.bc_webhook_url}),
+    )
+    .expect(1)
+    .mount(&app.bigcommerce_server)
+    .await;
+
+    let response = client
+        .get(app.test_server_url("/bigcommerce/install"))
+        .query(&[
+            ("context", "stores/STORE_HASH"),
+            ("scope", "test-scope"),
+            ("code", "test-code"),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_redirection());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn webhook_request_fails_without_valid_token() {
+    let app = spawn_app().await;
+    app.insert_test_store().await;
+    let payload = json!({
+        "scope": "store/app/uninstalled",
+        "store_id": "1025646",
+        "producer": "stores/test-store"
+    });
+
+    let response = app
+        .send_bc_webhook(&payload, &app.generate_bc_webhook_token("other-store"))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/bigcommerce/webhooks"))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute the request");
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .send_bc_webhook(
+            &json!({"scope": "store/app/uninstalled"}),
+            &app.generate_bc_webhook_token("test-store"),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let row = sqlx::query!("SELECT uninstalled FROM stores WHERE store_hash = 'test-store'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(!row.uninstalled);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn webhook_request_marks_store_as_uninstalled() {
+    let app = spawn_app().await;
+    app.insert_test_store().await;
+
+    let response = app
+        .send_bc_webhook(
+            &json!({
+                "scope": "store/app/uninstalled",
+                "store_id": "1025646",
+                "data": {"type": "store", "id": 1_025_646},
+                "created_at": 1_561_482_670,
+                "producer": "stores/test-store"
+            }),
+            &app.generate_bc_webhook_token("test-store"),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let row = sqlx::query!("SELECT uninstalled FROM stores WHERE store_hash = 'test-store'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(row.uninstalled);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn webhook_request_refreshes_store_information() {
+    let app = spawn_app().await;
+    app.insert_test_store().await;
+
+    get_store_information_mock()
+        .expect(2)
+        .mount(&app.bigcommerce_server)
+        .await;
+
+    for scope in ["store/information/updated", "store/channel/updated"] {
+        let response = app
+            .send_bc_webhook(
+                &json!({
+                    "scope": scope,
+                    "store_id": "1025646",
+                    "producer": "stores/test-store"
+                }),
+                &app.generate_bc_webhook_token("test-store"),
+            )
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let row =
+        sqlx::query!("SELECT name, secure_url, status FROM stores WHERE store_hash = 'test-store'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(row.name.as_deref(), Some("Test Store"));
+    assert_eq!(
+        row.secure_url.as_deref(),
+        Some("https://test-store-t85.mybigcommerce.com")
+    );
+    assert_eq!(row.status.as_deref(), Some("live"));
 }
 
 #[tokio::test(flavor = "multi_thread")]
@@ -127,7 +313,7 @@ async fn load_request_fails_with_bad_token() {
     let client = create_test_server_client_no_redirect();
 
     let response = client
-        .get(&app.test_server_url("/bigcommerce/load"))
+        .get(app.test_server_url("/bigcommerce/load"))
         .query(&[("signed_payload_jwt", "bad-token")])
         .send()
         .await
@@ -141,7 +327,7 @@ async fn load_request_fails_with_bad_token() {
     };
 
     let response = client
-        .get(&app.test_server_url("/bigcommerce/load"))
+        .get(app.test_server_url("/bigcommerce/load"))
         .query(&[(
             "signed_payload_jwt",
             app.generate_bc_jwt_token_with_params("bad-hash", &user, &user),
@@ -159,7 +345,7 @@ async fn load_request_succeeds() {
     let client = create_test_server_client_no_redirect();
 
     let response = client
-        .get(&app.test_server_url("/bigcommerce/load"))
+        .get(app.test_server_url("/bigcommerce/load"))
         .query(&[("signed_payload_jwt", &app.generate_bc_jwt_token())])
         .send()
         .await
@@ -178,6 +364,116 @@ async fn load_request_succeeds() {
     );
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn load_request_records_store_user() {
+    let app = spawn_app().await;
+    let client = create_test_server_client_no_redirect();
+    app.insert_test_store().await;
+
+    let owner = User {
+        id: 1,
+        email: "owner@test.com".to_owned(),
+    };
+    let user = User {
+        id: 2,
+        email: "staff@test.com".to_owned(),
+    };
+
+    for user in [&owner, &user, &user] {
+        let response = client
+            .get(app.test_server_url("/bigcommerce/load"))
+            .query(&[(
+                "signed_payload_jwt",
+                app.generate_bc_jwt_token_with_params("stores/test-store", &owner, user),
+            )])
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        assert!(response.status().is_redirection());
+    }
+
+    assert_eq!(
+        app.get_store_users("test-store").await,
+        vec![
+            (1, "owner@test.com".to_owned()),
+            (2, "staff@test.com".to_owned())
+        ]
+    );
+
+    let response = client
+        .get(app.test_server_url("/bigcommerce/load"))
+        .query(&[(
+            "signed_payload_jwt",
+            app.generate_bc_jwt_token_with_params("stores/test-store", &owner, &user),
+        )])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+    let location = reqwest::Url::parse(response.headers()["location"].to_str().unwrap()).unwrap();
+    let (_, token) = location
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap();
+
+    let claims = decode_token(&token, &app.jwt_secret).unwrap();
+    assert_eq!(claims.sub, "test-store");
+    assert_eq!(claims.user_id, Some(2));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn remove_user_request_succeeds() {
+    let app = spawn_app().await;
+    let client = create_test_server_client_no_redirect();
+    app.insert_test_store().await;
+
+    let owner = User {
+        id: 1,
+        email: "owner@test.com".to_owned(),
+    };
+    let user = User {
+        id: 2,
+        email: "staff@test.com".to_owned(),
+    };
+
+    for user in [&owner, &user] {
+        client
+            .get(app.test_server_url("/bigcommerce/load"))
+            .query(&[(
+                "signed_payload_jwt",
+                app.generate_bc_jwt_token_with_params("stores/test-store", &owner, user),
+            )])
+            .send()
+            .await
+            .expect("Failed to execute the request");
+    }
+
+    let response = client
+        .get(app.test_server_url("/bigcommerce/remove_user"))
+        .query(&[(
+            "signed_payload_jwt",
+            app.generate_bc_jwt_token_with_params("stores/test-store", &owner, &user),
+        )])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        app.get_store_users("test-store").await,
+        vec![(1, "owner@test.com".to_owned())]
+    );
+
+    let response = client
+        .get(app.test_server_url("/bigcommerce/remove_user"))
+        .query(&[("signed_payload_jwt", "bad-token")])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_client_error());
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn uninstall_request_succeeds() {
     let app = spawn_app().await;
@@ -187,12 +483,20 @@ async fn uninstall_request_succeeds() {
         "test-store".to_owned(),
         Secret::from("test-token".to_owned()),
     );
-    write_store_credentials(&store, &app.db_pool)
-        .await
-        .expect("Failed to initialize store");
+    write_store_credentials(
+        &store,
+        "store_v2_content",
+        &User {
+            id: 1,
+            email: "owner@test.com".to_owned(),
+        },
+        &app.db_pool,
+    )
+    .await
+    .expect("Failed to initialize store");
 
     let response = client
-        .get(&app.test_server_url("/bigcommerce/uninstall"))
+        .get(app.test_server_url("/bigcommerce/uninstall"))
         .query(&[("signed_payload_jwt", &app.generate_bc_jwt_token())])
         .send()
         .await
@@ -221,9 +525,17 @@ async fn uninstall_request_fails_with_non_owner() {
         "test-store".to_owned(),
         Secret::from("test-token".to_owned()),
     );
-    write_store_credentials(&store, &app.db_pool)
-        .await
-        .expect("Failed to initialize store");
+    write_store_credentials(
+        &store,
+        "store_v2_content",
+        &User {
+            id: 1,
+            email: "owner@test.com".to_owned(),
+        },
+        &app.db_pool,
+    )
+    .await
+    .expect("Failed to initialize store");
 
     let owner = User {
         id: 1,
@@ -235,7 +547,7 @@ async fn uninstall_request_fails_with_non_owner() {
     };
 
     let response = client
-        .get(&app.test_server_url("/bigcommerce/uninstall"))
+        .get(app.test_server_url("/bigcommerce/uninstall"))
         .query(&[(
             "signed_payload_jwt",
             &app.generate_bc_jwt_token_with_params("test-store", &owner, &user),
@@ -1,23 +1,131 @@
+use base64::Engine;
+use hmac::{Hmac, Mac};
 use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use once_cell::sync::Lazy;
 use reqwest::Client;
 use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
 use swu_app::{
     authentication::create_jwt,
     bigcommerce::auth::User,
     configuration::{Configuration, Database},
     data::WidgetConfiguration,
+    liq_pay::{Currency, HttpAPI as LiqPayHttpAPI},
     startup::{get_connection_pool, Application},
+    stripe::HttpAPI as StripeHttpAPI,
     telemetry::init_tracing,
 };
 use time::{Duration, OffsetDateTime};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
 use uuid::Uuid;
 use wiremock::MockServer;
 
-pub fn init_test_tracing() {
+// the global subscriber can only be set once per process when tests share a binary
+static TRACING: Lazy<()> = Lazy::new(|| {
     let default_filter_level = "trace".into();
     let subscriber_name = "test".into();
     init_tracing(subscriber_name, default_filter_level);
+});
+
+pub fn init_test_tracing() {
+    Lazy::force(&TRACING);
+}
+
+/// Local SMTP server that keeps every message it receives, like mailpit does for development
+#[derive(Clone)]
+pub struct SmtpSink {
+    pub port: u16,
+    messages: Arc<Mutex<Vec<String>>>,
+    reject: Arc<AtomicBool>,
+}
+
+impl SmtpSink {
+    async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind SMTP sink");
+        let sink = Self {
+            port: listener.local_addr().unwrap().port(),
+            messages: Arc::default(),
+            reject: Arc::default(),
+        };
+
+        let server = sink.clone();
+        drop(tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                drop(tokio::spawn(server.clone().handle(stream)));
+            }
+        }));
+
+        sink
+    }
+
+    /// Makes the sink refuse every message from now on
+    pub fn reject_messages(&self) {
+        self.reject.store(true, Ordering::SeqCst);
+    }
+
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.lock().unwrap().clone()
+    }
+
+    /// Waits for the messages that are sent in the background after a request
+    pub async fn wait_for_messages(&self, count: usize) -> Vec<String> {
+        for _ in 0..50 {
+            if self.messages().len() >= count {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+
+        self.messages()
+    }
+
+    async fn handle(self, stream: TcpStream) {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let mut data: Option<String> = None;
+
+        let _ = writer.write_all(b"220 localhost ESMTP\r\n").await;
+        while let Ok(Some(line)) = lines.next_line().await {
+            let reply: &[u8] = if let Some(message) = data.as_mut() {
+                if line != "." {
+                    message.push_str(&line);
+                    message.push('\n');
+                    continue;
+                }
+                self.messages.lock().unwrap().extend(data.take());
+                b"250 OK\r\n"
+            } else {
+                match line.get(..4).map(str::to_uppercase).as_deref() {
+                    Some("MAIL") if self.reject.load(Ordering::SeqCst) => {
+                        b"554 Transaction failed\r\n"
+                    }
+                    Some("DATA") => {
+                        data = Some(String::new());
+                        b"354 End data with <CR><LF>.<CR><LF>\r\n"
+                    }
+                    Some("QUIT") => {
+                        let _ = writer.write_all(b"221 Bye\r\n").await;
+                        return;
+                    }
+                    _ => b"250 OK\r\n",
+                }
+            };
+
+            if writer.write_all(reply).await.is_err() {
+                return;
+            }
+        }
+    }
 }
 
 pub struct TestApp {
@@ -26,19 +134,45 @@ pub struct TestApp {
     pub db_pool: PgPool,
 
     pub bigcommerce_server: MockServer,
+    pub liq_pay_server: MockServer,
+    pub stripe_server: MockServer,
+    pub smtp_sink: SmtpSink,
     pub jwt_secret: Secret<String>,
+    pub admin_api_key: Secret<String>,
     pub base_url: String,
     pub bc_secret: Secret<String>,
     pub bc_client_id: String,
     pub bc_redirect_uri: String,
+    pub bc_webhook_url: String,
+    pub liq_pay_client: LiqPayHttpAPI,
+    pub stripe_client: StripeHttpAPI,
 
     pub test_client: Client,
 }
 
+/// Decodes the checkout request that a pay response redirects to
+pub fn decode_liq_pay_checkout(response: &reqwest::Response) -> serde_json::Value {
+    let location = reqwest::Url::parse(response.headers()["location"].to_str().unwrap()).unwrap();
+    let (_, data) = location
+        .query_pairs()
+        .find(|(key, _)| key == "data")
+        .expect("Checkout link should have data");
+
+    serde_json::from_slice(
+        &base64::engine::general_purpose::STANDARD
+            .decode(data.as_bytes())
+            .unwrap(),
+    )
+    .unwrap()
+}
+
 pub async fn spawn_app() -> TestApp {
     init_test_tracing();
 
     let bigcommerce_server = MockServer::start().await;
+    let liq_pay_server = MockServer::start().await;
+    let stripe_server = MockServer::start().await;
+    let smtp_sink = SmtpSink::start().await;
 
     // configuration for this test instance
     let configuration = {
@@ -50,6 +184,17 @@ pub async fn spawn_app() -> TestApp {
         // we can reuse the mock server for both for now
         c.bigcommerce.api_base_url = bigcommerce_server.uri();
         c.bigcommerce.login_base_url = bigcommerce_server.uri();
+        c.liq_pay.api_base_url = liq_pay_server.uri();
+        c.stripe.api_base_url = stripe_server.uri();
+        c.stripe.webhook_secret = Secret::new("stripe-webhook-secret".to_owned());
+        c.stripe.currencies = vec![Currency::EUR];
+        c.checkout.currencies = vec![Currency::USD, Currency::EUR, Currency::UAH, Currency::PLN];
+        c.pay_limits.trusted_proxy_hops = 1;
+        c.application.admin_api_key = Secret::new("admin-api-key".to_owned());
+        c.smtp.host = "127.0.0.1".to_owned();
+        c.smtp.port = smtp_sink.port;
+        c.smtp.username = String::new();
+        c.smtp.tls = false;
         c
     };
 
@@ -59,17 +204,38 @@ pub async fn spawn_app() -> TestApp {
         .await
         .expect("Failed to build application.");
     let application_port = application.port();
-    let _ = tokio::spawn(application.run_until_stopped());
+    drop(tokio::spawn(application.run_until_stopped()));
 
     TestApp {
         address: format!("http://127.0.0.1:{}", application_port),
         port: application_port,
         bigcommerce_server,
+        liq_pay_server,
+        stripe_server,
+        smtp_sink,
         db_pool: get_connection_pool(&configuration.database),
         jwt_secret: configuration.application.jwt_secret,
+        admin_api_key: configuration.application.admin_api_key,
         bc_secret: configuration.bigcommerce.client_secret,
         bc_client_id: configuration.bigcommerce.client_id,
         bc_redirect_uri: configuration.bigcommerce.install_redirect_uri,
+        bc_webhook_url: configuration.bigcommerce.webhook_url,
+        liq_pay_client: LiqPayHttpAPI::new(
+            configuration.liq_pay.public_key,
+            configuration.liq_pay.private_key,
+            configuration.liq_pay.api_base_url,
+            configuration.liq_pay.result_url,
+            configuration.liq_pay.server_url,
+            configuration.liq_pay.sandbox,
+            std::time::Duration::from_millis(configuration.liq_pay.timeout.into()),
+        ),
+        stripe_client: StripeHttpAPI::new(
+            configuration.stripe.secret_key,
+            configuration.stripe.webhook_secret,
+            configuration.stripe.api_base_url,
+            configuration.stripe.result_url,
+            std::time::Duration::from_millis(configuration.stripe.timeout.into()),
+        ),
         base_url: configuration.application.base_url,
         test_client: reqwest::Client::new(),
     }
@@ -138,8 +304,35 @@ impl TestApp {
         encode(&header, &claims, &key).unwrap()
     }
 
+    /// Token BigCommerce sends back with the webhooks registered for `store_hash`
+    pub fn generate_bc_webhook_token(&self, store_hash: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.bc_secret.expose_secret().as_bytes())
+            .expect("HMAC can take key of any size");
+        mac.update(store_hash.as_bytes());
+
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    pub async fn send_bc_webhook(
+        &self,
+        payload: &serde_json::Value,
+        token: &str,
+    ) -> reqwest::Response {
+        self.test_client
+            .post(self.test_server_url("/bigcommerce/webhooks"))
+            .header("X-SWU-Webhook-Token", token)
+            .json(payload)
+            .send()
+            .await
+            .expect("Failed to execute the request")
+    }
+
     pub fn generate_local_jwt_token(&self) -> String {
-        create_jwt("test-store", &self.jwt_secret).unwrap()
+        create_jwt("test-store", 1, &self.jwt_secret).unwrap()
     }
 
     pub async fn insert_test_store(&self) {
@@ -201,6 +394,140 @@ impl TestApp {
             .map(|row| (row.event_type, row.metadata))
     }
 
+    pub async fn get_donations(
+        &self,
+    ) -> impl Iterator<Item = (String, String, f64, String, String)> {
+        sqlx::query!(
+            "SELECT order_id, status, amount, currency, provider FROM donations ORDER BY order_id;"
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| {
+            (
+                row.order_id,
+                row.status,
+                row.amount,
+                row.currency,
+                row.provider,
+            )
+        })
+    }
+
+    pub async fn get_subscriptions(
+        &self,
+    ) -> impl Iterator<Item = (String, String, Option<OffsetDateTime>)> {
+        sqlx::query!(
+            "SELECT order_id, status, next_charge_at FROM subscriptions ORDER BY order_id;"
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.order_id, row.status, row.next_charge_at))
+    }
+
+    pub async fn get_checkouts(&self) -> impl Iterator<Item = (String, String, String)> {
+        sqlx::query!("SELECT order_id, provider, campaign FROM checkouts ORDER BY created_at;")
+            .fetch_all(&self.db_pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| (row.order_id, row.provider, row.campaign))
+    }
+
+    pub async fn get_store_users(&self, store_hash: &str) -> Vec<(i32, String)> {
+        sqlx::query!(
+            "SELECT user_id, email FROM store_users WHERE store_hash = $1 ORDER BY user_id;",
+            store_hash
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.user_id, row.email))
+        .collect()
+    }
+
+    pub async fn get_receipts(&self) -> impl Iterator<Item = (String, String, bool, i32)> {
+        sqlx::query!("SELECT order_id, email, sent_at, attempts FROM receipt_outbox ORDER BY id;")
+            .fetch_all(&self.db_pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| (row.order_id, row.email, row.sent_at.is_some(), row.attempts))
+    }
+
+    pub async fn insert_checkout(&self, order_id: &str, created_at: OffsetDateTime) {
+        sqlx::query!(
+            r#"
+            INSERT INTO checkouts (order_id, provider, campaign, created_at)
+            VALUES ($1, 'liqpay', 'general', $2);
+            "#,
+            order_id,
+            created_at,
+        )
+        .execute(&self.db_pool)
+        .await
+        .unwrap();
+    }
+
+    pub async fn insert_campaign(
+        &self,
+        slug: &str,
+        description: &str,
+        currencies: &[&str],
+        ends_at: Option<OffsetDateTime>,
+    ) {
+        sqlx::query!(
+            r#"
+            INSERT INTO campaigns (slug, description, currencies, default_currency, preset_amounts, ends_at)
+            VALUES ($1, $2, $3, $4, $5, $6);
+            "#,
+            slug,
+            description,
+            &currencies
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            currencies[0],
+            &[10.0, 50.0],
+            ends_at,
+        )
+        .execute(&self.db_pool)
+        .await
+        .unwrap();
+    }
+
+    pub fn generate_liq_pay_callback(&self, payload: &serde_json::Value) -> [(String, String); 2] {
+        let data = base64::engine::general_purpose::STANDARD.encode(payload.to_string());
+        let signature = self.liq_pay_client.signature(&data);
+
+        [
+            ("data".to_owned(), data),
+            ("signature".to_owned(), signature),
+        ]
+    }
+
+    pub async fn send_liq_pay_callback(&self, payload: &serde_json::Value) -> reqwest::Response {
+        self.test_client
+            .post(self.test_server_url("/pay/callback"))
+            .form(&self.generate_liq_pay_callback(payload))
+            .send()
+            .await
+            .expect("Failed to execute the request")
+    }
+
+    pub fn generate_stripe_signature(&self, body: &str) -> String {
+        let timestamp = OffsetDateTime::now_utc().unix_timestamp();
+
+        format!(
+            "t={timestamp},v1={}",
+            self.stripe_client.signature(timestamp, body.as_bytes())
+        )
+    }
+
     pub async fn get_charity_visited_events(
         &self,
         store_hash: &str,
@@ -1,9 +1,11 @@
+pub mod admin;
 pub mod bigcommerce;
 pub mod widget;
 
 pub mod helpers;
 pub mod mocks;
 pub mod pay;
+pub mod reconciliation;
 
 #[tokio::test(flavor = "multi_thread")]
 async fn health_check() {
@@ -11,7 +13,7 @@ async fn health_check() {
 
     let response = app
         .test_client
-        .get(&app.test_server_url("/health_check"))
+        .get(app.test_server_url("/health_check"))
         .send()
         .await
         .expect("Failed to execute the request");
@@ -1,7 +1,7 @@
 use secrecy::{ExposeSecret, Secret};
 use serde_json::json;
 use wiremock::{
-    matchers::{body_partial_json, header, method, path},
+    matchers::{body_partial_json, body_string_contains, header, method, path, query_param},
     Mock, ResponseTemplate,
 };
 
@@ -20,6 +20,27 @@ pub fn get_scripts_mock(existing: bool) -> Mock {
         .named("BigCommerce get scripts request")
 }
 
+/// One page of a script listing that spans `total_pages`
+pub fn get_scripts_page_mock(page: u32, total_pages: u32, scripts: serde_json::Value) -> Mock {
+    Mock::given(method("GET"))
+        .and(path("/stores/test-store/v3/content/scripts"))
+        .and(header("X-Auth-Token", "test-token"))
+        .and(query_param("page", page.to_string()))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": scripts,
+            "meta": {
+                "pagination": {
+                    "total": 251,
+                    "count": 250,
+                    "per_page": 250,
+                    "current_page": page,
+                    "total_pages": total_pages
+                }
+            }
+        })))
+        .named("BigCommerce get scripts page request")
+}
+
 pub fn create_script_mock() -> Mock {
     let create_scripts_response: serde_json::Value =
         serde_json::from_str(include_str!("create_script.json")).expect("Failed to parse file");
@@ -57,6 +78,20 @@ pub fn delete_script_mock() -> Mock {
         .named("BigCommerce delete script request")
 }
 
+/// Error response to `http_method` on the scripts API, mounted ahead of the successful mocks
+pub fn scripts_error_mock(http_method: &str, status: u16) -> Mock {
+    Mock::given(method(http_method))
+        .and(path("/stores/test-store/v3/content/scripts"))
+        .and(header("X-Auth-Token", "test-token"))
+        .respond_with(
+            ResponseTemplate::new(status)
+                .insert_header("X-Rate-Limit-Requests-Left", "0")
+                .insert_header("X-Rate-Limit-Time-Reset-Ms", "50"),
+        )
+        .with_priority(1)
+        .named("BigCommerce scripts error response")
+}
+
 pub fn get_store_information_mock() -> Mock {
     let store_information_response: serde_json::Value =
         serde_json::from_str(include_str!("get_store.json")).expect("Failed to parse file");
@@ -69,13 +104,52 @@ pub fn get_store_information_mock() -> Mock {
         .named("BigCommerce get store information")
 }
 
+pub fn get_webhooks_mock(
+    store_hash: &str,
+    access_token: &str,
+    webhooks: serde_json::Value,
+) -> Mock {
+    Mock::given(method("GET"))
+        .and(path(format!("/stores/{store_hash}/v3/hooks")))
+        .and(header("X-Auth-Token", access_token))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": webhooks })))
+        .named("BigCommerce get webhooks request")
+}
+
+pub fn create_webhook_mock(
+    store_hash: &str,
+    access_token: &str,
+    webhook: serde_json::Value,
+) -> Mock {
+    Mock::given(method("POST"))
+        .and(path(format!("/stores/{store_hash}/v3/hooks")))
+        .and(header("X-Auth-Token", access_token))
+        .and(body_partial_json(webhook))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": {} })))
+        .named("BigCommerce create webhook request")
+}
+
+pub fn update_webhook_mock(
+    store_hash: &str,
+    access_token: &str,
+    id: i64,
+    webhook: serde_json::Value,
+) -> Mock {
+    Mock::given(method("PUT"))
+        .and(path(format!("/stores/{store_hash}/v3/hooks/{id}")))
+        .and(header("X-Auth-Token", access_token))
+        .and(body_partial_json(webhook))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": {} })))
+        .named("BigCommerce update webhook request")
+}
+
 pub fn get_oauth2_token_mock(client_secret: &Secret<String>, redirect_uri: &str) -> Mock {
     let oauth2_token_response: serde_json::Value =
         serde_json::from_str(include_str!("get_oauth2_token.json")).expect("Failed to parse file");
 
     Mock::given(method("POST"))
         .and(path("/oauth2/token"))
-        .and(body_partial_json(&json!({
+        .and(body_partial_json(json!({
             "client_secret": &client_secret.expose_secret(),
             "redirect_uri": &redirect_uri,
             "grant_type": "authorization_code",
@@ -83,3 +157,35 @@ pub fn get_oauth2_token_mock(client_secret: &Secret<String>, redirect_uri: &str)
         .respond_with(ResponseTemplate::new(200).set_body_json(&oauth2_token_response))
         .named("BigCommerce oauth token request")
 }
+
+pub fn liq_pay_unsubscribe_mock() -> Mock {
+    Mock::given(method("POST"))
+        .and(path("/api/request"))
+        .and(body_string_contains("signature="))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "action": "unsubscribe",
+            "payment_id": 1234,
+            "status": "unsubscribed",
+            "result": "ok"
+        })))
+        .named("LiqPay unsubscribe request")
+}
+
+pub fn stripe_create_checkout_session_mock() -> Mock {
+    Mock::given(method("POST"))
+        .and(path("/v1/checkout/sessions"))
+        .and(body_string_contains("client_reference_id="))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": "cs_test_1234",
+            "url": "https://checkout.stripe.com/c/pay/cs_test_1234"
+        })))
+        .named("Stripe create checkout session request")
+}
+
+pub fn liq_pay_status_mock(payment: serde_json::Value) -> Mock {
+    Mock::given(method("POST"))
+        .and(path("/api/request"))
+        .and(body_string_contains("signature="))
+        .respond_with(ResponseTemplate::new(200).set_body_json(payment))
+        .named("LiqPay status request")
+}
@@ -1,11 +1,15 @@
 use crate::helpers;
 use crate::helpers::create_test_server_client_no_redirect;
+use crate::mocks::{liq_pay_unsubscribe_mock, stripe_create_checkout_session_mock};
+use serde_json::json;
+use swu_app::authentication::create_unsubscribe_token;
+use time::{Duration, OffsetDateTime};
 
 #[tokio::test(flavor = "multi_thread")]
 async fn pay_check() {
     let app = helpers::spawn_app().await;
     let response = create_test_server_client_no_redirect()
-        .get(&app.test_server_url("/pay?amount=123&action=subscribe&currency=USD&language=en"))
+        .get(app.test_server_url("/pay?amount=123&action=subscribe&currency=USD&language=en"))
         .send()
         .await
         .expect("Failed to execute the request");
@@ -15,3 +19,1296 @@ async fn pay_check() {
         "Response should be a redirection"
     );
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_checkout_returns_donors_to_result_url_in_sandbox() {
+    let app = helpers::spawn_app().await;
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url("/pay?amount=123&action=pay&currency=USD&language=en"))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    let data = helpers::decode_liq_pay_checkout(&response);
+    assert_eq!(data["result_url"], "https://standwithukraineapp.com");
+    assert_eq!(
+        data["server_url"],
+        "https://standwithukraineapp.com/pay/callback"
+    );
+    assert_eq!(
+        data["sandbox"], 1,
+        "Checkouts outside of production should use the sandbox"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_subscription_with_periodicity_and_start_date() {
+    let app = helpers::spawn_app().await;
+    let start_date = (OffsetDateTime::now_utc() + Duration::days(30)).date();
+
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url("/pay"))
+        .query(&[
+            ("amount", "123"),
+            ("action", "subscribe"),
+            ("currency", "USD"),
+            ("language", "en"),
+            ("periodicity", "year"),
+            ("start_date", &start_date.to_string()),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(
+        response.status().is_redirection(),
+        "Response should be a redirection"
+    );
+
+    let subscriptions = app.get_subscriptions().await.collect::<Vec<_>>();
+    assert_eq!(subscriptions.len(), 1);
+    assert_eq!(subscriptions[0].1, "prepared");
+    assert_eq!(
+        subscriptions[0].2,
+        Some(start_date.midnight().assume_utc()),
+        "First charge should be scheduled on the start date"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_fails_with_invalid_subscription_options() {
+    let app = helpers::spawn_app().await;
+    let yesterday = (OffsetDateTime::now_utc() - Duration::days(1)).date();
+
+    for query in [
+        [
+            ("action", "subscribe"),
+            ("start_date", yesterday.to_string().as_str()),
+        ],
+        [("action", "pay"), ("periodicity", "month")],
+        [("action", "subscribe"), ("periodicity", "quarter")],
+    ] {
+        let response = create_test_server_client_no_redirect()
+            .get(app.test_server_url("/pay?amount=123&currency=USD&language=en"))
+            .query(&query)
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        assert!(
+            response.status().is_client_error(),
+            "Response should be a client error"
+        );
+    }
+
+    assert_eq!(app.get_subscriptions().await.count(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_fails_with_invalid_amount() {
+    let app = helpers::spawn_app().await;
+
+    for (amount, currency, message) in [
+        ("-5", "USD", "Amount must be a positive number."),
+        ("NaN", "USD", "Amount must be a positive number."),
+        ("5", "UAH", "Amount must be at least 10 UAH."),
+        ("1000000", "EUR", "Amount must be at most 10000 EUR."),
+        (
+            "10.005",
+            "USD",
+            "Amount cannot have more than 2 decimal places.",
+        ),
+    ] {
+        let response = create_test_server_client_no_redirect()
+            .get(app.test_server_url("/pay?action=pay&language=en"))
+            .query(&[("amount", amount), ("currency", currency)])
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        assert_eq!(response.status().as_u16(), 422);
+        assert_eq!(
+            response.json::<serde_json::Value>().await.unwrap(),
+            json!({
+                "error": "invalid_input",
+                "field": "amount",
+                "message": message
+            })
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_fails_with_malformed_query() {
+    let app = helpers::spawn_app().await;
+
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url("/pay?amount=123&action=subscribe&currency=GBP&language=en"))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["error"], "invalid_query");
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .contains("unknown variant"),
+        "Response should explain the invalid currency"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_fails_above_configured_amount_cap() {
+    let app = helpers::spawn_app().await;
+
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url("/pay?amount=15000&action=pay&currency=USD&language=en"))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 422);
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap()["message"],
+        "Amount must be at most 10000 USD."
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_limits_checkouts_per_client() {
+    let app = helpers::spawn_app().await;
+    let client = create_test_server_client_no_redirect();
+    let pay = |forwarded_for: &'static str| {
+        client
+            .get(app.test_server_url("/pay?amount=100&action=pay&currency=USD&language=en"))
+            .header("X-Forwarded-For", forwarded_for)
+            .send()
+    };
+
+    for _ in 0..10 {
+        let response = pay("198.51.100.1, 203.0.113.7").await.unwrap();
+        assert!(response.status().is_redirection());
+    }
+
+    let response = pay("198.51.100.2, 203.0.113.7").await.unwrap();
+    assert_eq!(
+        response.status().as_u16(),
+        429,
+        "Client should be identified by the address added by the load balancer"
+    );
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap()["error"],
+        "too_many_checkouts"
+    );
+
+    let response = pay("203.0.113.8").await.unwrap();
+    assert!(response.status().is_redirection());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_limits_distinct_amounts_per_client() {
+    let app = helpers::spawn_app().await;
+
+    for (amount, status) in [
+        ("10", 303),
+        ("11", 303),
+        ("12", 303),
+        ("13", 303),
+        ("14", 303),
+        ("15", 429),
+        ("10", 303),
+    ] {
+        let response = create_test_server_client_no_redirect()
+            .get(app.test_server_url("/pay?action=pay&currency=USD&language=en"))
+            .query(&[("amount", amount)])
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        assert_eq!(response.status().as_u16(), status, "amount {amount}");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_callback_records_donation() {
+    let app = helpers::spawn_app().await;
+    let payload = json!({
+        "order_id": "order-1",
+        "payment_id": 1234,
+        "action": "pay",
+        "status": "success",
+        "amount": 100.0,
+        "currency": "UAH"
+    });
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/pay/callback"))
+        .form(&app.generate_liq_pay_callback(&payload))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_success());
+    assert_eq!(
+        app.get_donations().await.collect::<Vec<_>>(),
+        vec![(
+            "order-1".to_owned(),
+            "success".to_owned(),
+            100.0,
+            "UAH".to_owned(),
+            "liqpay".to_owned()
+        )]
+    );
+
+    let payload = json!({
+        "order_id": "order-1",
+        "payment_id": 1234,
+        "action": "pay",
+        "status": "reversed",
+        "amount": 100.0,
+        "currency": "UAH"
+    });
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/pay/callback"))
+        .form(&app.generate_liq_pay_callback(&payload))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_success());
+    assert_eq!(
+        app.get_donations().await.collect::<Vec<_>>(),
+        vec![(
+            "order-1".to_owned(),
+            "reversed".to_owned(),
+            100.0,
+            "UAH".to_owned(),
+            "liqpay".to_owned()
+        )]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_callback_keeps_reversal_over_late_callbacks() {
+    let app = helpers::spawn_app().await;
+
+    for status in ["success", "reversed", "success"] {
+        let response = app
+            .send_liq_pay_callback(&json!({
+                "order_id": "order-1",
+                "payment_id": 1234,
+                "action": "pay",
+                "status": status,
+                "amount": 100.0,
+                "currency": "UAH"
+            }))
+            .await;
+
+        assert!(response.status().is_success());
+    }
+
+    assert_eq!(
+        app.get_donations().await.next().unwrap().1,
+        "reversed",
+        "A late callback should not undo a reversal"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_callback_keeps_success_over_late_failure() {
+    let app = helpers::spawn_app().await;
+
+    for status in ["success", "failure", "error"] {
+        let response = app
+            .send_liq_pay_callback(&json!({
+                "order_id": "order-1",
+                "payment_id": 1234,
+                "action": "pay",
+                "status": status,
+                "amount": 100.0,
+                "currency": "UAH"
+            }))
+            .await;
+
+        assert!(response.status().is_success());
+    }
+
+    assert_eq!(
+        app.get_donations().await.next().unwrap().1,
+        "success",
+        "A late failure should not undo a received payment"
+    );
+}
+
+/// Starts a checkout that asks for a receipt and returns its order id
+async fn checkout_with_receipt(app: &helpers::TestApp) -> String {
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url(
+            "/pay?amount=100&action=pay&currency=UAH&language=en&email=donor%40example.com",
+        ))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+    assert!(response.status().is_redirection());
+
+    helpers::decode_liq_pay_checkout(&response)["order_id"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_callback_sends_receipt() {
+    let app = helpers::spawn_app().await;
+    let order_id = checkout_with_receipt(&app).await;
+
+    for status in ["success", "success"] {
+        let response = app
+            .send_liq_pay_callback(&json!({
+                "order_id": order_id,
+                "payment_id": 1234,
+                "action": "pay",
+                "status": status,
+                "amount": 100.0,
+                "currency": "UAH"
+            }))
+            .await;
+
+        assert!(response.status().is_success());
+    }
+
+    let messages = app.smtp_sink.wait_for_messages(1).await;
+    assert_eq!(
+        messages.len(),
+        1,
+        "Repeated callbacks should send one receipt"
+    );
+    assert!(messages[0].contains("donor@example.com"));
+    assert!(messages[0].contains(&format!("Order id: {order_id}")));
+    assert!(messages[0].contains("Amount: 100.00 UAH"));
+
+    // the outbox is updated right after the message is accepted
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert_eq!(
+        app.get_receipts().await.collect::<Vec<_>>(),
+        vec![(order_id, "donor@example.com".to_owned(), true, 1)]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_callback_succeeds_when_receipt_cannot_be_sent() {
+    let app = helpers::spawn_app().await;
+    let order_id = checkout_with_receipt(&app).await;
+    app.smtp_sink.reject_messages();
+
+    let response = app
+        .send_liq_pay_callback(&json!({
+            "order_id": order_id,
+            "payment_id": 1234,
+            "action": "pay",
+            "status": "success",
+            "amount": 100.0,
+            "currency": "UAH"
+        }))
+        .await;
+
+    assert!(response.status().is_success());
+    assert_eq!(app.get_donations().await.count(), 1);
+
+    let mut receipts = vec![];
+    for _ in 0..50 {
+        receipts = app.get_receipts().await.collect();
+        if receipts.iter().any(|(_, _, _, attempts)| *attempts > 0) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(
+        receipts,
+        vec![(order_id, "donor@example.com".to_owned(), false, 1)],
+        "The receipt should stay in the outbox to be retried"
+    );
+    assert!(app.smtp_sink.messages().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_callback_sends_receipt_only_for_subscription_charges() {
+    let app = helpers::spawn_app().await;
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url(
+            "/pay?amount=100&action=subscribe&periodicity=month&currency=UAH&language=en&email=donor%40example.com",
+        ))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+    let order_id = helpers::decode_liq_pay_checkout(&response)["order_id"].clone();
+
+    for (payment_id, action, status) in [
+        (1234, "subscribe", "subscribed"),
+        (1235, "regular", "success"),
+    ] {
+        let response = app
+            .send_liq_pay_callback(&json!({
+                "order_id": order_id,
+                "payment_id": payment_id,
+                "action": action,
+                "status": status,
+                "amount": 100.0,
+                "currency": "UAH"
+            }))
+            .await;
+
+        assert!(response.status().is_success());
+    }
+
+    app.smtp_sink.wait_for_messages(1).await;
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert_eq!(
+        app.get_receipts().await.count(),
+        1,
+        "Only the charge should get a receipt"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_callback_skips_receipt_without_email() {
+    let app = helpers::spawn_app().await;
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url("/pay?amount=100&action=pay&currency=UAH&language=en&email="))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+    let order_id = helpers::decode_liq_pay_checkout(&response)["order_id"].clone();
+
+    let response = app
+        .send_liq_pay_callback(&json!({
+            "order_id": order_id,
+            "payment_id": 1234,
+            "action": "pay",
+            "status": "success",
+            "amount": 100.0,
+            "currency": "UAH"
+        }))
+        .await;
+
+    assert!(response.status().is_success());
+    assert_eq!(app.get_receipts().await.count(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_rejects_invalid_receipt_email() {
+    let app = helpers::spawn_app().await;
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url(
+            "/pay?amount=100&action=pay&currency=UAH&language=en&email=not-an-email",
+        ))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 422);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["field"], "email");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_callback_fails_with_invalid_signature() {
+    let app = helpers::spawn_app().await;
+    let [data, _] = app.generate_liq_pay_callback(&json!({
+        "order_id": "order-1",
+        "payment_id": 1234,
+        "action": "pay",
+        "status": "success",
+        "amount": 100.0,
+        "currency": "UAH"
+    }));
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/pay/callback"))
+        .form(&[data, ("signature".to_owned(), "invalid".to_owned())])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(app.get_donations().await.count(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_campaign_uses_campaign_description() {
+    let app = helpers::spawn_app().await;
+    app.insert_campaign("winter", "Warm clothes for the winter", &["UAH"], None)
+        .await;
+
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url("/pay/c/winter?amount=100&action=pay&currency=UAH&language=en"))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(
+        response.status().is_redirection(),
+        "Response should be a redirection"
+    );
+
+    let data = helpers::decode_liq_pay_checkout(&response);
+    assert_eq!(data["description"], "Warm clothes for the winter");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_campaign_fails_for_unknown_or_inactive_campaign() {
+    let app = helpers::spawn_app().await;
+    app.insert_campaign(
+        "summer",
+        "Summer fundraiser",
+        &["UAH"],
+        Some(OffsetDateTime::now_utc() - Duration::days(1)),
+    )
+    .await;
+
+    for slug in ["unknown", "summer"] {
+        let response = create_test_server_client_no_redirect()
+            .get(app.test_server_url(&format!(
+                "/pay/c/{slug}?amount=100&action=pay&currency=UAH&language=en"
+            )))
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        assert_eq!(response.status().as_u16(), 404);
+        assert_eq!(
+            response.json::<serde_json::Value>().await.unwrap()["error"],
+            "campaign_not_found"
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_campaign_fails_with_currency_not_allowed() {
+    let app = helpers::spawn_app().await;
+    app.insert_campaign("winter", "Warm clothes for the winter", &["UAH"], None)
+        .await;
+
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url("/pay/c/winter?amount=100&action=pay&currency=USD&language=en"))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 422);
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap(),
+        json!({
+            "error": "invalid_input",
+            "field": "currency",
+            "message": "USD is not accepted by this campaign."
+        })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_without_query_renders_landing_page() {
+    let app = helpers::spawn_app().await;
+
+    let response = app
+        .test_client
+        .get(app.test_server_url("/pay"))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/html; charset=utf-8"
+    );
+
+    let page = response.text().await.unwrap();
+    assert!(page.contains("Stand with Ukraine"));
+    assert!(page.contains("Support BigCommerce colleagues defending Ukraine"));
+    assert!(page.contains(r#"<option value="USD" selected>USD</option>"#));
+    assert!(
+        !page.contains(r#"<option value="PLN">PLN</option>"#),
+        "Currencies that need Stripe should only be offered by a stored campaign"
+    );
+    assert!(page.contains(r#"name="action" value="subscribe""#));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_landing_page_offers_configured_currencies_of_stored_general_campaign() {
+    let app = helpers::spawn_app().await;
+    app.insert_campaign(
+        "general",
+        "Support BigCommerce colleagues defending Ukraine",
+        &["UAH", "PLN", "RON"],
+        None,
+    )
+    .await;
+
+    let page = app
+        .test_client
+        .get(app.test_server_url("/pay"))
+        .send()
+        .await
+        .expect("Failed to execute the request")
+        .text()
+        .await
+        .unwrap();
+
+    assert!(page.contains(r#"<option value="PLN">PLN</option>"#));
+    assert!(
+        !page.contains(r#"<option value="RON">RON</option>"#),
+        "Currencies that are not configured should not be offered"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_campaign_landing_page_is_localized() {
+    let app = helpers::spawn_app().await;
+    app.insert_campaign("winter", "Warm clothes for the winter", &["UAH"], None)
+        .await;
+
+    let page = app
+        .test_client
+        .get(app.test_server_url("/pay/c/winter?language=ua"))
+        .send()
+        .await
+        .expect("Failed to execute the request")
+        .text()
+        .await
+        .unwrap();
+
+    assert!(page.contains("Підтримай Україну"));
+    assert!(page.contains("Warm clothes for the winter"));
+    assert!(page.contains(r#"<option value="UAH" selected>UAH</option>"#));
+    assert!(!page.contains(r#"<option value="USD">USD</option>"#));
+    assert!(page.contains(">50</button>"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_landing_page_form_redirects_to_checkout() {
+    let app = helpers::spawn_app().await;
+    app.insert_campaign("winter", "Warm clothes for the winter", &["UAH"], None)
+        .await;
+
+    let response = create_test_server_client_no_redirect()
+        .post(app.test_server_url("/pay/c/winter"))
+        .form(&[
+            ("language", "ua"),
+            ("amount", "50"),
+            ("action", "pay"),
+            ("currency", "UAH"),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(
+        response.status().is_redirection(),
+        "Response should be a redirection"
+    );
+
+    let data = helpers::decode_liq_pay_checkout(&response);
+    assert_eq!(data["description"], "Warm clothes for the winter");
+    assert_eq!(data["language"], "ua");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_landing_page_form_shows_errors_next_to_fields() {
+    let app = helpers::spawn_app().await;
+    app.insert_campaign(
+        "winter",
+        "Warm clothes for the winter",
+        &["UAH", "USD"],
+        None,
+    )
+    .await;
+
+    let response = create_test_server_client_no_redirect()
+        .post(app.test_server_url("/pay/c/winter"))
+        .form(&[
+            ("language", "ua"),
+            ("amount", "10.555"),
+            ("action", "subscribe"),
+            ("currency", "USD"),
+            ("email", "donor@example.com"),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 422);
+    assert!(response
+        .headers()
+        .get("content-type")
+        .is_some_and(|value| value.to_str().unwrap().starts_with("text/html")));
+    let page = response.text().await.unwrap();
+    assert!(page.contains("Введіть додатну суму з не більше ніж двома знаками після коми."));
+    assert!(page.contains(r#"value="10.555""#));
+    assert!(page.contains(r#"value="subscribe" checked"#));
+    assert!(page.contains(r#"<option value="USD" selected>USD</option>"#));
+    assert!(page.contains(r#"value="donor@example.com""#));
+
+    let response = create_test_server_client_no_redirect()
+        .post(app.test_server_url("/pay"))
+        .form(&[("language", "en"), ("amount", "many"), ("action", "pay")])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 400);
+    let page = response.text().await.unwrap();
+    assert!(page.contains("Please check the form and try again."));
+    assert!(page.contains(r#"value="many""#));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_qr_code_renders_donation_link() {
+    let app = helpers::spawn_app().await;
+    app.insert_campaign("winter", "Warm clothes for the winter", &["UAH"], None)
+        .await;
+
+    for (path, content_type, signature) in [
+        (
+            "/pay/qr?amount=50&action=pay&currency=USD&language=en",
+            "image/svg+xml",
+            b"<?xml".as_slice(),
+        ),
+        (
+            "/pay/qr?amount=50&action=pay&currency=USD&language=en&format=png",
+            "image/png",
+            b"\x89PNG".as_slice(),
+        ),
+        ("/pay/qr", "image/svg+xml", b"<?xml".as_slice()),
+        (
+            "/pay/c/winter/qr?language=ua&format=png",
+            "image/png",
+            b"\x89PNG".as_slice(),
+        ),
+    ] {
+        let response = app
+            .test_client
+            .get(app.test_server_url(path))
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        assert_eq!(response.status().as_u16(), 200, "{path}");
+        assert_eq!(response.headers()["content-type"], content_type);
+        assert!(response.bytes().await.unwrap().starts_with(signature));
+    }
+
+    assert_eq!(
+        app.get_checkouts().await.count(),
+        0,
+        "QR codes should not create checkouts"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_qr_code_fails_for_invalid_donation_link() {
+    let app = helpers::spawn_app().await;
+
+    for (path, status) in [
+        ("/pay/qr?amount=0&action=pay&currency=USD&language=en", 422),
+        ("/pay/qr?amount=50&currency=USD", 400),
+        ("/pay/qr?format=gif", 400),
+        ("/pay/c/unknown/qr", 404),
+    ] {
+        let response = app
+            .test_client
+            .get(app.test_server_url(path))
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        assert_eq!(response.status().as_u16(), status, "{path}");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn embedded_checkout_returns_signed_liq_pay_data() {
+    let app = helpers::spawn_app().await;
+
+    let response = app
+        .test_client
+        .get(app.test_server_url("/pay/checkout?amount=123&action=pay&currency=USD&language=en"))
+        .header("Origin", "https://store.example.com")
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["access-control-allow-origin"], "*");
+
+    let checkout = response.json::<serde_json::Value>().await.unwrap();
+    let data = checkout["data"].as_str().unwrap();
+    assert_eq!(checkout["provider"], "liqpay");
+    assert_eq!(
+        checkout["signature"],
+        app.liq_pay_client.signature(data),
+        "Signature should match the data"
+    );
+    assert!(checkout["url"].as_str().unwrap().ends_with(&format!(
+        "?data={data}&signature={}",
+        checkout["signature"].as_str().unwrap()
+    )));
+
+    let (order_id, ..) = app
+        .get_checkouts()
+        .await
+        .next()
+        .expect("Checkout should be recorded");
+    assert_eq!(checkout["order_id"], order_id);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn embedded_campaign_checkout_fails_with_currency_not_allowed() {
+    let app = helpers::spawn_app().await;
+    app.insert_campaign("winter", "Warm clothes for the winter", &["UAH"], None)
+        .await;
+
+    let response = app
+        .test_client
+        .get(app.test_server_url(
+            "/pay/c/winter/checkout?amount=100&action=pay&currency=USD&language=en",
+        ))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 422);
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap()["field"],
+        "currency"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn embedded_checkout_with_stripe_has_no_signed_data() {
+    let app = helpers::spawn_app().await;
+    stripe_create_checkout_session_mock()
+        .expect(1)
+        .mount(&app.stripe_server)
+        .await;
+
+    let checkout = app
+        .test_client
+        .get(app.test_server_url("/pay/checkout?amount=123&action=pay&currency=EUR&language=en"))
+        .send()
+        .await
+        .expect("Failed to execute the request")
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        checkout,
+        json!({
+            "provider": "stripe",
+            "order_id": checkout["order_id"],
+            "url": "https://checkout.stripe.com/c/pay/cs_test_1234"
+        })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_with_stripe_redirects_to_checkout_session() {
+    let app = helpers::spawn_app().await;
+    stripe_create_checkout_session_mock()
+        .expect(2)
+        .mount(&app.stripe_server)
+        .await;
+
+    for query in [
+        "/pay?amount=123&action=pay&currency=EUR&language=en",
+        "/pay?amount=123&action=pay&currency=USD&language=en&provider=stripe",
+    ] {
+        let response = create_test_server_client_no_redirect()
+            .get(app.test_server_url(query))
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        assert!(
+            response.status().is_redirection(),
+            "Response should be a redirection"
+        );
+        assert_eq!(
+            response.headers()["location"],
+            "https://checkout.stripe.com/c/pay/cs_test_1234"
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_maps_storefront_locale_to_checkout_language() {
+    let app = helpers::spawn_app().await;
+    app.insert_campaign("general", "General", &["UAH", "PLN"], None)
+        .await;
+    stripe_create_checkout_session_mock()
+        .expect(1)
+        .mount(&app.stripe_server)
+        .await;
+
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url("/pay?amount=100&action=pay&currency=PLN&language=pl-PL"))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(
+        response.status().is_redirection(),
+        "Currencies LiqPay does not accept should be checked out with Stripe"
+    );
+    let request = &app.stripe_server.received_requests().await.unwrap()[0];
+    let body = String::from_utf8_lossy(&request.body);
+    assert!(body.contains("locale=pl"));
+    assert!(body.contains("%5Bcurrency%5D=pln"));
+
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url("/pay?amount=100&action=pay&currency=UAH&language=de_DE"))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(
+        helpers::decode_liq_pay_checkout(&response)["language"],
+        "en",
+        "LiqPay checkout should fall back to English"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_fails_for_unavailable_language_or_currency() {
+    let app = helpers::spawn_app().await;
+    app.insert_campaign("general", "General", &["UAH", "PLN", "RON"], None)
+        .await;
+
+    for (query, expected) in [
+        (
+            "/pay?amount=100&action=pay&currency=RON&language=ro",
+            json!({
+                "error": "invalid_input",
+                "field": "currency",
+                "message": "RON is not accepted."
+            }),
+        ),
+        (
+            "/pay?amount=100&action=pay&currency=PLN&language=pl&provider=liqpay",
+            json!({
+                "error": "unsupported_checkout",
+                "field": "provider",
+                "message": "This currency is only supported by Stripe."
+            }),
+        ),
+    ] {
+        let response = create_test_server_client_no_redirect()
+            .get(app.test_server_url(query))
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        assert_eq!(response.status().as_u16(), 422);
+        assert_eq!(
+            response.json::<serde_json::Value>().await.unwrap(),
+            expected
+        );
+    }
+
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url("/pay?amount=100&action=pay&currency=UAH&language=fr-FR"))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_with_stripe_fails_for_subscriptions() {
+    let app = helpers::spawn_app().await;
+
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url(
+            "/pay?amount=123&action=subscribe&currency=USD&language=en&provider=stripe",
+        ))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 422);
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap(),
+        json!({
+            "error": "unsupported_checkout",
+            "field": "provider",
+            "message": "Recurring donations are only supported by LiqPay."
+        })
+    );
+    assert_eq!(app.get_subscriptions().await.count(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stripe_callback_records_donation() {
+    let app = helpers::spawn_app().await;
+    let body = json!({
+        "type": "checkout.session.completed",
+        "data": {
+            "object": {
+                "client_reference_id": "order-1",
+                "amount_total": 10050,
+                "currency": "eur",
+                "payment_status": "paid"
+            }
+        }
+    })
+    .to_string();
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/pay/callback/stripe"))
+        .header("Stripe-Signature", app.generate_stripe_signature(&body))
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_success());
+    assert_eq!(
+        app.get_donations().await.collect::<Vec<_>>(),
+        vec![(
+            "order-1".to_owned(),
+            "success".to_owned(),
+            100.5,
+            "EUR".to_owned(),
+            "stripe".to_owned()
+        )]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stripe_callback_fails_with_invalid_signature() {
+    let app = helpers::spawn_app().await;
+    let body = json!({
+        "type": "checkout.session.completed",
+        "data": {
+            "object": {
+                "client_reference_id": "order-1",
+                "amount_total": 10050,
+                "currency": "eur",
+                "payment_status": "paid"
+            }
+        }
+    })
+    .to_string();
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/pay/callback/stripe"))
+        .header("Stripe-Signature", app.generate_stripe_signature("other"))
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(app.get_donations().await.count(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pay_callback_records_subscription() {
+    let app = helpers::spawn_app().await;
+    let payload = json!({
+        "order_id": "order-1",
+        "payment_id": 1234,
+        "action": "subscribe",
+        "status": "subscribed",
+        "amount": 100.0,
+        "currency": "UAH"
+    });
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/pay/callback"))
+        .form(&app.generate_liq_pay_callback(&payload))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_success());
+
+    let subscriptions = app.get_subscriptions().await.collect::<Vec<_>>();
+    assert_eq!(subscriptions.len(), 1);
+    assert_eq!(subscriptions[0].0, "order-1");
+    assert_eq!(subscriptions[0].1, "subscribed");
+    assert!(
+        subscriptions[0].2.is_some(),
+        "Next charge should be scheduled"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unsubscribe_request_cancels_subscription() {
+    let app = helpers::spawn_app().await;
+    let payload = json!({
+        "order_id": "order-1",
+        "payment_id": 1234,
+        "action": "subscribe",
+        "status": "subscribed",
+        "amount": 100.0,
+        "currency": "UAH"
+    });
+    app.test_client
+        .post(app.test_server_url("/pay/callback"))
+        .form(&app.generate_liq_pay_callback(&payload))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    liq_pay_unsubscribe_mock()
+        .expect(1)
+        .mount(&app.liq_pay_server)
+        .await;
+
+    let token = create_unsubscribe_token("order-1", &app.jwt_secret).unwrap();
+
+    let response = app
+        .test_client
+        .get(app.test_server_url("/pay/unsubscribe"))
+        .query(&[("token", &token)])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_success());
+    assert!(response.text().await.unwrap().contains(&token));
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/pay/unsubscribe"))
+        .form(&[("token", &token)])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_success());
+    assert_eq!(
+        app.get_subscriptions().await.collect::<Vec<_>>(),
+        vec![("order-1".to_owned(), "unsubscribed".to_owned(), None)]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscription_receipt_links_to_cancellation() {
+    let app = helpers::spawn_app().await;
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url(
+            "/pay?amount=100&action=subscribe&periodicity=week&currency=UAH&language=en&email=donor%40example.com",
+        ))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+    let order_id = helpers::decode_liq_pay_checkout(&response)["order_id"].clone();
+
+    app.send_liq_pay_callback(&json!({
+        "order_id": order_id,
+        "payment_id": 1234,
+        "action": "regular",
+        "status": "success",
+        "amount": 100.0,
+        "currency": "UAH"
+    }))
+    .await;
+
+    // the link is longer than a line so the body is sent as quoted-printable
+    let message = app.smtp_sink.wait_for_messages(1).await[0]
+        .replace("=\r\n", "")
+        .replace("=\n", "")
+        .replace("=3D", "=");
+    let link = message
+        .split_whitespace()
+        .find(|word| word.contains("/pay/unsubscribe?token="))
+        .expect("Receipt should link to the cancellation page")
+        .to_owned();
+    assert!(link.starts_with(&app.base_url));
+    let token = link.split("token=").nth(1).unwrap();
+
+    liq_pay_unsubscribe_mock()
+        .expect(1)
+        .mount(&app.liq_pay_server)
+        .await;
+
+    let response = app
+        .test_client
+        .get(app.test_server_url("/pay/unsubscribe"))
+        .query(&[("token", token)])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_success());
+    let page = response.text().await.unwrap();
+    assert!(page.contains("Cancel your recurring donation?"));
+    assert!(page.contains("Frequency: Weekly"));
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/pay/unsubscribe"))
+        .form(&[("token", token)])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("Your recurring donation has been cancelled."));
+    assert_eq!(
+        app.get_subscriptions()
+            .await
+            .map(|(_, status, _)| status)
+            .collect::<Vec<_>>(),
+        vec!["unsubscribed".to_owned()]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unsubscribe_request_fails_with_invalid_token_or_unknown_subscription() {
+    let app = helpers::spawn_app().await;
+
+    liq_pay_unsubscribe_mock()
+        .expect(0)
+        .mount(&app.liq_pay_server)
+        .await;
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/pay/unsubscribe"))
+        .form(&[("token", app.generate_local_jwt_token())])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let token = create_unsubscribe_token("order-1", &app.jwt_secret).unwrap();
+    let response = app
+        .test_client
+        .post(app.test_server_url("/pay/unsubscribe"))
+        .form(&[("token", &token)])
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert_eq!(response.status().as_u16(), 404);
+}
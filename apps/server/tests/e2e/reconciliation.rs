@@ -0,0 +1,126 @@
+use serde_json::json;
+use swu_app::{
+    configuration::Reconciliation,
+    liq_pay::Currency,
+    payments::Payments,
+    reconciliation::{reconcile, Summary},
+};
+use time::{Duration, OffsetDateTime};
+
+use crate::{helpers, mocks::liq_pay_status_mock};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconcile_records_payments_of_pending_checkouts() {
+    let app = helpers::spawn_app().await;
+    let now = OffsetDateTime::now_utc();
+
+    app.insert_checkout("order-lost", now - Duration::hours(2))
+        .await;
+    app.insert_checkout("order-recent", now - Duration::minutes(5))
+        .await;
+    app.insert_checkout("order-abandoned", now - Duration::days(7))
+        .await;
+    app.insert_checkout("order-paid", now - Duration::hours(2))
+        .await;
+    app.send_liq_pay_callback(&json!({
+        "order_id": "order-paid",
+        "payment_id": 1,
+        "action": "pay",
+        "status": "success",
+        "amount": 50.0,
+        "currency": "USD"
+    }))
+    .await;
+
+    liq_pay_status_mock(json!({
+        "order_id": "order-lost",
+        "payment_id": 2,
+        "action": "pay",
+        "status": "success",
+        "amount": 100.0,
+        "currency": "UAH"
+    }))
+    .expect(1)
+    .mount(&app.liq_pay_server)
+    .await;
+
+    let summary = reconcile(
+        now,
+        &Reconciliation {
+            pending_after_minutes: 60,
+            lookback_hours: 72,
+        },
+        &Payments::new(
+            app.liq_pay_client.clone(),
+            app.stripe_client.clone(),
+            vec![Currency::EUR],
+        ),
+        &app.jwt_secret,
+        &app.db_pool,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        summary,
+        Summary {
+            checked: 1,
+            recorded: 1,
+            failed: 0
+        }
+    );
+    assert_eq!(
+        app.get_donations().await.collect::<Vec<_>>(),
+        vec![
+            (
+                "order-lost".to_owned(),
+                "success".to_owned(),
+                100.0,
+                "UAH".to_owned(),
+                "liqpay".to_owned()
+            ),
+            (
+                "order-paid".to_owned(),
+                "success".to_owned(),
+                50.0,
+                "USD".to_owned(),
+                "liqpay".to_owned()
+            )
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconcile_keeps_checkouts_pending_when_lookup_fails() {
+    let app = helpers::spawn_app().await;
+    let now = OffsetDateTime::now_utc();
+    app.insert_checkout("order-lost", now - Duration::hours(2))
+        .await;
+
+    let summary = reconcile(
+        now,
+        &Reconciliation {
+            pending_after_minutes: 60,
+            lookback_hours: 72,
+        },
+        &Payments::new(
+            app.liq_pay_client.clone(),
+            app.stripe_client.clone(),
+            vec![Currency::EUR],
+        ),
+        &app.jwt_secret,
+        &app.db_pool,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        summary,
+        Summary {
+            checked: 1,
+            recorded: 0,
+            failed: 1
+        }
+    );
+    assert_eq!(app.get_donations().await.count(), 0);
+}
@@ -12,7 +12,7 @@ async fn save_widget_configuration_fails_with_invalid_config() {
 
     let response = app
         .test_client
-        .post(&app.test_server_url("/api/v1/configuration"))
+        .post(app.test_server_url("/api/v1/configuration"))
         .bearer_auth(app.generate_local_jwt_token())
         .json(&bad_configuration)
         .send()
@@ -28,7 +28,7 @@ async fn save_widget_configuration_fails_when_store_not_defined() {
 
     let response = app
         .test_client
-        .post(&app.test_server_url("/api/v1/configuration"))
+        .post(app.test_server_url("/api/v1/configuration"))
         .bearer_auth(app.generate_local_jwt_token())
         .json(&get_widget_configuration())
         .send()
@@ -44,7 +44,7 @@ async fn read_widget_configuration_fails_with_no_store() {
 
     let response = app
         .test_client
-        .get(&app.test_server_url("/api/v1/configuration"))
+        .get(app.test_server_url("/api/v1/configuration"))
         .bearer_auth(app.generate_local_jwt_token())
         .send()
         .await
@@ -61,7 +61,7 @@ async fn save_and_read_widget_configuration() {
 
     let response = app
         .test_client
-        .post(&app.test_server_url("/api/v1/configuration"))
+        .post(app.test_server_url("/api/v1/configuration"))
         .bearer_auth(app.generate_local_jwt_token())
         .json(&get_widget_configuration())
         .send()
@@ -97,7 +97,7 @@ async fn save_and_read_widget_configuration() {
 
     let response_widget_configuration = app
         .test_client
-        .get(&app.test_server_url("/api/v1/configuration"))
+        .get(app.test_server_url("/api/v1/configuration"))
         .bearer_auth(app.generate_local_jwt_token())
         .send()
         .await
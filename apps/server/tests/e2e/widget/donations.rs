@@ -0,0 +1,236 @@
+use secrecy::ExposeSecret;
+use serde_json::{json, Value};
+
+use crate::helpers::{self, create_test_server_client_no_redirect, TestApp};
+
+async fn record_donation(app: &TestApp, order_id: &str, status: &str, amount: f64, currency: &str) {
+    let response = app
+        .test_client
+        .post(app.test_server_url("/pay/callback"))
+        .form(&app.generate_liq_pay_callback(&json!({
+            "order_id": order_id,
+            "payment_id": 1234,
+            "action": "pay",
+            "status": status,
+            "amount": amount,
+            "currency": currency
+        })))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_success());
+}
+
+async fn get_summary(app: &TestApp, query: &[(&str, &str)]) -> Value {
+    let response = app
+        .test_client
+        .get(app.test_server_url("/api/v2/donations/summary"))
+        .query(query)
+        .header("Origin", "https://store.example.com")
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_success());
+    assert_eq!(response.headers()["access-control-allow-origin"], "*");
+    assert_eq!(response.headers()["cache-control"], "public, max-age=60");
+
+    response.json().await.unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn donation_summary_totals_confirmed_donations() {
+    let app = helpers::spawn_app().await;
+
+    let response = create_test_server_client_no_redirect()
+        .get(app.test_server_url(
+            "/pay?amount=50&action=pay&currency=USD&language=en&store_hash=store1",
+        ))
+        .send()
+        .await
+        .expect("Failed to execute the request");
+    let referred_order_id = helpers::decode_liq_pay_checkout(&response)["order_id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    record_donation(&app, &referred_order_id, "success", 50.0, "USD").await;
+    record_donation(&app, "order-1", "success", 100.0, "UAH").await;
+    record_donation(&app, "order-2", "success", 150.0, "UAH").await;
+    record_donation(&app, "order-3", "failure", 200.0, "UAH").await;
+    record_donation(&app, "order-4", "success", 300.0, "UAH").await;
+    record_donation(&app, "order-4", "reversed", 300.0, "UAH").await;
+
+    assert_eq!(
+        get_summary(&app, &[]).await,
+        json!({
+            "totals": [
+                {"currency": "UAH", "amount": 250.0, "donations": 2},
+                {"currency": "USD", "amount": 50.0, "donations": 1}
+            ]
+        })
+    );
+    assert_eq!(
+        get_summary(&app, &[("store_hash", "store1")]).await,
+        json!({
+            "totals": [
+                {"currency": "USD", "amount": 50.0, "donations": 1}
+            ]
+        })
+    );
+    assert_eq!(
+        get_summary(&app, &[("campaign", "general")]).await,
+        get_summary(&app, &[]).await,
+        "Donations without a campaign belong to the general campaign"
+    );
+    app.insert_campaign("winter", "Warm clothes for the winter", &["UAH"], None)
+        .await;
+    assert_eq!(
+        get_summary(&app, &[("campaign", "winter")]).await,
+        json!({ "totals": [] })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn donation_summary_rejects_unknown_campaign_and_invalid_store_hash() {
+    let app = helpers::spawn_app().await;
+
+    for (query, status) in [
+        (("campaign", "unknown"), 404),
+        (("store_hash", "not a store"), 400),
+        (("store_hash", "a-store-hash-that-is-far-too-long"), 400),
+    ] {
+        let response = app
+            .test_client
+            .get(app.test_server_url("/api/v2/donations/summary"))
+            .query(&[query])
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        assert_eq!(response.status().as_u16(), status);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn donation_summary_totals_every_subscription_charge() {
+    let app = helpers::spawn_app().await;
+
+    for (payment_id, action, status) in [
+        (1, "subscribe", "subscribed"),
+        (2, "regular", "success"),
+        (3, "regular", "success"),
+        // a late callback must not change the outcome of a charge
+        (3, "regular", "failure"),
+        (4, "regular", "failure"),
+        (5, "subscribe", "unsubscribed"),
+    ] {
+        let response = app
+            .send_liq_pay_callback(&json!({
+                "order_id": "order-1",
+                "payment_id": payment_id,
+                "action": action,
+                "status": status,
+                "amount": 100.0,
+                "currency": "UAH"
+            }))
+            .await;
+
+        assert!(response.status().is_success());
+    }
+
+    assert_eq!(
+        get_summary(&app, &[]).await,
+        json!({
+            "totals": [
+                {"currency": "UAH", "amount": 200.0, "donations": 1}
+            ]
+        }),
+        "Charges should be kept after the subscription is cancelled"
+    );
+    assert_eq!(
+        app.get_subscriptions()
+            .await
+            .map(|(_, status, _)| status)
+            .collect::<Vec<_>>(),
+        vec!["unsubscribed".to_owned()]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn donation_summary_normalizes_totals_to_reporting_currency() {
+    let app = helpers::spawn_app().await;
+
+    record_donation(&app, "order-1", "success", 1000.0, "UAH").await;
+    record_donation(&app, "order-2", "success", 20.0, "EUR").await;
+    record_donation(&app, "order-3", "success", 50.0, "USD").await;
+
+    let response = app
+        .test_client
+        .put(app.test_server_url("/admin/exchange-rates"))
+        .bearer_auth(app.admin_api_key.expose_secret())
+        .body("UAH,USD,0.025\n")
+        .send()
+        .await
+        .expect("Failed to execute the request");
+    assert!(response.status().is_success());
+
+    assert_eq!(
+        get_summary(&app, &[("reporting_currency", "USD")]).await["total"],
+        json!({"currency": "USD", "amount": 75.0, "missing_rates": ["EUR"]})
+    );
+    assert_eq!(
+        get_summary(&app, &[("reporting_currency", "UAH")]).await["total"],
+        json!({"currency": "UAH", "amount": 3000.0, "missing_rates": ["EUR"]})
+    );
+    assert!(get_summary(&app, &[]).await.get("total").is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn store_donations_are_attributed_to_widget_charity() {
+    let app = helpers::spawn_app().await;
+
+    for (store_hash, source, amount) in [
+        ("test-store", "razom", "100"),
+        ("test-store", "razom", "50"),
+        ("test-store", "unicef", "20"),
+        ("other-store", "razom", "30"),
+    ] {
+        let response = create_test_server_client_no_redirect()
+            .get(app.test_server_url("/pay?action=pay&currency=USD&language=en"))
+            .query(&[
+                ("amount", amount),
+                ("store_hash", store_hash),
+                ("source", source),
+            ])
+            .send()
+            .await
+            .expect("Failed to execute the request");
+        let order_id = helpers::decode_liq_pay_checkout(&response)["order_id"]
+            .as_str()
+            .unwrap()
+            .to_owned();
+
+        record_donation(&app, &order_id, "success", amount.parse().unwrap(), "USD").await;
+    }
+
+    let response = app
+        .test_client
+        .get(app.test_server_url("/api/v1/donations"))
+        .bearer_auth(app.generate_local_jwt_token())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.json::<Value>().await.unwrap(),
+        json!({
+            "totals": [
+                {"source": "razom", "currency": "USD", "amount": 150.0, "donations": 2},
+                {"source": "unicef", "currency": "USD", "amount": 20.0, "donations": 1}
+            ]
+        })
+    );
+}
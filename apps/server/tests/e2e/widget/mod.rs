@@ -1,3 +1,4 @@
 pub mod analytics;
 pub mod configuration;
+pub mod donations;
 pub mod publish;
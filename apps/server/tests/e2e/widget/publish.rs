@@ -1,10 +1,17 @@
-use swu_app::{bigcommerce::store::Information, data::StoreStatus};
+use serde_json::json;
+use swu_app::{
+    authentication::create_unsubscribe_token, bigcommerce::store::Information, data::StoreStatus,
+};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
 
 use crate::{
     helpers::{create_test_server_client_no_redirect, get_widget_configuration, spawn_app},
     mocks::{
-        create_script_mock, delete_script_mock, get_scripts_mock, get_store_information_mock,
-        update_script_mock,
+        create_script_mock, delete_script_mock, get_scripts_mock, get_scripts_page_mock,
+        get_store_information_mock, scripts_error_mock, update_script_mock,
     },
 };
 
@@ -14,7 +21,7 @@ async fn widget_publish_request_fails_without_token_or_with_invalid_token() {
 
     let response = app
         .test_client
-        .post(&app.test_server_url("/api/v1/configuration"))
+        .post(app.test_server_url("/api/v1/configuration"))
         .json(&get_widget_configuration())
         .send()
         .await
@@ -24,7 +31,7 @@ async fn widget_publish_request_fails_without_token_or_with_invalid_token() {
 
     let response = app
         .test_client
-        .post(&app.test_server_url("/api/v1/configuration"))
+        .post(app.test_server_url("/api/v1/configuration"))
         .bearer_auth("test-token")
         .json(&get_widget_configuration())
         .send()
@@ -32,6 +39,18 @@ async fn widget_publish_request_fails_without_token_or_with_invalid_token() {
         .unwrap();
 
     assert!(response.status().is_client_error());
+
+    // cancellation links are signed with the same secret but must not open the dashboard API
+    let response = app
+        .test_client
+        .post(app.test_server_url("/api/v1/configuration"))
+        .bearer_auth(create_unsubscribe_token("test-store", &app.jwt_secret).unwrap())
+        .json(&get_widget_configuration())
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
 }
 
 #[tokio::test(flavor = "multi_thread")]
@@ -41,7 +60,7 @@ async fn widget_publish_request_succeeds() {
     app.insert_test_store().await;
 
     app.test_client
-        .post(&app.test_server_url("/api/v1/configuration"))
+        .post(app.test_server_url("/api/v1/configuration"))
         .bearer_auth(app.generate_local_jwt_token())
         .json(&get_widget_configuration())
         .send()
@@ -50,7 +69,7 @@ async fn widget_publish_request_succeeds() {
 
     let response = app
         .test_client
-        .get(&app.test_server_url("/api/v1/publish"))
+        .get(app.test_server_url("/api/v1/publish"))
         .bearer_auth(app.generate_local_jwt_token())
         .send()
         .await
@@ -75,7 +94,7 @@ async fn widget_publish_request_succeeds() {
 
         let response = app
             .test_client
-            .post(&app.test_server_url("/api/v1/publish"))
+            .post(app.test_server_url("/api/v1/publish"))
             .bearer_auth(app.generate_local_jwt_token())
             .send()
             .await
@@ -86,7 +105,7 @@ async fn widget_publish_request_succeeds() {
 
     let response = app
         .test_client
-        .get(&app.test_server_url("/api/v1/publish"))
+        .get(app.test_server_url("/api/v1/publish"))
         .bearer_auth(app.generate_local_jwt_token())
         .send()
         .await
@@ -97,6 +116,13 @@ async fn widget_publish_request_succeeds() {
 
     assert!(response.published);
 
+    let row =
+        sqlx::query!("SELECT published_changed_by FROM stores WHERE store_hash = 'test-store'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(row.published_changed_by, Some(1));
+
     // second publish - should use put request to bc to update existing script
     {
         let _get_guard = get_scripts_mock(true)
@@ -111,7 +137,7 @@ async fn widget_publish_request_succeeds() {
 
         let response = app
             .test_client
-            .post(&app.test_server_url("/api/v1/publish"))
+            .post(app.test_server_url("/api/v1/publish"))
             .bearer_auth(app.generate_local_jwt_token())
             .send()
             .await
@@ -121,6 +147,222 @@ async fn widget_publish_request_succeeds() {
     }
 }
 
+fn script(uuid: &str, name: &str) -> serde_json::Value {
+    json!({
+        "uuid": uuid,
+        "api_client_id": "test-client-id",
+        "enabled": true,
+        "channel_id": 1,
+        "name": name
+    })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn widget_publish_request_finds_script_on_later_page() {
+    let app = spawn_app().await;
+
+    app.insert_test_store().await;
+    app.test_client
+        .post(app.test_server_url("/api/v1/configuration"))
+        .bearer_auth(app.generate_local_jwt_token())
+        .json(&get_widget_configuration())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    get_scripts_page_mock(1, 2, json!([script("other-script", "Other App")]))
+        .expect(1)
+        .mount(&app.bigcommerce_server)
+        .await;
+    get_scripts_page_mock(
+        2,
+        2,
+        json!([script(
+            "095be615-a8ad-4c33-8e9c-c7612fbf6c9f",
+            "Stand With Ukraine"
+        )]),
+    )
+    .expect(1)
+    .mount(&app.bigcommerce_server)
+    .await;
+    update_script_mock()
+        .expect(1)
+        .mount(&app.bigcommerce_server)
+        .await;
+    create_script_mock()
+        .expect(0)
+        .mount(&app.bigcommerce_server)
+        .await;
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/api/v1/publish"))
+        .bearer_auth(app.generate_local_jwt_token())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_success());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn widget_remove_request_removes_scripts_on_every_page() {
+    let app = spawn_app().await;
+
+    app.insert_test_store().await;
+
+    get_scripts_page_mock(1, 2, json!([script("first-script", "Stand With Ukraine")]))
+        .mount(&app.bigcommerce_server)
+        .await;
+    get_scripts_page_mock(
+        2,
+        2,
+        json!([script(
+            "095be615-a8ad-4c33-8e9c-c7612fbf6c9f",
+            "Stand With Ukraine"
+        )]),
+    )
+    .mount(&app.bigcommerce_server)
+    .await;
+    delete_script_mock()
+        .expect(1)
+        .mount(&app.bigcommerce_server)
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path("/stores/test-store/v3/content/scripts/first-script"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&app.bigcommerce_server)
+        .await;
+
+    let response = app
+        .test_client
+        .delete(app.test_server_url("/api/v1/publish"))
+        .bearer_auth(app.generate_local_jwt_token())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_success());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn widget_publish_request_stops_on_empty_scripts_page() {
+    let app = spawn_app().await;
+
+    app.insert_test_store().await;
+    app.test_client
+        .post(app.test_server_url("/api/v1/configuration"))
+        .bearer_auth(app.generate_local_jwt_token())
+        .json(&get_widget_configuration())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    get_scripts_page_mock(1, 1000, json!([script("other-script", "Other App")]))
+        .expect(1)
+        .mount(&app.bigcommerce_server)
+        .await;
+    get_scripts_page_mock(2, 1000, json!([]))
+        .expect(1)
+        .mount(&app.bigcommerce_server)
+        .await;
+    get_scripts_page_mock(3, 1000, json!([]))
+        .expect(0)
+        .mount(&app.bigcommerce_server)
+        .await;
+    create_script_mock()
+        .expect(1)
+        .mount(&app.bigcommerce_server)
+        .await;
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/api/v1/publish"))
+        .bearer_auth(app.generate_local_jwt_token())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_success());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn widget_publish_request_fails_for_endless_scripts_listing() {
+    let app = spawn_app().await;
+
+    app.insert_test_store().await;
+    app.test_client
+        .post(app.test_server_url("/api/v1/configuration"))
+        .bearer_auth(app.generate_local_jwt_token())
+        .json(&get_widget_configuration())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    // every page claims to be the first one of many
+    Mock::given(method("GET"))
+        .and(path("/stores/test-store/v3/content/scripts"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [script("other-script", "Other App")],
+            "meta": {"pagination": {"current_page": 1, "total_pages": 1000}}
+        })))
+        .expect(20)
+        .mount(&app.bigcommerce_server)
+        .await;
+    create_script_mock()
+        .expect(0)
+        .mount(&app.bigcommerce_server)
+        .await;
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/api/v1/publish"))
+        .bearer_auth(app.generate_local_jwt_token())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_server_error());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn widget_publish_request_fails_without_scripts_scope() {
+    let app = spawn_app().await;
+
+    app.insert_test_store().await;
+    sqlx::query!(
+        "UPDATE stores SET scope = 'store_v2_information_read_only' WHERE store_hash = 'test-store'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    get_scripts_mock(false)
+        .expect(0)
+        .mount(&app.bigcommerce_server)
+        .await;
+
+    for request in [
+        app.test_client.post(app.test_server_url("/api/v1/publish")),
+        app.test_client
+            .delete(app.test_server_url("/api/v1/publish")),
+    ] {
+        let response = request
+            .bearer_auth(app.generate_local_jwt_token())
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        assert_eq!(response.status().as_u16(), 403);
+        assert!(response
+            .text()
+            .await
+            .unwrap()
+            .contains("reinstall it with the scripts scope"));
+    }
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn widget_publish_request_fails_without_configuration_saved() {
     let app = spawn_app().await;
@@ -128,7 +370,7 @@ async fn widget_publish_request_fails_without_configuration_saved() {
     app.insert_test_store().await;
 
     app.test_client
-        .post(&app.test_server_url("/api/v1/configuration"))
+        .post(app.test_server_url("/api/v1/configuration"))
         .bearer_auth(app.generate_local_jwt_token())
         .send()
         .await
@@ -136,7 +378,7 @@ async fn widget_publish_request_fails_without_configuration_saved() {
 
     let response = app
         .test_client
-        .post(&app.test_server_url("/api/v1/publish"))
+        .post(app.test_server_url("/api/v1/publish"))
         .bearer_auth(app.generate_local_jwt_token())
         .send()
         .await
@@ -152,16 +394,103 @@ async fn widget_publish_request_fails_without_bigcommerce_server_response() {
     app.insert_test_store().await;
 
     app.test_client
-        .post(&app.test_server_url("/api/v1/configuration"))
+        .post(app.test_server_url("/api/v1/configuration"))
+        .bearer_auth(app.generate_local_jwt_token())
+        .json(&get_widget_configuration())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/api/v1/publish"))
+        .bearer_auth(app.generate_local_jwt_token())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_server_error());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn widget_publish_request_retries_rate_limited_and_unavailable_requests() {
+    let app = spawn_app().await;
+
+    app.insert_test_store().await;
+    app.test_client
+        .post(app.test_server_url("/api/v1/configuration"))
+        .bearer_auth(app.generate_local_jwt_token())
+        .json(&get_widget_configuration())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    scripts_error_mock("GET", 503)
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.bigcommerce_server)
+        .await;
+    get_scripts_mock(false)
+        .expect(1)
+        .mount(&app.bigcommerce_server)
+        .await;
+    // creating a script is only repeated when BigCommerce did not accept it
+    scripts_error_mock("POST", 429)
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.bigcommerce_server)
+        .await;
+    create_script_mock()
+        .expect(1)
+        .mount(&app.bigcommerce_server)
+        .await;
+
+    let response = app
+        .test_client
+        .post(app.test_server_url("/api/v1/publish"))
+        .bearer_auth(app.generate_local_jwt_token())
+        .send()
+        .await
+        .expect("Failed to execute the request");
+
+    assert!(response.status().is_success());
+
+    let response = app
+        .test_client
+        .get(app.test_server_url("/api/v1/publish"))
+        .bearer_auth(app.generate_local_jwt_token())
+        .send()
+        .await
+        .expect("Failed to execute the request")
+        .json::<StoreStatus>()
+        .await
+        .expect("Invalid response format");
+
+    assert!(response.published);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn widget_publish_request_fails_when_bigcommerce_stays_unavailable() {
+    let app = spawn_app().await;
+
+    app.insert_test_store().await;
+    app.test_client
+        .post(app.test_server_url("/api/v1/configuration"))
         .bearer_auth(app.generate_local_jwt_token())
         .json(&get_widget_configuration())
         .send()
         .await
         .expect("Failed to execute the request");
 
+    // the first attempt and three retries
+    scripts_error_mock("GET", 503)
+        .expect(4)
+        .mount(&app.bigcommerce_server)
+        .await;
+
     let response = app
         .test_client
-        .post(&app.test_server_url("/api/v1/publish"))
+        .post(app.test_server_url("/api/v1/publish"))
         .bearer_auth(app.generate_local_jwt_token())
         .send()
         .await
@@ -170,13 +499,46 @@ async fn widget_publish_request_fails_without_bigcommerce_server_response() {
     assert!(response.status().is_server_error());
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn widget_publish_request_does_not_retry_failed_script_creation() {
+    for status in [500, 503] {
+        let app = spawn_app().await;
+
+        app.insert_test_store().await;
+        app.test_client
+            .post(app.test_server_url("/api/v1/configuration"))
+            .bearer_auth(app.generate_local_jwt_token())
+            .json(&get_widget_configuration())
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        get_scripts_mock(false).mount(&app.bigcommerce_server).await;
+        // the script may have been created, so sending it again could duplicate it
+        scripts_error_mock("POST", status)
+            .expect(1)
+            .mount(&app.bigcommerce_server)
+            .await;
+
+        let response = app
+            .test_client
+            .post(app.test_server_url("/api/v1/publish"))
+            .bearer_auth(app.generate_local_jwt_token())
+            .send()
+            .await
+            .expect("Failed to execute the request");
+
+        assert!(response.status().is_server_error());
+    }
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn get_published_status_fails_without_store() {
     let app = spawn_app().await;
 
     let response = app
         .test_client
-        .get(&app.test_server_url("/api/v1/publish"))
+        .get(app.test_server_url("/api/v1/publish"))
         .bearer_auth(app.generate_local_jwt_token())
         .send()
         .await
@@ -191,7 +553,7 @@ async fn widget_preview_request_fails_without_store() {
 
     let response = app
         .test_client
-        .get(&app.test_server_url("/api/v1/preview"))
+        .get(app.test_server_url("/api/v1/preview"))
         .bearer_auth(app.generate_local_jwt_token())
         .send()
         .await
@@ -213,7 +575,7 @@ async fn widget_preview_request_succeeds() {
         .await;
 
     let response = client
-        .get(&app.test_server_url("/api/v1/preview"))
+        .get(app.test_server_url("/api/v1/preview"))
         .bearer_auth(app.generate_local_jwt_token())
         .send()
         .await
@@ -234,7 +596,7 @@ async fn widget_remove_request_fails_without_store() {
 
     let response = app
         .test_client
-        .delete(&app.test_server_url("/api/v1/publish"))
+        .delete(app.test_server_url("/api/v1/publish"))
         .bearer_auth(app.generate_local_jwt_token())
         .send()
         .await
@@ -261,7 +623,7 @@ async fn widget_remove_request_succeeds() {
 
     let response = app
         .test_client
-        .delete(&app.test_server_url("/api/v1/publish"))
+        .delete(app.test_server_url("/api/v1/publish"))
         .bearer_auth(app.generate_local_jwt_token())
         .send()
         .await
@@ -271,7 +633,7 @@ async fn widget_remove_request_succeeds() {
 
     let response = app
         .test_client
-        .get(&app.test_server_url("/api/v1/publish"))
+        .get(app.test_server_url("/api/v1/publish"))
         .bearer_auth(app.generate_local_jwt_token())
         .send()
         .await
@@ -301,7 +663,7 @@ async fn widget_remove_request_with_feedback_succeeds() {
 
     let response = app
         .test_client
-        .delete(&app.test_server_url("/api/v1/publish"))
+        .delete(app.test_server_url("/api/v1/publish"))
         .query(&[("reason", "I did not like the design!")])
         .bearer_auth(app.generate_local_jwt_token())
         .send()
@@ -312,7 +674,7 @@ async fn widget_remove_request_with_feedback_succeeds() {
 
     let response = app
         .test_client
-        .get(&app.test_server_url("/api/v1/publish"))
+        .get(app.test_server_url("/api/v1/publish"))
         .bearer_auth(app.generate_local_jwt_token())
         .send()
         .await
@@ -324,7 +686,7 @@ async fn widget_remove_request_with_feedback_succeeds() {
     assert!(!response.published);
 
     let rows = sqlx::query!(
-        "SELECT reason FROM unpublish_events WHERE store_hash = $1",
+        "SELECT reason, user_id FROM unpublish_events WHERE store_hash = $1",
         "test-store"
     )
     .fetch_all(&app.db_pool)
@@ -333,4 +695,9 @@ async fn widget_remove_request_with_feedback_succeeds() {
 
     assert_eq!(rows.len(), 1);
     assert_eq!(rows[0].reason, "I did not like the design!");
+    assert_eq!(
+        rows[0].user_id,
+        Some(1),
+        "The staff member should be recorded"
+    );
 }
@@ -0,0 +1,11 @@
+-- Create the donations ledger fed by liq pay callbacks
+CREATE TABLE donations(
+	order_id VARCHAR(50) PRIMARY KEY,
+	payment_id BIGINT,
+	action VARCHAR(25) NOT NULL,
+	status VARCHAR(25) NOT NULL,
+	amount DOUBLE PRECISION NOT NULL,
+	currency VARCHAR(3) NOT NULL,
+	created_at timestamptz NOT NULL,
+	updated_at timestamptz NOT NULL
+);
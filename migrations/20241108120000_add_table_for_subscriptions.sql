@@ -0,0 +1,10 @@
+-- Track recurring donations so they can be cancelled
+CREATE TABLE subscriptions(
+	order_id VARCHAR(50) PRIMARY KEY,
+	status VARCHAR(25) NOT NULL,
+	amount DOUBLE PRECISION NOT NULL,
+	currency VARCHAR(3) NOT NULL,
+	next_charge_at timestamptz,
+	created_at timestamptz NOT NULL,
+	updated_at timestamptz NOT NULL
+);
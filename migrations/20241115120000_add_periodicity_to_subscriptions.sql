@@ -0,0 +1,3 @@
+-- Record how often a subscription is charged
+ALTER TABLE subscriptions
+ADD periodicity VARCHAR(10) NOT NULL DEFAULT 'month';
@@ -0,0 +1,3 @@
+-- Record which payment provider processed a donation
+ALTER TABLE donations
+ADD provider VARCHAR(25) NOT NULL DEFAULT 'liqpay';
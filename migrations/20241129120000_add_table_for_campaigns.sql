@@ -0,0 +1,10 @@
+-- Configure fundraisers without redeploying
+CREATE TABLE campaigns(
+	slug VARCHAR(50) PRIMARY KEY,
+	description TEXT NOT NULL,
+	currencies VARCHAR(3)[] NOT NULL,
+	preset_amounts DOUBLE PRECISION[] NOT NULL,
+	starts_at timestamptz,
+	ends_at timestamptz,
+	created_at timestamptz NOT NULL DEFAULT now()
+);
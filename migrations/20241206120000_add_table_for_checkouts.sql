@@ -0,0 +1,11 @@
+-- Remember what each checkout was made for so donations can be attributed
+CREATE TABLE checkouts(
+	order_id VARCHAR(50) PRIMARY KEY,
+	provider VARCHAR(25) NOT NULL,
+	campaign VARCHAR(50) NOT NULL,
+	store_hash VARCHAR(25),
+	created_at timestamptz NOT NULL
+);
+
+CREATE INDEX idx_checkouts_campaign ON checkouts(campaign);
+CREATE INDEX idx_checkouts_store_hash ON checkouts(store_hash);
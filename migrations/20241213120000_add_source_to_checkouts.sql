@@ -0,0 +1,3 @@
+-- Record which widget charity referred the donor
+ALTER TABLE checkouts
+ADD source VARCHAR(25);
@@ -0,0 +1,15 @@
+-- Keep an audit trail of every status a donation went through, including refunds
+CREATE TABLE donation_status_changes(
+	id bigserial PRIMARY KEY,
+	order_id VARCHAR(50) NOT NULL references donations(order_id),
+	previous_status VARCHAR(25),
+	status VARCHAR(25) NOT NULL,
+	amount DOUBLE PRECISION NOT NULL,
+	err_code VARCHAR(50),
+	created_at timestamptz NOT NULL
+);
+
+CREATE INDEX idx_donation_status_changes_order_id ON donation_status_changes(order_id);
+
+INSERT INTO donation_status_changes (order_id, status, amount, created_at)
+SELECT order_id, status, amount, updated_at FROM donations;
@@ -0,0 +1,8 @@
+-- Exchange rates entered by finance to report donations in a single currency
+CREATE TABLE exchange_rates(
+	currency VARCHAR(3) NOT NULL,
+	base_currency VARCHAR(3) NOT NULL,
+	rate DOUBLE PRECISION NOT NULL,
+	updated_at timestamptz NOT NULL,
+	PRIMARY KEY (currency, base_currency)
+);
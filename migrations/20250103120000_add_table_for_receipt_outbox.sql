@@ -0,0 +1,20 @@
+-- Send donors a receipt for confirmed payments without making callbacks depend on email delivery
+ALTER TABLE checkouts
+ADD receipt_email VARCHAR(254);
+
+CREATE TABLE receipt_outbox(
+	id bigserial PRIMARY KEY,
+	order_id VARCHAR(50) NOT NULL references donations(order_id),
+	payment_id BIGINT NOT NULL,
+	email VARCHAR(254) NOT NULL,
+	amount DOUBLE PRECISION NOT NULL,
+	currency VARCHAR(3) NOT NULL,
+	campaign VARCHAR(50) NOT NULL,
+	paid_at timestamptz NOT NULL,
+	attempts INTEGER NOT NULL DEFAULT 0,
+	last_error TEXT,
+	sent_at timestamptz,
+	UNIQUE (order_id, payment_id)
+);
+
+CREATE INDEX idx_receipt_outbox_unsent ON receipt_outbox(id) WHERE sent_at IS NULL;
@@ -0,0 +1,6 @@
+-- Store metadata kept up to date by BigCommerce webhooks
+ALTER TABLE stores
+ADD name VARCHAR(255),
+ADD secure_url VARCHAR(255),
+ADD status VARCHAR(25),
+ADD information_updated_at timestamptz;
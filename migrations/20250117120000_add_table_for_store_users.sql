@@ -0,0 +1,16 @@
+-- Staff members who opened the app in a store and who changed the widget
+CREATE TABLE store_users(
+	id bigserial PRIMARY KEY,
+	store_hash VARCHAR(25) NOT NULL references stores(store_hash),
+	user_id INTEGER NOT NULL,
+	email VARCHAR(255) NOT NULL,
+	created_at timestamptz NOT NULL,
+	last_seen_at timestamptz NOT NULL,
+	UNIQUE (store_hash, user_id)
+);
+
+ALTER TABLE stores
+ADD published_changed_by INTEGER;
+
+ALTER TABLE unpublish_events
+ADD user_id INTEGER;
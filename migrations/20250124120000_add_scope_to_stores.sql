@@ -0,0 +1,5 @@
+-- Record the scopes granted at install time and who installed the app
+ALTER TABLE stores
+ADD scope TEXT,
+ADD installed_by_user_id INTEGER,
+ADD installed_by_email VARCHAR(255);
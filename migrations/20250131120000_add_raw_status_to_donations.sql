@@ -0,0 +1,15 @@
+-- Keep the status reported by the provider, unknown statuses are otherwise all recorded as pending
+ALTER TABLE donations
+ADD raw_status VARCHAR(50);
+
+ALTER TABLE donation_status_changes
+ADD raw_status VARCHAR(50);
+
+UPDATE donations SET raw_status = status;
+UPDATE donation_status_changes SET raw_status = status;
+
+ALTER TABLE donations
+ALTER COLUMN raw_status SET NOT NULL;
+
+ALTER TABLE donation_status_changes
+ALTER COLUMN raw_status SET NOT NULL;
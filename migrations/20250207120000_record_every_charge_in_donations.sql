@@ -0,0 +1,35 @@
+-- Record every charge of an order so that each payment of a subscription is counted
+ALTER TABLE donation_status_changes
+DROP CONSTRAINT donation_status_changes_order_id_fkey;
+
+ALTER TABLE receipt_outbox
+DROP CONSTRAINT receipt_outbox_order_id_fkey;
+
+-- providers that do not report a payment id only charge an order once
+UPDATE donations SET payment_id = 0 WHERE payment_id IS NULL;
+
+ALTER TABLE donations
+ALTER COLUMN payment_id SET NOT NULL;
+
+ALTER TABLE donations
+DROP CONSTRAINT donations_pkey;
+
+ALTER TABLE donations
+ADD PRIMARY KEY (order_id, payment_id);
+
+ALTER TABLE donation_status_changes
+ADD payment_id BIGINT;
+
+UPDATE donation_status_changes SET payment_id = donations.payment_id
+FROM donations
+WHERE donations.order_id = donation_status_changes.order_id;
+
+ALTER TABLE donation_status_changes
+ALTER COLUMN payment_id SET NOT NULL;
+
+ALTER TABLE donation_status_changes
+ADD FOREIGN KEY (order_id, payment_id) references donations(order_id, payment_id);
+
+-- receipts queued before every charge was recorded may point to a charge that was overwritten since
+ALTER TABLE receipt_outbox
+ADD FOREIGN KEY (order_id, payment_id) references donations(order_id, payment_id) NOT VALID;
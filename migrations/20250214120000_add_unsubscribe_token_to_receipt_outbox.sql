@@ -0,0 +1,3 @@
+-- Give donors with a recurring donation a link to cancel it in every receipt
+ALTER TABLE receipt_outbox
+ADD unsubscribe_token TEXT;
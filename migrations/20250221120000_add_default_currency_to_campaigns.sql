@@ -0,0 +1,8 @@
+-- Preselect a currency on the landing page of a campaign
+ALTER TABLE campaigns
+ADD default_currency VARCHAR(3);
+
+UPDATE campaigns SET default_currency = currencies[1];
+
+ALTER TABLE campaigns
+ALTER COLUMN default_currency SET NOT NULL;